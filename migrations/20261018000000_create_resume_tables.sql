@@ -0,0 +1,52 @@
+-- Every resume that has been stored, live or pending. Rows are never updated, so the older rows of a
+-- variant double as its revision log
+CREATE TABLE resumes (
+    id BIGSERIAL PRIMARY KEY,
+    variant TEXT NOT NULL,
+    overview TEXT NOT NULL,
+    name TEXT,
+    phone TEXT,
+    email TEXT,
+    github TEXT,
+    linkedin TEXT,
+    website TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX resumes_variant_idx ON resumes (variant, id);
+
+CREATE TABLE resume_experiences (
+    resume_id BIGINT NOT NULL REFERENCES resumes (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    company TEXT,
+    title TEXT,
+    duration TEXT,
+    location TEXT,
+    description TEXT[],
+    PRIMARY KEY (resume_id, position)
+);
+
+CREATE TABLE resume_education (
+    resume_id BIGINT NOT NULL REFERENCES resumes (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    college TEXT,
+    degree TEXT,
+    major TEXT,
+    PRIMARY KEY (resume_id, position)
+);
+
+-- One row per skill category, a category the resume doesn't have has no row at all
+CREATE TABLE resume_skills (
+    resume_id BIGINT NOT NULL REFERENCES resumes (id) ON DELETE CASCADE,
+    category TEXT NOT NULL,
+    skills TEXT[] NOT NULL,
+    PRIMARY KEY (resume_id, category)
+);
+
+-- Which stored resume is live and which is pending for each variant
+CREATE TABLE resume_state (
+    variant TEXT PRIMARY KEY,
+    live_resume_id BIGINT REFERENCES resumes (id),
+    pending_resume_id BIGINT REFERENCES resumes (id),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,17 @@
+-- The revision history of each variant. A revision's resume is a row in resumes like any other, the
+-- uploaded PDF is kept with it
+CREATE TABLE resume_revisions (
+    variant TEXT NOT NULL,
+    rev BIGINT NOT NULL,
+    resume_id BIGINT NOT NULL REFERENCES resumes (id),
+    uploaded_at BIGINT NOT NULL,
+    sha256 TEXT NOT NULL,
+    pdf BYTEA,
+    note TEXT,
+    PRIMARY KEY (variant, rev)
+);
+
+-- Which revisions the live and pending resumes came from
+ALTER TABLE resume_state
+    ADD COLUMN live_rev BIGINT,
+    ADD COLUMN pending_rev BIGINT;
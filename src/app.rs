@@ -1,12 +1,10 @@
 #![allow(non_snake_case)]
 
-use std::sync::Arc;
-
 use crate::oauth::oauth_client::*;
-use crate::{server_functions::*, PersonalInfo, ResumeCache};
+use crate::{server_functions::*, PersonalInfo, Resume};
 use leptos::prelude::*;
 use leptos_meta::{provide_meta_context, Stylesheet, Title};
-use leptos_router::{components::*, path};
+use leptos_router::{components::*, hooks::use_query_map, path};
 
 #[component]
 pub fn App() -> impl IntoView {
@@ -26,6 +24,8 @@ pub fn App() -> impl IntoView {
             <main>
                 <Routes fallback=move || "Not found.">
                     <Route path=path!("/") view=HomePage />
+                    <Route path=path!("/link-expired") view=LinkExpired />
+                    <Route path=path!("/login-failed") view=LoginFailed />
                     <Route path=path!("/*any") view=NotFound />
                 </Routes>
             </main>
@@ -35,7 +35,10 @@ pub fn App() -> impl IntoView {
 
 #[component]
 fn HomePage() -> impl IntoView {
-    let resume_result = Resource::new_blocking(|| (), |_| get_resume_info());
+    // `?variant=backend` picks which resume variant is shown, otherwise the default one is used
+    let query = use_query_map();
+    let resume_result =
+        Resource::new_blocking(move || query.read().get("variant"), get_resume_info);
     view! {
         <div class="home-page">
             <div class="parallax">
@@ -88,6 +91,7 @@ fn AboutContainer() -> impl IntoView {
     let logout = ServerAction::<Logout>::new();
     let get_user = Resource::new_blocking(move || (logout.version().get()), |_| get_user_info());
     let oauth_redirect = ServerAction::<OauthRedirect>::new();
+    let login_providers = Resource::new_blocking(|| (), |_| get_login_providers());
     let profile_redirect = ServerAction::<ProfileRedirect>::new();
     view! {
         <div class="main-container">
@@ -99,6 +103,7 @@ fn AboutContainer() -> impl IntoView {
                         }>
                             {move || Suspend::new(async move {
                                 let user_info = RwSignal::new(get_user.await.unwrap());
+                                let providers = login_providers.await.unwrap_or_default();
                                 if user_info.get().is_some() {
                                     view! {
                                         <a class="dropbtn">{user_info.get().unwrap().first_name}</a>
@@ -116,11 +121,35 @@ fn AboutContainer() -> impl IntoView {
                                         </ul>
                                     }
                                         .into_any()
+                                } else if providers.len() > 1 {
+                                    // Lets the user pick which provider to log in with
+                                    view! {
+                                        <a class="dropbtn">"Login"</a>
+                                        <ul class="dropdown-content">
+                                            {providers
+                                                .into_iter()
+                                                .map(|provider| {
+                                                    let name = provider.name.clone();
+                                                    view! {
+                                                        <li>
+                                                            <a on:click=move |_| {
+                                                                oauth_redirect
+                                                                    .dispatch(OauthRedirect {
+                                                                        provider: Some(name.clone()),
+                                                                    });
+                                                            }>{provider.display_name}</a>
+                                                        </li>
+                                                    }
+                                                })
+                                                .collect_view()}
+                                        </ul>
+                                    }
+                                        .into_any()
                                 } else {
                                     view! {
                                         <a
                                             on:click=move |_| {
-                                                oauth_redirect.dispatch(OauthRedirect {});
+                                                oauth_redirect.dispatch(OauthRedirect { provider: None });
                                             }
                                             class="dropbtn"
                                         >
@@ -251,9 +280,9 @@ fn ContactForm() -> impl IntoView {
 
 #[component]
 fn ExperienceDetails() -> impl IntoView {
-    let resume_cache: Arc<ResumeCache> = expect_context();
-    let resume = resume_cache.resume.read().unwrap();
-    let pdf_link = Resource::new_blocking(|| (), |_| generate_pdf_link());
+    let resume: Resume = expect_context();
+    let query = use_query_map();
+    let pdf_link = Resource::new_blocking(move || query.read().get("variant"), generate_pdf_link);
 
     let experience_items = resume
         .experience
@@ -325,15 +354,21 @@ fn SkillItem<'a>(title: &'static str, skills: &'a Vec<String>) -> impl IntoView
 
 #[component]
 fn SkillsDetails() -> impl IntoView {
-    let resume_cache: Arc<ResumeCache> = expect_context();
-    let resume = resume_cache.resume.read().unwrap();
-    view! {
-        <SkillItem title="Languages" skills=resume.skills.languages.as_ref().unwrap() />
-        <SkillItem title="Tools and Frameworks" skills=resume.skills.frameworks.as_ref().unwrap() />
-        <SkillItem title="Database" skills=resume.skills.database.as_ref().unwrap() />
-        <SkillItem title="Devops and Deployment" skills=resume.skills.devops.as_ref().unwrap() />
-        <SkillItem title="Development Tools" skills=resume.skills.dev_tools.as_ref().unwrap() />
-    }
+    let resume: Resume = expect_context();
+    // Categories missing from the parsed resume are skipped rather than failing the whole page
+    [
+        ("Languages", &resume.skills.languages),
+        ("Tools and Frameworks", &resume.skills.frameworks),
+        ("Database", &resume.skills.database),
+        ("Devops and Deployment", &resume.skills.devops),
+        ("Development Tools", &resume.skills.dev_tools),
+    ]
+    .into_iter()
+    .filter_map(|(title, skills)| {
+        let skills = skills.as_ref().filter(|skills| !skills.is_empty())?;
+        Some(view! { <SkillItem title=title skills=skills /> })
+    })
+    .collect_view()
 }
 
 #[component]
@@ -525,6 +560,130 @@ fn ContactDetails() -> impl IntoView {
     }
 }
 
+// Logins that fail on the OAuth callback end up here, with the reason from LoginError
+#[component]
+fn LoginFailed() -> impl IntoView {
+    let query = use_query_map();
+    let oauth_redirect = ServerAction::<OauthRedirect>::new();
+
+    let (title, message) = match query.read_untracked().get("reason").as_deref() {
+        Some("denied") => (
+            "Login cancelled",
+            "Access wasn't granted, so you haven't been logged in.",
+        ),
+        Some("expired") => (
+            "Login expired",
+            "This login took too long or was already used, please start it again.",
+        ),
+        Some("unknown") => (
+            "Login provider not found",
+            "This login provider isn't set up on this site.",
+        ),
+        Some("unavailable") => (
+            "Login unavailable",
+            "The login service couldn't be reached right now, please try again later.",
+        ),
+        _ => (
+            "Login failed",
+            "Something went wrong while logging you in, please try again.",
+        ),
+    };
+
+    #[cfg(feature = "ssr")]
+    {
+        use actix_web::http::StatusCode;
+
+        let resp = expect_context::<leptos_actix::ResponseOptions>();
+        resp.set_status(match query.read_untracked().get("reason").as_deref() {
+            Some("denied") => StatusCode::FORBIDDEN,
+            Some("expired") => StatusCode::UNAUTHORIZED,
+            Some("unknown") => StatusCode::NOT_FOUND,
+            Some("unavailable") => StatusCode::SERVICE_UNAVAILABLE,
+            Some("session") => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_GATEWAY,
+        });
+    }
+
+    view! {
+        <body class="home-page">
+            <div class="blurred-backdrop">
+                <div class="parallax">
+                    <h1 class="extra-large">
+                        <span class="custom-text-accent">{title}</span>
+                    </h1>
+                    <h4 class="subtitle">{message}</h4>
+                    <a
+                        class="btn"
+                        on:click=move |_| {
+                            oauth_redirect
+                                .dispatch(OauthRedirect {
+                                    provider: query.read_untracked().get("provider"),
+                                });
+                        }
+                    >
+                        "Try again"
+                    </a>
+                </div>
+            </div>
+        </body>
+    }
+}
+
+// Download links that are expired, used up or unknown end up here when opened in a browser
+#[component]
+fn LinkExpired() -> impl IntoView {
+    let query = use_query_map();
+    let refresh_link = ServerAction::<RefreshPdfLink>::new();
+
+    let (title, message) = match query.read_untracked().get("reason").as_deref() {
+        Some("used") => (
+            "Link already used",
+            "This download link has already been used as many times as it allows.",
+        ),
+        Some("unknown") => (
+            "Link not found",
+            "This download link doesn't exist, it may have been mistyped.",
+        ),
+        _ => (
+            "Link expired",
+            "Download links only work for a limited time, and this one has run out.",
+        ),
+    };
+
+    #[cfg(feature = "ssr")]
+    {
+        let resp = expect_context::<leptos_actix::ResponseOptions>();
+        resp.set_status(match query.read_untracked().get("reason").as_deref() {
+            Some("unknown") => actix_web::http::StatusCode::NOT_FOUND,
+            _ => actix_web::http::StatusCode::GONE,
+        });
+    }
+
+    view! {
+        <body class="home-page">
+            <div class="blurred-backdrop">
+                <div class="parallax">
+                    <h1 class="extra-large">
+                        <span class="custom-text-accent">{title}</span>
+                    </h1>
+                    <h4 class="subtitle">{message}</h4>
+                    <a
+                        class="btn"
+                        on:click=move |_| {
+                            refresh_link
+                                .dispatch(RefreshPdfLink {
+                                    variant: query.read_untracked().get("variant"),
+                                });
+                        }
+                    >
+                        "Get a fresh link"
+                    </a>
+                </div>
+            </div>
+        </body>
+    }
+}
+
 #[component]
 fn NotFound() -> impl IntoView {
     #[cfg(feature = "ssr")]
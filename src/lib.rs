@@ -1,4 +1,4 @@
-use std::sync::RwLock;
+use std::{collections::HashMap, sync::RwLock};
 
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "ssr")]
@@ -57,32 +57,11 @@ pub struct UserInfo {
     pub username: String,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct User {
-    pub first_name: String,
-    pub last_name: String,
-    pub username: String,
-    pub verified: bool,
-    pub email: String,
-}
-
-#[cfg(feature = "ssr")]
-impl From<User> for UserInfo {
-    fn from(value: User) -> Self {
-        UserInfo {
-            first_name: value.first_name,
-            last_name: value.last_name,
-            username: value.username,
-        }
-    }
-}
-
-#[cfg(feature = "ssr")]
-#[derive(Serialize, Deserialize)]
-pub struct UserInfoResponse {
-    pub success: bool,
-    pub user_data: User,
-    pub timestamp: i64,
+// A provider offered in the login dropdown
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LoginProvider {
+    pub name: String,
+    pub display_name: String,
 }
 
 #[cfg(feature = "ssr")]
@@ -99,10 +78,11 @@ impl SmtpInfo {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ResumeCache {
-    pub resume: RwLock<Resume>,
+    // Live resume of every variant, keyed by variant name
+    pub variants: RwLock<HashMap<String, Resume>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Resume {
     info: ApplicantInfo,
     skills: Skills,
@@ -111,7 +91,7 @@ pub struct Resume {
     education: Vec<Education>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Experience {
     company: Option<String>,
     title: Option<String>,
@@ -120,14 +100,14 @@ pub struct Experience {
     desc: Option<Vec<String>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Education {
     college: Option<String>,
     degree: Option<String>,
     major: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Skills {
     languages: Option<Vec<String>>,
     frameworks: Option<Vec<String>>,
@@ -136,7 +116,7 @@ pub struct Skills {
     dev_tools: Option<Vec<String>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ApplicantInfo {
     name: Option<String>,
     phone: Option<String>,
@@ -1,39 +1,42 @@
-use cfg_if::cfg_if;
-
-cfg_if! {
-    if #[cfg(feature = "ssr")] {
-        use actix_files::NamedFile;
-        use actix_web::{Result};
-        use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
-        use std::path::PathBuf;
-        use actix_web::web;
-        use redis::{Client, Commands};
-        use chrisbratti_website::server_functions::get_env_variable;
-
-        use lazy_static::lazy_static;
-
-        lazy_static! {
-            static ref RESUME_FILE_NAME: String = get_env_variable("RESUME_FILE_NAME").expect("RESUME_FILE_NAME not set!");
-        }
-    }
-}
-
 #[cfg(feature = "ssr")]
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    use std::{sync::RwLock, time::Duration};
+    use std::{collections::HashMap, sync::RwLock, time::Duration};
 
     use actix_files::Files;
     use actix_identity::IdentityMiddleware;
-    use actix_session::{config::PersistentSession, storage::RedisSessionStore, SessionMiddleware};
+    use actix_session::{config::PersistentSession, SessionMiddleware};
     use actix_web::{cookie::Key, *};
     use chrisbratti_website::{
         app::*,
         middleware::VerifyApiKey,
-        oauth::oauth_client::handle_oauth_response,
-        routes::resume_routes::{approve_pending_resume, upload_resume},
+        oauth::{
+            oauth_client::handle_oauth_response, provider_registry::ProviderRegistry,
+            session_refresh::SessionRefresher,
+        },
+        routes::download_routes::{download_negotiated_resume, download_resume},
+        routes::link_routes::{create_download_link, get_download_link, get_download_links},
+        routes::resume_routes::{
+            approve_pending_resume, create_override, delete_override, diff_pending_resume,
+            export_resume_source, get_pending_resume, get_resume_history, get_resume_job,
+            get_resume_revision, import_resume, import_resume_file, list_overrides, reject_resume,
+            rollback_resume, upload_resume,
+        },
+        routes::sweep_routes::{get_sweep_metrics, trigger_sweep},
         server_functions::get_env_variable,
-        services::resume_parsing_service::load_resume,
+        services::{
+            download_link_service::LinkMode,
+            redis_repository::RedisRepository,
+            redis_sweep_service::run_redis_sweeper,
+            resume_approval_service::run_auto_approval,
+            resume_job_service::run_resume_jobs,
+            resume_parsing_service::load_resume,
+            resume_store_service::{init_resume_store, resume_store},
+            resume_variant_service::is_default,
+            resume_watch_service::watch_resume_files,
+            session_store_service::SessionBackend,
+            token_store_service::{configured_token_store, SESSION_TTL},
+        },
         PersonalInfo, ResumeCache, SmtpInfo,
     };
     use leptos::config::get_configuration;
@@ -43,21 +46,88 @@ async fn main() -> std::io::Result<()> {
 
     let conf = get_configuration(None).unwrap();
     let addr = conf.leptos_options.site_addr;
-    let redis_connection_string =
-        get_env_variable("REDIS_CONNECTION_STRING").expect("Connection string not set!");
+    // Optional when both TOKEN_STORE and SESSION_STORE are set to keep things without redis
+    let redis_connection_string = get_env_variable("REDIS_CONNECTION_STRING");
+
+    init_resume_store()
+        .await
+        .expect("Could not set up the resume store!");
+
+    let mut variants = HashMap::new();
+
+    let live_variants = resume_store()
+        .live_variants()
+        .await
+        .expect("Could not list resume variants!");
 
-    let resume = load_resume().await.unwrap();
+    for variant in live_variants {
+        match load_resume(&variant).await {
+            Ok(resume) => {
+                variants.insert(variant, resume);
+            }
+            // The site can't render anything without the default resume
+            Err(err) if is_default(&variant) => panic!("Could not load {variant} resume: {err}"),
+            Err(err) => println!("Skipping {variant} resume: {err}"),
+        }
+    }
+
+    println!(
+        "Loaded resume variants: {}",
+        variants.keys().cloned().collect::<Vec<String>>().join(", ")
+    );
 
     let resume_cache = web::Data::new(ResumeCache {
-        resume: RwLock::new(resume),
+        variants: RwLock::new(variants),
     });
 
+    // Picks up resume files that are edited or replaced on disk while the server is running
+    tokio::spawn(watch_resume_files(resume_cache.clone().into_inner()));
+
+    // Makes pending resumes live once their review window is over, if RESUME_AUTO_APPROVE is set
+    tokio::spawn(run_auto_approval(resume_cache.clone().into_inner()));
+
     let personal_info = web::Data::new(PersonalInfo::new());
 
     let smtp_info = web::Data::new(SmtpInfo::new());
 
-    let redis_client =
-        web::Data::new(redis::Client::open(redis_connection_string.clone()).unwrap());
+    let redis = match &redis_connection_string {
+        Some(connection_string) => Some(
+            RedisRepository::connect(connection_string)
+                .await
+                .expect("Could not connect to redis!"),
+        ),
+        None => None,
+    };
+
+    let token_store =
+        configured_token_store(redis.as_ref()).expect("Could not set up the token store!");
+
+    let link_mode = LinkMode::configured().expect("Could not set up download links!");
+    println!("Minting {} download links", link_mode.name());
+    let link_mode = web::Data::new(link_mode);
+
+    // Works through queued resume uploads, including any left over from before a restart. The
+    // queue lives in redis, so uploads are turned off without it
+    match &redis {
+        Some(redis) => {
+            tokio::spawn(run_resume_jobs(redis.clone()));
+        }
+        None => println!("REDIS_CONNECTION_STRING not set, resume uploads are disabled"),
+    }
+
+    // Prunes expired download links and OAuth states that would otherwise pile up
+    tokio::spawn(run_redis_sweeper(token_store.clone()));
+
+    let redis = redis.map(web::Data::new);
+    let token_store = web::Data::from(token_store);
+
+    let oauth_providers = web::Data::new(
+        ProviderRegistry::load()
+            .await
+            .expect("Could not set up the OAuth providers!"),
+    );
+
+    let session_refresher = web::Data::new(SessionRefresher::default());
 
     let secret_key = Key::from(
         get_env_variable("REDIS_KEY")
@@ -65,9 +135,9 @@ async fn main() -> std::io::Result<()> {
             .as_bytes(),
     );
 
-    let store = RedisSessionStore::new(redis_connection_string)
+    let store = SessionBackend::configured(redis_connection_string.as_deref())
         .await
-        .unwrap();
+        .expect("Could not set up the session store!");
 
     HttpServer::new(move || {
         // Generate the list of routes in your Leptos App
@@ -84,13 +154,33 @@ async fn main() -> std::io::Result<()> {
             .service(Files::new("/assets", &site_root))
             // serve the favicon from /favicon.ico
             .service(favicon)
-            .service(download_pdf)
+            .service(download_resume)
+            .service(download_negotiated_resume)
             .route("/auth", web::get().to(handle_oauth_response))
+            .route("/auth/{provider}", web::get().to(handle_oauth_response))
             .service(
                 web::scope("/internal")
                     .wrap(VerifyApiKey)
+                    .service(create_download_link)
+                    .service(get_download_links)
+                    .service(get_download_link)
+                    .service(trigger_sweep)
+                    .service(get_sweep_metrics)
                     .service(upload_resume)
-                    .service(approve_pending_resume),
+                    .service(get_resume_job)
+                    .service(get_pending_resume)
+                    .service(approve_pending_resume)
+                    .service(reject_resume)
+                    .service(diff_pending_resume)
+                    .service(list_overrides)
+                    .service(create_override)
+                    .service(delete_override)
+                    .service(import_resume)
+                    .service(import_resume_file)
+                    .service(export_resume_source)
+                    .service(get_resume_history)
+                    .service(get_resume_revision)
+                    .service(rollback_resume),
             )
             .leptos_routes(routes, {
                 let leptos_options = leptos_options.clone();
@@ -119,10 +209,18 @@ async fn main() -> std::io::Result<()> {
             .app_data(resume_cache.clone())
             .app_data(personal_info.clone())
             .app_data(smtp_info.clone())
-            .app_data(redis_client.clone())
+            .app_data(token_store.clone())
+            .app_data(link_mode.clone())
+            .app_data(oauth_providers.clone())
+            .app_data(session_refresher.clone())
+            .configure(|cfg| {
+                if let Some(redis) = &redis {
+                    cfg.app_data(redis.clone());
+                }
+            })
             .wrap(
                 IdentityMiddleware::builder()
-                    .login_deadline(Some(Duration::new(259200, 0)))
+                    .login_deadline(Some(Duration::new(SESSION_TTL, 0)))
                     .build(),
             )
             // Uses Session middleware for all Session info, uses Redis as a backend
@@ -154,49 +252,6 @@ async fn favicon(
     ))?)
 }
 
-#[cfg(feature = "ssr")]
-#[actix_web::get("/{uuid}/resume.pdf")]
-pub async fn download_pdf(
-    path: web::Path<String>,
-    redis_client: web::Data<Client>,
-) -> Result<NamedFile, actix_web::Error> {
-    use std::time::{SystemTime, UNIX_EPOCH};
-
-    let uuid = path.into_inner();
-    println!("Serving file for UUID: {}", uuid);
-
-    let mut con = redis_client
-        .get_connection()
-        .map_err(|_| actix_web::error::ErrorInternalServerError("Could not connect to redis!"))?;
-
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-
-    let score: Option<u64> = con
-        .zscore("pdf_links", &uuid)
-        .map_err(|_| actix_web::error::ErrorInternalServerError("Error fetching from redis!"))?;
-
-    if score.is_none_or(|expiry| expiry < now) {
-        return Err(actix_web::error::ErrorInternalServerError(
-            "Link invalid or expired",
-        ));
-    }
-
-    // Update to resume path var
-    let path: PathBuf = format!("uploads/{}.pdf", RESUME_FILE_NAME.as_str()).into();
-
-    let file = NamedFile::open(path)?.set_content_disposition(ContentDisposition {
-        disposition: DispositionType::Attachment,
-        parameters: vec![DispositionParam::Filename(
-            "ChrisBratti_Resume.pdf".to_string(),
-        )],
-    });
-
-    Ok(file)
-}
-
 #[cfg(not(any(feature = "ssr", feature = "csr")))]
 pub fn main() {
     // no client-side main function
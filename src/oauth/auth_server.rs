@@ -0,0 +1,102 @@
+use reqwest::Client;
+use serde_json::{Map, Value};
+
+use super::{login_error::TokenEndpointError, oidc::OidcError, TokenResponse};
+
+// A provider running chris-bratti/auth-server, which has its own login page, token endpoint and
+// user info endpoint rather than the standard OpenID Connect ones
+pub struct AuthServerProvider {
+    pub client: Client,
+    // Where users log in and manage their profile
+    pub login_url: String,
+    // Where the token and user info endpoints are
+    pub api_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+impl AuthServerProvider {
+    pub fn authorization_url(&self, state: &str, code_challenge: Option<&str>) -> String {
+        let mut login_url = format!(
+            "{}/login?client_id={}&state={}",
+            self.login_url, self.client_id, state
+        );
+
+        if let Some(code_challenge) = code_challenge {
+            login_url.push_str(&format!(
+                "&code_challenge={code_challenge}&code_challenge_method=S256"
+            ));
+        }
+
+        login_url
+    }
+
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: Option<&str>,
+    ) -> Result<TokenResponse, OidcError> {
+        let mut params = vec![
+            ("grant_type", "authorization_code"),
+            ("authorization_code", code),
+        ];
+
+        // Proves this is the same client that started the login
+        if let Some(code_verifier) = code_verifier {
+            params.push(("code_verifier", code_verifier));
+        }
+
+        self.request_token(&params).await
+    }
+
+    pub async fn refresh(&self, refresh_token: &str) -> Result<TokenResponse, OidcError> {
+        self.request_token(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+        ])
+        .await
+    }
+
+    async fn request_token(&self, params: &[(&str, &str)]) -> Result<TokenResponse, OidcError> {
+        let response = self
+            .client
+            .post(format!("{}/v0/oauth/token", self.api_url))
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .form(params)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(TokenEndpointError::from_response(response).await.into());
+        }
+
+        Ok(response.json().await?)
+    }
+
+    // The user's fields, for the provider's claim mapping to pick from
+    pub async fn user_info(
+        &self,
+        access_token: &str,
+        username: &str,
+    ) -> Result<Map<String, Value>, OidcError> {
+        let mut response: Map<String, Value> = self
+            .client
+            .get(format!("{}/v0/users/info", self.api_url))
+            .query(&[("username", username)])
+            .bearer_auth(access_token)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        match response.remove("user_data") {
+            Some(Value::Object(user_data)) => Ok(user_data),
+            _ => Err("User info response has no user_data".into()),
+        }
+    }
+
+    pub fn profile_url(&self) -> String {
+        format!("{}/user", self.login_url)
+    }
+}
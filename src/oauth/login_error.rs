@@ -0,0 +1,138 @@
+use std::fmt;
+
+use actix_web::{http::header::LOCATION, HttpResponse};
+use serde_json::Value;
+
+use super::provider_registry::OauthError;
+
+// A token endpoint answered with an error status. The body is kept since providers explain what
+// went wrong in it, like {"error": "invalid_grant"}
+#[derive(Debug)]
+pub struct TokenEndpointError {
+    pub status: u16,
+    pub body: String,
+}
+
+impl TokenEndpointError {
+    pub async fn from_response(response: reqwest::Response) -> Self {
+        TokenEndpointError {
+            status: response.status().as_u16(),
+            body: response.text().await.unwrap_or_default(),
+        }
+    }
+
+    // The OAuth error code from the body, if the provider sent one
+    pub fn error_code(&self) -> Option<String> {
+        serde_json::from_str::<Value>(&self.body)
+            .ok()?
+            .get("error")?
+            .as_str()
+            .map(str::to_string)
+    }
+}
+
+impl fmt::Display for TokenEndpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Token endpoint returned {}: {}", self.status, self.body)
+    }
+}
+
+impl std::error::Error for TokenEndpointError {}
+
+// Everything that can go wrong while finishing a login on the /auth callback. Users are sent to the
+// login failed page with a reason, the details only go to the log
+#[derive(Debug)]
+pub enum LoginError {
+    // The user didn't give consent, or the provider wouldn't let them in
+    AccessDenied,
+    // Any other error the provider sent back to the callback
+    ProviderError {
+        error: String,
+        description: Option<String>,
+    },
+    UnknownProvider(String),
+    // The state is unknown, expired, already used or from another provider's login
+    InvalidState,
+    MissingCode,
+    TokenEndpoint(TokenEndpointError),
+    ProviderUnavailable(String),
+    // The tokens came back but couldn't be trusted, like an ID token that failed verification
+    InvalidLogin(String),
+    StoreUnavailable(String),
+    Session(String),
+}
+
+impl LoginError {
+    // Used in the login failed page's query string
+    pub fn reason(&self) -> &'static str {
+        match self {
+            LoginError::AccessDenied => "denied",
+            LoginError::InvalidState | LoginError::MissingCode => "expired",
+            LoginError::UnknownProvider(_) => "unknown",
+            LoginError::ProviderError { .. }
+            | LoginError::TokenEndpoint(_)
+            | LoginError::InvalidLogin(_) => "failed",
+            LoginError::ProviderUnavailable(_) | LoginError::StoreUnavailable(_) => "unavailable",
+            LoginError::Session(_) => "session",
+        }
+    }
+
+    // Providers send error=access_denied when the user declines
+    pub fn from_callback(error: String, description: Option<String>) -> Self {
+        match error.as_str() {
+            "access_denied" => LoginError::AccessDenied,
+            _ => LoginError::ProviderError { error, description },
+        }
+    }
+
+    // The login failed page offers to try again, with the same provider when it's known
+    pub fn redirect(&self, provider: Option<&str>) -> HttpResponse {
+        let provider = provider.filter(|_| !matches!(self, LoginError::UnknownProvider(_)));
+
+        let location = match provider {
+            Some(provider) => format!("/login-failed?reason={}&provider={provider}", self.reason()),
+            None => format!("/login-failed?reason={}", self.reason()),
+        };
+
+        HttpResponse::SeeOther()
+            .insert_header((LOCATION, location))
+            .finish()
+    }
+}
+
+impl fmt::Display for LoginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoginError::AccessDenied => write!(f, "Access was denied"),
+            LoginError::ProviderError { error, description } => write!(
+                f,
+                "Provider returned {error}: {}",
+                description.as_deref().unwrap_or("no description")
+            ),
+            LoginError::UnknownProvider(provider) => write!(f, "Unknown login provider {provider}"),
+            LoginError::InvalidState => write!(f, "OAuth state is invalid or expired"),
+            LoginError::MissingCode => write!(f, "Callback has no authorization code"),
+            LoginError::TokenEndpoint(err) => write!(f, "{err}"),
+            LoginError::ProviderUnavailable(err) => write!(f, "Provider unreachable: {err}"),
+            LoginError::InvalidLogin(err) => write!(f, "Login could not be verified: {err}"),
+            LoginError::StoreUnavailable(err) => write!(f, "Token store unavailable: {err}"),
+            LoginError::Session(err) => write!(f, "Could not start session: {err}"),
+        }
+    }
+}
+
+impl From<OauthError> for LoginError {
+    fn from(err: OauthError) -> Self {
+        let err = match err.downcast::<TokenEndpointError>() {
+            Ok(err) => return LoginError::TokenEndpoint(*err),
+            Err(err) => err,
+        };
+
+        // Responses that don't parse are the provider's fault, not a connection problem
+        match err.downcast::<reqwest::Error>() {
+            Ok(err) if err.is_decode() => LoginError::InvalidLogin(err.to_string()),
+            Ok(err) => LoginError::ProviderUnavailable(err.to_string()),
+            Err(err) => LoginError::InvalidLogin(err.to_string()),
+        }
+    }
+}
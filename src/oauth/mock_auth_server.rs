@@ -0,0 +1,162 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use actix_identity::IdentityMiddleware;
+use actix_session::{storage::CookieSessionStore, SessionMiddleware};
+use actix_web::{
+    cookie::Key,
+    http::{header::LOCATION, StatusCode},
+    test, web, App, HttpResponse, HttpServer,
+};
+use serde_json::{json, Value};
+
+use crate::services::{memory_token_store::MemoryTokenStore, token_store_service::TokenStore};
+
+use super::{oauth_client::handle_oauth_response, provider_registry::ProviderRegistry};
+
+type TokenRequests = Arc<Mutex<Vec<HashMap<String, String>>>>;
+
+// An auth server for tests. Its token endpoint keeps every form it was sent and answers them with
+// the given responses in turn, repeating the last one once they run out
+pub(crate) struct MockAuthServer {
+    pub url: String,
+    token_requests: TokenRequests,
+}
+
+impl MockAuthServer {
+    pub async fn start(status: u16, body: Value) -> Self {
+        Self::responding(vec![(status, body)], Duration::ZERO).await
+    }
+
+    // `delay` holds every answer back, so requests that arrive together overlap
+    pub async fn responding(responses: Vec<(u16, Value)>, delay: Duration) -> Self {
+        let token_requests = TokenRequests::default();
+        let requests = token_requests.clone();
+        let responses = Arc::new(responses);
+
+        let server = HttpServer::new(move || {
+            let requests = requests.clone();
+            let responses = responses.clone();
+
+            App::new().route(
+                "/v0/oauth/token",
+                web::post().to(move |form: web::Form<HashMap<String, String>>| {
+                    let (status, body) = {
+                        let mut requests = requests.lock().unwrap();
+                        requests.push(form.into_inner());
+                        responses[(requests.len() - 1).min(responses.len() - 1)].clone()
+                    };
+
+                    async move {
+                        tokio::time::sleep(delay).await;
+                        HttpResponse::build(StatusCode::from_u16(status).unwrap()).json(body)
+                    }
+                }),
+            )
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+
+        MockAuthServer {
+            url,
+            token_requests,
+        }
+    }
+
+    pub async fn issuing_tokens() -> Self {
+        Self::start(200, Self::tokens("access", 4_000_000_000)).await
+    }
+
+    // A token response for octocat
+    pub fn tokens(access_token: &str, expiry: i64) -> Value {
+        json!({
+            "success": true,
+            "access_token": access_token,
+            "refresh_token": format!("{access_token}-refresh"),
+            "username": "octocat",
+            "expiry": expiry
+        })
+    }
+
+    pub fn token_requests(&self) -> Vec<HashMap<String, String>> {
+        self.token_requests.lock().unwrap().clone()
+    }
+
+    // This server's entry in a providers file. `pkce` is left out when None, so OAUTH_PKCE decides
+    pub fn provider(&self, name: &str, pkce: Option<bool>) -> String {
+        let pkce = pkce
+            .map(|pkce| format!("pkce = {pkce}"))
+            .unwrap_or_default();
+
+        format!(
+            r#"
+            [[providers]]
+            name = "{name}"
+            type = "auth-server"
+            client_id = "site"
+            client_secret_env = "MOCK_CLIENT_SECRET"
+            login_url = "{url}"
+            api_url = "{url}"
+            {pkce}
+            "#,
+            url = self.url
+        )
+    }
+
+    // A registry with this server as its only provider, called "mock"
+    pub async fn registry(&self, pkce: Option<bool>) -> web::Data<ProviderRegistry> {
+        registry(&self.provider("mock", pkce)).await
+    }
+}
+
+pub(crate) async fn registry(providers: &str) -> web::Data<ProviderRegistry> {
+    // Sessions are encrypted with ENCRYPTION_KEY, which has to be 32 bytes
+    std::env::set_var("ENCRYPTION_KEY", "0".repeat(32));
+    std::env::set_var("MOCK_CLIENT_SECRET", "secret");
+
+    web::Data::new(ProviderRegistry::from_toml(providers).await.unwrap())
+}
+
+pub(crate) fn memory_store() -> web::Data<dyn TokenStore> {
+    web::Data::from(Arc::new(MemoryTokenStore::default()) as Arc<dyn TokenStore>)
+}
+
+// Sends the provider's redirect back through the callback, behind the same middleware as the
+// site, and returns the status and where the user was sent
+pub(crate) async fn callback(
+    store: &web::Data<dyn TokenStore>,
+    registry: &web::Data<ProviderRegistry>,
+    uri: &str,
+) -> (StatusCode, String) {
+    let app = test::init_service(
+        App::new()
+            .wrap(IdentityMiddleware::default())
+            .wrap(SessionMiddleware::new(
+                CookieSessionStore::default(),
+                Key::generate(),
+            ))
+            .app_data(store.clone())
+            .app_data(registry.clone())
+            .route("/auth", web::get().to(handle_oauth_response))
+            .route("/auth/{provider}", web::get().to(handle_oauth_response)),
+    )
+    .await;
+
+    let response = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    (response.status(), location)
+}
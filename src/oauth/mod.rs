@@ -1,15 +1,35 @@
+#[cfg(feature = "ssr")]
+pub mod auth_server;
+#[cfg(feature = "ssr")]
+pub mod login_error;
+#[cfg(all(test, feature = "ssr"))]
+mod mock_auth_server;
 pub mod oauth_client;
+#[cfg(feature = "ssr")]
+pub mod oidc;
+#[cfg(feature = "ssr")]
+pub mod pkce;
+#[cfg(feature = "ssr")]
+pub mod provider_registry;
+#[cfg(feature = "ssr")]
+pub mod session_refresh;
 use cfg_if::cfg_if;
 
 cfg_if! {
     if #[cfg(feature = "ssr")] {
         use serde::{Deserialize, Serialize};
+        use std::time::{SystemTime, UNIX_EPOCH};
         use crate::server_functions::encrypt_string;
+        use crate::UserInfo;
+        use oidc::OidcTokenResponse;
 
+        // Providers send back either a code, or an error such as access_denied
         #[derive(Deserialize)]
         pub struct OauthResponse {
-            pub code: String,
-            pub state: String,
+            pub code: Option<String>,
+            pub state: Option<String>,
+            pub error: Option<String>,
+            pub error_description: Option<String>,
         }
 
         #[derive(Deserialize, Serialize)]
@@ -21,17 +41,51 @@ cfg_if! {
             pub expiry: i64
         }
 
-        #[derive(Deserialize, Serialize)]
+        // Username, or the subject claim for OpenID Connect logins
+        #[derive(Deserialize, Serialize, Clone)]
         pub struct SessionData {
+            // The provider that issued the tokens, empty for sessions from before there were several
+            #[serde(default)]
+            pub provider: String,
             pub username: String,
             pub access_token: String,
-            pub refresh_token: String,
+            // OpenID Connect providers don't always hand out refresh tokens
+            pub refresh_token: Option<String>,
             pub expiry: i64,
+            // Profile from the ID token, for providers without a userinfo endpoint
+            pub profile: Option<UserInfo>,
         }
 
         impl From<TokenResponse> for SessionData{
             fn from(value: TokenResponse) -> Self {
-                SessionData { username: value.username, access_token: encrypt_string(&value.access_token).unwrap(), refresh_token: encrypt_string(&value.refresh_token).unwrap(), expiry: value.expiry }
+                SessionData { provider: String::new(), username: value.username, access_token: encrypt_string(&value.access_token).unwrap(), refresh_token: Some(encrypt_string(&value.refresh_token).unwrap()), expiry: value.expiry, profile: None }
+            }
+        }
+
+        // Providers that leave out expires_in get their access tokens checked again after an hour
+        const DEFAULT_ACCESS_TOKEN_SECONDS: i64 = 3600;
+
+        impl SessionData {
+            pub fn from_oidc(username: String, profile: Option<UserInfo>, tokens: OidcTokenResponse) -> Self {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+
+                SessionData {
+                    provider: String::new(),
+                    username,
+                    access_token: encrypt_string(&tokens.access_token).unwrap(),
+                    refresh_token: tokens.refresh_token.map(|refresh_token| encrypt_string(&refresh_token).unwrap()),
+                    expiry: now + tokens.expires_in.unwrap_or(DEFAULT_ACCESS_TOKEN_SECONDS),
+                    profile,
+                }
+            }
+
+            // What the session is stored and the user identified by. Users of different providers
+            // can share a username, so it's prefixed with the provider
+            pub fn key(&self) -> String {
+                match self.provider.as_str() {
+                    "" => self.username.clone(),
+                    provider => format!("{provider}:{}", self.username),
+                }
             }
         }
 
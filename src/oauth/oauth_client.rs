@@ -1,46 +1,64 @@
+use crate::LoginProvider;
 use cfg_if::cfg_if;
 use leptos::{prelude::ServerFnError, server};
 
 cfg_if! {
     if #[cfg(feature = "ssr")] {
-        use crate::server_functions::{generate_token, get_env_variable};
-        use reqwest::Certificate;
-
-        lazy_static! {
-            static ref OAUTH_REDIRECT_URL: String = get_env_variable("OAUTH_REDIRECT_URL").expect("OAUTH_REDIRECT_URL is unset!");
-            static ref OAUTH_TOKEN_URL: String = get_env_variable("OAUTH_TOKEN_URL").expect("OAUTH_TOKEN_URL is unset!");
-            static ref CLIENT_ID: String = get_env_variable("CLIENT_ID").expect("CLIENT_ID is unset!");
-            static ref CLIENT_SECRET: String = get_env_variable("CLIENT_SECRET").expect("CLIENT_SECRET IS UNSET!");
-            static ref CLIENT: Client = reqwest::Client::builder().danger_accept_invalid_hostnames(true).add_root_certificate(load_certificate().unwrap()).build().unwrap();
-        }
+        use crate::server_functions::generate_token;
         use actix_web::web;
-        use lazy_static::lazy_static;
         use leptos_actix::extract;
-        use redis::Client as RedisClient;
-        use reqwest::Client;
-        use redis::Commands;
+        use crate::services::token_store_service::{LoginState, TokenStore};
+        use super::pkce::{code_challenge, generate_code_verifier};
+        use super::provider_registry::{OauthProvider, ProviderRegistry};
+        use super::session_refresh::{SessionRefresher, OAUTH_RELOGIN};
         use std::time::{SystemTime, UNIX_EPOCH};
-        use super::{OauthResponse, TokenResponse};
-        use actix_web::HttpRequest;
-        use crate::UserInfoResponse;
+        use super::OauthResponse;
+        use actix_web::{HttpRequest, HttpResponse};
+        use super::login_error::LoginError;
+        use crate::UserInfo;
         use super::SessionData;
         use actix_identity::Identity;
         use actix_web::{HttpMessage, Responder};
-        use std::{fs::File, io::Read};
 
-        fn load_certificate() -> Result<Certificate, reqwest::Error> {
-            let mut buf = Vec::new();
-            File::open("/certs/cert.pem").unwrap().read_to_end(&mut buf).unwrap();
+        // The stored session of the logged in user, if there is one
+        async fn current_session() -> Result<Option<SessionData>, ServerFnError> {
+            let user: Option<Identity> = extract().await?;
+
+            let Some(user) = user else {
+                return Ok(None);
+            };
 
-            Certificate::from_pem(&buf)
+            let store: web::Data<dyn TokenStore> = extract().await?;
 
+            store
+                .load_session(&user.id()?)
+                .await
+                .map_err(|err| ServerFnError::new(format!("Error loading session!: {err}")))
         }
     }
 }
 
+#[server(GetLoginProviders, "/api")]
+pub async fn get_login_providers() -> Result<Vec<LoginProvider>, ServerFnError> {
+    let registry: web::Data<ProviderRegistry> = extract().await?;
+
+    Ok(registry.login_providers())
+}
+
 #[server(ProfileRedirect, "/api")]
 pub async fn profile_redirect() -> Result<(), ServerFnError> {
-    leptos_actix::redirect(format!("{}/user", OAUTH_REDIRECT_URL.to_string()).as_str());
+    let registry: web::Data<ProviderRegistry> = extract().await?;
+
+    let provider_name = current_session()
+        .await?
+        .map(|session_data| session_data.provider)
+        .unwrap_or_default();
+
+    let provider = registry
+        .get(&provider_name)
+        .ok_or_else(|| ServerFnError::new("No login provider configured"))?;
+
+    leptos_actix::redirect(&provider.profile_url());
 
     Ok(())
 }
@@ -52,17 +70,33 @@ pub async fn logout() -> Result<(), ServerFnError> {
     Ok(())
 }
 
+// Starts a login with the named provider, or the default one
 #[server(OauthRedirect, "/api")]
-pub async fn oauth_redirect() -> Result<(), ServerFnError> {
+pub async fn oauth_redirect(provider: Option<String>) -> Result<(), ServerFnError> {
+    let registry: web::Data<ProviderRegistry> = extract().await?;
     let user: Option<Identity> = extract().await?;
-    if let Some(_) = user {
-        leptos_actix::redirect(format!("{}/user", OAUTH_REDIRECT_URL.to_string()).as_str());
-        return Ok(());
+    if user.is_some() {
+        return profile_redirect().await;
     }
-    let redis_client: web::Data<RedisClient> = extract().await?;
+    let store: web::Data<dyn TokenStore> = extract().await?;
 
-    let mut con = redis_client.get_connection().unwrap();
+    let provider = registry
+        .get(provider.as_deref().unwrap_or_default())
+        .ok_or_else(|| ServerFnError::new("Unknown login provider"))?;
 
+    let login_url = start_login(provider, store.get_ref()).await?;
+
+    leptos_actix::redirect(&login_url);
+
+    Ok(())
+}
+
+// Saves a new login's state and builds the URL that sends the user to the provider
+#[cfg(feature = "ssr")]
+async fn start_login(
+    provider: &OauthProvider,
+    store: &dyn TokenStore,
+) -> Result<String, ServerFnError> {
     let state = generate_token();
 
     let ttl_seconds = 300;
@@ -72,155 +106,534 @@ pub async fn oauth_redirect() -> Result<(), ServerFnError> {
         .as_secs();
     let expiry_time = now + ttl_seconds;
 
-    () = con.zadd("states", &state, expiry_time).map_err(|err| {
-        ServerFnError::new(format!("Error adding to redis cache!: {}", err.to_string()))
-    })?;
+    let code_verifier = provider.pkce.then(generate_code_verifier);
+    // Ties the ID token to this login, only used with OpenID Connect
+    let nonce = provider.uses_nonce().then(generate_token);
 
-    leptos_actix::redirect(
-        format!(
-            "{}/login?client_id={}&state={}",
-            OAUTH_REDIRECT_URL.to_string(),
-            CLIENT_ID.to_string(),
-            state
-        )
-        .as_str(),
-    );
+    let login_state = LoginState {
+        code_verifier: code_verifier.clone(),
+        nonce: nonce.clone(),
+        provider: provider.name.clone(),
+    };
 
-    Ok(())
+    store
+        .add_state(&state, &login_state, expiry_time)
+        .await
+        .map_err(|err| ServerFnError::new(format!("Error saving OAuth state!: {err}")))?;
+
+    let code_challenge = code_verifier.as_deref().map(code_challenge);
+
+    provider
+        .authorization_url(&state, nonce.as_deref(), code_challenge.as_deref())
+        .map_err(|err| ServerFnError::new(format!("Error building login URL!: {err}")))
 }
 
+// Callback for /auth/{provider}. The plain /auth callback belongs to the default provider. Failed
+// logins end up on the login failed page rather than an error response
 #[cfg(feature = "ssr")]
 pub async fn handle_oauth_response(
     request: HttpRequest,
+    provider: Option<web::Path<String>>,
     oauth_response: web::Query<OauthResponse>,
-    redis_client: web::Data<RedisClient>,
+    store: web::Data<dyn TokenStore>,
+    registry: web::Data<ProviderRegistry>,
 ) -> impl Responder {
-    use actix_web::HttpResponse;
-
-    use super::SessionData;
-
-    let mut con = redis_client.get_connection().unwrap();
+    let provider_name = provider.map(|provider| provider.into_inner());
+
+    match finish_login(
+        &request,
+        provider_name.as_deref().unwrap_or_default(),
+        oauth_response.into_inner(),
+        store.get_ref(),
+        &registry,
+    )
+    .await
+    {
+        Ok(response) => response,
+        Err(err) => {
+            println!("Login failed: {err}");
+            err.redirect(provider_name.as_deref())
+        }
+    }
+}
 
+#[cfg(feature = "ssr")]
+async fn finish_login(
+    request: &HttpRequest,
+    provider_name: &str,
+    oauth_response: OauthResponse,
+    store: &dyn TokenStore,
+    registry: &ProviderRegistry,
+) -> Result<HttpResponse, LoginError> {
     let now = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
         .as_secs();
 
-    let OauthResponse { code, state } = oauth_response.into_inner();
-
-    let redis_score: Option<u64> = con
-        .zscore("states", &state)
-        .map_err(|_| HttpResponse::InternalServerError().finish())
-        .unwrap();
-
-    if redis_score.is_none_or(|expiry| expiry < now) {
-        return HttpResponse::Unauthorized().body("OAuth credentials are invalid or expired");
+    let provider = registry
+        .get(provider_name)
+        .ok_or_else(|| LoginError::UnknownProvider(provider_name.to_string()))?;
+
+    let OauthResponse {
+        code,
+        state,
+        error,
+        error_description,
+    } = oauth_response;
+
+    if let Some(error) = error {
+        // The state won't be used anymore, so it's cleared out rather than left to expire
+        if let Some(state) = state {
+            let _ = store.take_state(&state, now).await;
+        }
+        return Err(LoginError::from_callback(error, error_description));
     }
 
-    () = con.zrem("states", state).unwrap();
+    let state = state.ok_or(LoginError::InvalidState)?;
 
-    let params = [
-        ("grant_type", "authorization_code"),
-        ("authorization_code", &code),
-    ];
-
-    let token_response = request_access_token(&params)
+    let login_state = store
+        .take_state(&state, now)
         .await
-        .map_err(|_| {
-            HttpResponse::InternalServerError().body("Error querying authentication server")
-        })
-        .unwrap();
+        .map_err(|err| LoginError::StoreUnavailable(err.to_string()))?
+        .ok_or(LoginError::InvalidState)?;
+
+    // A login has to come back to the callback of the provider it was started with
+    if registry
+        .get(&login_state.provider)
+        .is_none_or(|started_with| started_with.name != provider.name)
+    {
+        return Err(LoginError::InvalidState);
+    }
 
-    let username = token_response.username.clone();
+    let code = code.ok_or(LoginError::MissingCode)?;
 
-    let session_data = serde_json::to_string(&SessionData::from(token_response)).unwrap();
+    let session_data = provider
+        .exchange_code(
+            &code,
+            login_state.code_verifier.as_deref(),
+            login_state.nonce.as_deref(),
+        )
+        .await?;
 
-    () = con
-        .set(username.clone(), session_data)
-        .map_err(|_| HttpResponse::InternalServerError().finish())
-        .unwrap();
+    store
+        .save_session(&session_data)
+        .await
+        .map_err(|err| LoginError::StoreUnavailable(err.to_string()))?;
 
-    Identity::login(&request.extensions(), username.clone())
-        .map_err(|_| HttpResponse::InternalServerError().finish())
-        .unwrap();
+    Identity::login(&request.extensions(), session_data.key())
+        .map_err(|err| LoginError::Session(err.to_string()))?;
 
-    println!("Logged user in");
+    println!("Logged user in with {}", provider.name);
 
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/"))
-        .finish()
+        .finish())
 }
 
+// Refreshes the session with the provider that issued it if its access token is about to run out,
+// then asks that provider for the user's profile. None once the provider won't refresh the session
+// anymore, the user is logged out then or sent to log in again with OAUTH_RELOGIN
 #[cfg(feature = "ssr")]
-async fn request_access_token(params: &[(&str, &str)]) -> Result<TokenResponse, reqwest::Error> {
-    let res = CLIENT
-        .post(format!("{}/v0/oauth/token", OAUTH_TOKEN_URL.to_string()))
-        .basic_auth(CLIENT_ID.to_string(), Some(CLIENT_SECRET.to_string()))
-        .header("Content-Type", "application/x-www-form-urlencoded")
-        .form(&params)
-        .send()
+pub async fn call_user_endpoint(
+    session_data: SessionData,
+) -> Result<Option<UserInfo>, ServerFnError> {
+    let registry: web::Data<ProviderRegistry> = extract().await?;
+    let refresher: web::Data<SessionRefresher> = extract().await?;
+    let store: web::Data<dyn TokenStore> = extract().await?;
+
+    let provider = registry.get(&session_data.provider).ok_or_else(|| {
+        ServerFnError::new(format!(
+            "Session is from unknown provider {}",
+            session_data.provider
+        ))
+    })?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+
+    let session_data = refresher
+        .fresh_session(session_data, provider, store.get_ref(), now)
         .await
-        .unwrap();
+        .map_err(|err| ServerFnError::new(format!("Error refreshing session!: {err}")))?;
 
-    res.json::<TokenResponse>().await
-}
+    let Some(session_data) = session_data else {
+        end_session().await?;
 
-#[cfg(feature = "ssr")]
-pub async fn refresh_access_token(refresh_token: String) -> Result<TokenResponse, ServerFnError> {
-    let params = [
-        ("grant_type", "refresh_token"),
-        ("refresh_token", &refresh_token),
-    ];
+        if *OAUTH_RELOGIN {
+            let login_url = start_login(provider, store.get_ref()).await?;
+            leptos_actix::redirect(&login_url);
+        }
+
+        return Ok(None);
+    };
 
-    request_access_token(&params)
+    provider
+        .user_info(&session_data)
         .await
-        .map_err(|err| ServerFnError::from(err))
+        .map(Some)
+        .map_err(|err| ServerFnError::new(format!("Error fetching user info!: {err}")))
 }
 
+// Logs out a user whose stored session is gone
 #[cfg(feature = "ssr")]
-pub async fn call_user_endpoint(
-    session_data: SessionData,
-) -> Result<UserInfoResponse, ServerFnError> {
-    use crate::server_functions::decrypt_string;
+pub async fn end_session() -> Result<(), ServerFnError> {
+    let user: Option<Identity> = extract().await?;
 
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
+    if let Some(user) = user {
+        Identity::logout(user);
+    }
+
+    Ok(())
+}
 
-    let redis_client: web::Data<RedisClient> = extract().await?;
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use std::{collections::HashMap, sync::Arc, time::Duration};
 
-    let mut con = redis_client.get_connection().unwrap();
+    use actix_identity::IdentityMiddleware;
+    use actix_session::{storage::CookieSessionStore, SessionMiddleware};
+    use actix_web::{
+        cookie::Key,
+        http::{header::LOCATION, StatusCode},
+        test, App,
+    };
+    use leptos::server_fn::ServerFn;
+    use serde_json::json;
 
-    let session_data = if session_data.expiry < now.try_into().unwrap() {
-        // Todo: handle expired refresh_token
-        let token_response =
-            refresh_access_token(decrypt_string(&session_data.refresh_token).unwrap()).await?;
+    use crate::{
+        server_functions::GetUserInfo,
+        services::{failing_token_store::FailingTokenStore, token_store_service::LoginState},
+    };
 
-        let username = token_response.username.clone();
+    use super::super::mock_auth_server::{callback, memory_store, MockAuthServer};
+    use super::*;
+
+    fn query_params(url: &str) -> HashMap<String, String> {
+        url.split_once('?')
+            .map(|(_, query)| query)
+            .unwrap_or_default()
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
 
-        let session_data = SessionData::from(token_response);
+    async fn start(
+        store: &web::Data<dyn TokenStore>,
+        registry: &web::Data<ProviderRegistry>,
+    ) -> HashMap<String, String> {
+        let provider = registry.get("mock").unwrap();
 
-        let session_string = serde_json::to_string(&session_data)?;
+        query_params(&start_login(provider, store.get_ref()).await.unwrap())
+    }
 
-        () = con.set(username.clone(), session_string)?;
+    #[actix_web::test]
+    async fn pkce_challenge_matches_the_verifier_sent_for_the_code() {
+        let server = MockAuthServer::issuing_tokens().await;
+        let registry = server.registry(Some(true)).await;
+        let store = memory_store();
 
-        session_data
-    } else {
-        session_data
-    };
+        let login = start(&store, &registry).await;
+        assert_eq!(login["code_challenge_method"], "S256");
 
-    let res = CLIENT
-        .get(format!(
-            "{}/v0/users/info?username={}",
-            OAUTH_TOKEN_URL.to_string(),
-            &session_data.username
-        ))
-        .bearer_auth(decrypt_string(&session_data.access_token).unwrap())
-        .send()
-        .await?;
+        let (status, location) = callback(
+            &store,
+            &registry,
+            &format!("/auth/mock?code=abc&state={}", login["state"]),
+        )
+        .await;
+        assert_eq!((status, location.as_str()), (StatusCode::FOUND, "/"));
 
-    res.json::<UserInfoResponse>()
-        .await
-        .map_err(|err| ServerFnError::from(err))
+        let requests = server.token_requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0]["grant_type"], "authorization_code");
+        assert_eq!(requests[0]["authorization_code"], "abc");
+
+        let code_verifier = &requests[0]["code_verifier"];
+        assert!((43..=128).contains(&code_verifier.len()));
+        assert_eq!(code_challenge(code_verifier), login["code_challenge"]);
+    }
+
+    #[actix_web::test]
+    async fn every_login_gets_its_own_verifier() {
+        let server = MockAuthServer::issuing_tokens().await;
+        let registry = server.registry(Some(true)).await;
+        let store = memory_store();
+
+        let first = start(&store, &registry).await;
+        let second = start(&store, &registry).await;
+
+        assert_ne!(first["code_challenge"], second["code_challenge"]);
+    }
+
+    // The only test that leaves pkce out of the providers file, so it alone reads OAUTH_PKCE
+    #[actix_web::test]
+    async fn oauth_pkce_false_leaves_pkce_out() {
+        std::env::set_var("OAUTH_PKCE", "false");
+
+        let server = MockAuthServer::issuing_tokens().await;
+        let registry = server.registry(None).await;
+        let store = memory_store();
+
+        assert!(!registry.get("mock").unwrap().pkce);
+
+        let login = start(&store, &registry).await;
+        assert!(!login.contains_key("code_challenge"));
+        assert!(!login.contains_key("code_challenge_method"));
+
+        let (status, _) = callback(
+            &store,
+            &registry,
+            &format!("/auth/mock?code=abc&state={}", login["state"]),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FOUND);
+
+        let requests = server.token_requests();
+        assert_eq!(requests.len(), 1);
+        assert!(!requests[0].contains_key("code_verifier"));
+    }
+
+    fn failing_store(failing: &'static str) -> web::Data<dyn TokenStore> {
+        web::Data::from(Arc::new(FailingTokenStore::new(failing)) as Arc<dyn TokenStore>)
+    }
+
+    #[actix_web::test]
+    async fn declined_logins_are_sent_to_the_login_failed_page() {
+        let server = MockAuthServer::issuing_tokens().await;
+        let registry = server.registry(Some(true)).await;
+        let store = memory_store();
+
+        let login = start(&store, &registry).await;
+
+        let (status, location) = callback(
+            &store,
+            &registry,
+            &format!("/auth/mock?error=access_denied&state={}", login["state"]),
+        )
+        .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        assert_eq!(location, "/login-failed?reason=denied&provider=mock");
+
+        // The declined login's state can't be used anymore
+        let (_, location) = callback(
+            &store,
+            &registry,
+            &format!("/auth/mock?code=abc&state={}", login["state"]),
+        )
+        .await;
+        assert_eq!(location, "/login-failed?reason=expired&provider=mock");
+        assert!(server.token_requests().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn provider_errors_are_sent_to_the_login_failed_page() {
+        let server = MockAuthServer::issuing_tokens().await;
+        let registry = server.registry(Some(true)).await;
+        let store = memory_store();
+
+        let login = start(&store, &registry).await;
+
+        let (status, location) = callback(
+            &store,
+            &registry,
+            &format!(
+                "/auth/mock?error=server_error&error_description=Down&state={}",
+                login["state"]
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        assert_eq!(location, "/login-failed?reason=failed&provider=mock");
+        assert!(server.token_requests().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn unknown_and_expired_states_are_sent_to_the_login_failed_page() {
+        let server = MockAuthServer::issuing_tokens().await;
+        let registry = server.registry(Some(true)).await;
+        let store = memory_store();
+
+        let expired = LoginState {
+            provider: "mock".to_string(),
+            ..Default::default()
+        };
+        store.add_state("expired", &expired, 1).await.unwrap();
+
+        for uri in [
+            "/auth/mock?code=abc&state=unknown",
+            "/auth/mock?code=abc&state=expired",
+            "/auth/mock?code=abc",
+        ] {
+            let (status, location) = callback(&store, &registry, uri).await;
+            assert_eq!(status, StatusCode::SEE_OTHER, "{uri}");
+            assert_eq!(
+                location, "/login-failed?reason=expired&provider=mock",
+                "{uri}"
+            );
+        }
+
+        assert!(server.token_requests().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn unknown_providers_are_sent_to_the_login_failed_page() {
+        let server = MockAuthServer::issuing_tokens().await;
+        let registry = server.registry(Some(true)).await;
+        let store = memory_store();
+
+        let login = start(&store, &registry).await;
+
+        // The login failed page can't offer to try a provider that doesn't exist again
+        let (status, location) = callback(
+            &store,
+            &registry,
+            &format!("/auth/other?code=abc&state={}", login["state"]),
+        )
+        .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        assert_eq!(location, "/login-failed?reason=unknown");
+    }
+
+    #[actix_web::test]
+    async fn token_endpoint_errors_are_sent_to_the_login_failed_page() {
+        let server = MockAuthServer::start(400, json!({ "error": "invalid_grant" })).await;
+        let registry = server.registry(Some(true)).await;
+        let store = memory_store();
+
+        let login = start(&store, &registry).await;
+
+        let (status, location) = callback(
+            &store,
+            &registry,
+            &format!("/auth/mock?code=abc&state={}", login["state"]),
+        )
+        .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        assert_eq!(location, "/login-failed?reason=failed&provider=mock");
+        assert_eq!(server.token_requests().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn store_failures_are_sent_to_the_login_failed_page() {
+        let server = MockAuthServer::issuing_tokens().await;
+        let registry = server.registry(Some(true)).await;
+
+        for failing in ["take_state", "save_session"] {
+            let store = failing_store(failing);
+            let login = start(&store, &registry).await;
+
+            let (status, location) = callback(
+                &store,
+                &registry,
+                &format!("/auth/mock?code=abc&state={}", login["state"]),
+            )
+            .await;
+            assert_eq!(status, StatusCode::SEE_OTHER, "{failing}");
+            assert_eq!(
+                location, "/login-failed?reason=unavailable&provider=mock",
+                "{failing}"
+            );
+        }
+
+        // Only the login that got its state back asked for tokens
+        assert_eq!(server.token_requests().len(), 1);
+    }
+
+    // Identity::login only fails when the session can't take the user's id, which a string id
+    // always fits in, so the callback can't be made to fail there
+    #[actix_web::test]
+    async fn session_failures_are_sent_to_the_login_failed_page() {
+        let response = LoginError::Session("Session is full".to_string()).redirect(Some("mock"));
+
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response.headers().get(LOCATION).unwrap(),
+            "/login-failed?reason=session&provider=mock"
+        );
+    }
+
+    // The refresh is refused with invalid_grant, so the next request for the user's details ends
+    // both the stored session and the login
+    #[actix_web::test]
+    async fn rejected_refreshes_log_the_user_out() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        // The login's tokens are already due for a refresh
+        let server = MockAuthServer::responding(
+            vec![
+                (200, MockAuthServer::tokens("access", now)),
+                (400, json!({ "error": "invalid_grant" })),
+            ],
+            Duration::ZERO,
+        )
+        .await;
+        let registry = server.registry(Some(true)).await;
+        let store = memory_store();
+
+        let app = test::init_service(
+            App::new()
+                .wrap(IdentityMiddleware::default())
+                .wrap(SessionMiddleware::new(
+                    CookieSessionStore::default(),
+                    Key::generate(),
+                ))
+                .app_data(store.clone())
+                .app_data(registry.clone())
+                .app_data(web::Data::new(SessionRefresher::default()))
+                .route("/auth/{provider}", web::get().to(handle_oauth_response))
+                .route("/api/{tail:.*}", leptos_actix::handle_server_fns()),
+        )
+        .await;
+
+        let login = start(&store, &registry).await;
+        let response = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri(&format!("/auth/mock?code=abc&state={}", login["state"]))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+
+        let login_cookie = response
+            .response()
+            .cookies()
+            .find(|cookie| cookie.name() == "id")
+            .unwrap()
+            .into_owned();
+        assert!(store.load_session("mock:octocat").await.unwrap().is_some());
+
+        let response = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri(GetUserInfo::PATH)
+                .cookie(login_cookie)
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // The session cookie is removed along with the stored session
+        let session_cookie = response
+            .response()
+            .cookies()
+            .find(|cookie| cookie.name() == "id")
+            .unwrap()
+            .into_owned();
+        assert_eq!(session_cookie.value(), "");
+        assert!(store.load_session("mock:octocat").await.unwrap().is_none());
+
+        assert_eq!(test::read_body(response).await, "null");
+
+        let requests = server.token_requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1]["grant_type"], "refresh_token");
+        assert_eq!(requests[1]["refresh_token"], "access-refresh");
+    }
 }
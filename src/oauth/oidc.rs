@@ -0,0 +1,507 @@
+use std::{
+    sync::RwLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{Jwk, JwkSet},
+    Algorithm, DecodingKey, Header, Validation,
+};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use super::login_error::TokenEndpointError;
+
+pub type OidcError = Box<dyn std::error::Error + Send + Sync>;
+
+// An unknown key id refetches the JWKS in case the provider rotated its keys, but no more often
+// than this so forged tokens can't hammer the provider
+const JWKS_REFETCH_SECONDS: u64 = 60;
+
+// The parts of /.well-known/openid-configuration the login flow uses
+#[derive(Deserialize, Clone)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: Option<String>,
+    pub jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+pub struct OidcTokenResponse {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_in: Option<i64>,
+    pub id_token: Option<String>,
+}
+
+// Claims from an ID token or userinfo response, mapped to a profile by the provider's settings
+pub type Claims = Map<String, Value>;
+
+// The user the claims are about, which sessions from this provider are keyed by
+pub fn subject(claims: &Claims) -> Result<&str, OidcError> {
+    claims
+        .get("sub")
+        .and_then(Value::as_str)
+        .ok_or_else(|| "Claims have no subject".into())
+}
+
+// A standards-compliant OpenID Connect provider, set up from its discovery document
+pub struct OidcProvider {
+    client: Client,
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+    scopes: String,
+    pub metadata: ProviderMetadata,
+    // Signing keys and when they were fetched
+    jwks: RwLock<(JwkSet, u64)>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+impl OidcProvider {
+    pub async fn discover(
+        client: Client,
+        issuer: &str,
+        client_id: String,
+        client_secret: String,
+        redirect_uri: String,
+        scopes: String,
+    ) -> Result<Self, OidcError> {
+        let metadata: ProviderMetadata = client
+            .get(format!(
+                "{}/.well-known/openid-configuration",
+                issuer.trim_end_matches('/')
+            ))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        // ID tokens are checked against the issuer, so it has to be the one that was configured
+        if metadata.issuer.trim_end_matches('/') != issuer.trim_end_matches('/') {
+            return Err(format!(
+                "Discovery document is for {}, not {issuer}",
+                metadata.issuer
+            )
+            .into());
+        }
+
+        let jwks = fetch_jwks(&client, &metadata.jwks_uri).await?;
+
+        println!("Discovered OpenID Connect provider {}", metadata.issuer);
+
+        Ok(OidcProvider {
+            client,
+            client_id,
+            client_secret,
+            redirect_uri,
+            scopes,
+            metadata,
+            jwks: RwLock::new((jwks, now())),
+        })
+    }
+
+    pub fn authorization_url(
+        &self,
+        state: &str,
+        nonce: &str,
+        code_challenge: Option<&str>,
+    ) -> Result<String, OidcError> {
+        let mut params = vec![
+            ("response_type", "code"),
+            ("client_id", self.client_id.as_str()),
+            ("redirect_uri", self.redirect_uri.as_str()),
+            ("scope", self.scopes.as_str()),
+            ("state", state),
+            ("nonce", nonce),
+        ];
+
+        if let Some(code_challenge) = code_challenge {
+            params.push(("code_challenge", code_challenge));
+            params.push(("code_challenge_method", "S256"));
+        }
+
+        Ok(Url::parse_with_params(&self.metadata.authorization_endpoint, &params)?.to_string())
+    }
+
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: Option<&str>,
+    ) -> Result<OidcTokenResponse, OidcError> {
+        let mut params = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.redirect_uri.as_str()),
+        ];
+
+        if let Some(code_verifier) = code_verifier {
+            params.push(("code_verifier", code_verifier));
+        }
+
+        self.request_token(&params).await
+    }
+
+    pub async fn refresh(&self, refresh_token: &str) -> Result<OidcTokenResponse, OidcError> {
+        self.request_token(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+        ])
+        .await
+    }
+
+    async fn request_token(&self, params: &[(&str, &str)]) -> Result<OidcTokenResponse, OidcError> {
+        let response = self
+            .client
+            .post(&self.metadata.token_endpoint)
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(params)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(TokenEndpointError::from_response(response).await.into());
+        }
+
+        Ok(response.json().await?)
+    }
+
+    // Checks the signature against the provider's keys, along with the issuer, audience and expiry.
+    // Tokens from a login must carry the nonce that login was started with, refreshed ones needn't
+    pub async fn verify_id_token(
+        &self,
+        id_token: &str,
+        nonce: Option<&str>,
+    ) -> Result<Claims, OidcError> {
+        let header = decode_header(id_token)?;
+
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err("ID tokens signed with a shared secret aren't accepted".into());
+        }
+
+        let key = self.decoding_key(&header).await?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.metadata.issuer]);
+        validation.set_audience(&[&self.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        let claims = decode::<Claims>(id_token, &key, &validation)?.claims;
+
+        if let Some(nonce) = nonce {
+            if claims.get("nonce").and_then(Value::as_str) != Some(nonce) {
+                return Err("ID token nonce doesn't match the login".into());
+            }
+        }
+
+        subject(&claims)?;
+
+        Ok(claims)
+    }
+
+    pub async fn user_info(&self, access_token: &str) -> Result<Option<Claims>, OidcError> {
+        let Some(userinfo_endpoint) = &self.metadata.userinfo_endpoint else {
+            return Ok(None);
+        };
+
+        Ok(Some(
+            self.client
+                .get(userinfo_endpoint)
+                .bearer_auth(access_token)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?,
+        ))
+    }
+
+    async fn decoding_key(&self, header: &Header) -> Result<DecodingKey, OidcError> {
+        let fetched_at = {
+            let jwks = self.jwks.read().map_err(|_| "JWKS lock poisoned")?;
+            if let Some(jwk) = find_key(&jwks.0, header) {
+                return Ok(DecodingKey::from_jwk(jwk)?);
+            }
+            jwks.1
+        };
+
+        if now() < fetched_at + JWKS_REFETCH_SECONDS {
+            return Err("ID token is signed with an unknown key".into());
+        }
+
+        let refetched = fetch_jwks(&self.client, &self.metadata.jwks_uri).await?;
+        let key = find_key(&refetched, header).map(DecodingKey::from_jwk);
+
+        *self.jwks.write().map_err(|_| "JWKS lock poisoned")? = (refetched, now());
+
+        Ok(key.ok_or("ID token is signed with an unknown key")??)
+    }
+}
+
+// Tokens without a key id are only accepted when the provider has a single key
+fn find_key<'a>(jwks: &'a JwkSet, header: &Header) -> Option<&'a Jwk> {
+    match &header.kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+}
+
+async fn fetch_jwks(client: &Client, jwks_uri: &str) -> Result<JwkSet, OidcError> {
+    Ok(client
+        .get(jwks_uri)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use jsonwebtoken::{encode, EncodingKey};
+    use openssl::rsa::Rsa;
+    use serde_json::json;
+
+    use super::*;
+
+    const CLIENT_ID: &str = "site";
+    const NONCE: &str = "login-nonce";
+
+    // A provider that publishes one freshly generated RSA key, and the key to sign tokens with
+    struct MockIssuer {
+        url: String,
+        signing_key: EncodingKey,
+    }
+
+    impl MockIssuer {
+        async fn start() -> Self {
+            let rsa = Rsa::generate(2048).unwrap();
+            let signing_key =
+                EncodingKey::from_rsa_pem(&rsa.private_key_to_pem().unwrap()).unwrap();
+
+            let jwks = json!({
+                "keys": [{
+                    "kty": "RSA",
+                    "kid": "test-key",
+                    "alg": "RS256",
+                    "use": "sig",
+                    "n": URL_SAFE_NO_PAD.encode(rsa.n().to_vec()),
+                    "e": URL_SAFE_NO_PAD.encode(rsa.e().to_vec()),
+                }]
+            });
+
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+
+            let metadata = json!({
+                "issuer": url,
+                "authorization_endpoint": format!("{url}/authorize"),
+                "token_endpoint": format!("{url}/token"),
+                "jwks_uri": format!("{url}/jwks"),
+            });
+
+            let server = HttpServer::new(move || {
+                let metadata = metadata.clone();
+                let jwks = jwks.clone();
+
+                App::new()
+                    .route(
+                        "/.well-known/openid-configuration",
+                        web::get().to(move || {
+                            let metadata = metadata.clone();
+                            async move { HttpResponse::Ok().json(metadata) }
+                        }),
+                    )
+                    .route(
+                        "/jwks",
+                        web::get().to(move || {
+                            let jwks = jwks.clone();
+                            async move { HttpResponse::Ok().json(jwks) }
+                        }),
+                    )
+            })
+            .workers(1)
+            .listen(listener)
+            .unwrap();
+
+            actix_web::rt::spawn(server.run());
+
+            MockIssuer { url, signing_key }
+        }
+
+        async fn provider(&self) -> OidcProvider {
+            OidcProvider::discover(
+                Client::new(),
+                &self.url,
+                CLIENT_ID.to_string(),
+                "secret".to_string(),
+                "http://localhost/auth/test".to_string(),
+                "openid".to_string(),
+            )
+            .await
+            .unwrap()
+        }
+
+        // Claims a login's ID token would have, with `changes` applied on top
+        fn claims(&self, changes: Value) -> Value {
+            let mut claims = json!({
+                "iss": self.url,
+                "aud": CLIENT_ID,
+                "sub": "user-1",
+                "iat": now(),
+                "exp": now() + 300,
+                "nonce": NONCE,
+            });
+
+            for (claim, value) in changes.as_object().unwrap() {
+                claims[claim] = value.clone();
+            }
+
+            claims
+        }
+
+        fn id_token(&self, changes: Value) -> String {
+            let mut header = Header::new(Algorithm::RS256);
+            header.kid = Some("test-key".to_string());
+
+            encode(&header, &self.claims(changes), &self.signing_key).unwrap()
+        }
+    }
+
+    #[actix_web::test]
+    async fn valid_id_tokens_are_accepted() {
+        let issuer = MockIssuer::start().await;
+        let provider = issuer.provider().await;
+
+        let claims = provider
+            .verify_id_token(&issuer.id_token(json!({})), Some(NONCE))
+            .await
+            .unwrap();
+        assert_eq!(subject(&claims).unwrap(), "user-1");
+
+        // Refreshed tokens aren't checked for the login's nonce
+        let refreshed = issuer.id_token(json!({ "nonce": null }));
+        assert!(provider.verify_id_token(&refreshed, None).await.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn id_tokens_from_another_issuer_are_rejected() {
+        let issuer = MockIssuer::start().await;
+        let provider = issuer.provider().await;
+
+        let id_token = issuer.id_token(json!({ "iss": "https://evil.example.com" }));
+        assert!(provider
+            .verify_id_token(&id_token, Some(NONCE))
+            .await
+            .is_err());
+    }
+
+    #[actix_web::test]
+    async fn id_tokens_for_another_client_are_rejected() {
+        let issuer = MockIssuer::start().await;
+        let provider = issuer.provider().await;
+
+        let id_token = issuer.id_token(json!({ "aud": "some-other-site" }));
+        assert!(provider
+            .verify_id_token(&id_token, Some(NONCE))
+            .await
+            .is_err());
+    }
+
+    #[actix_web::test]
+    async fn expired_id_tokens_are_rejected() {
+        let issuer = MockIssuer::start().await;
+        let provider = issuer.provider().await;
+
+        // Well past the default leeway of a minute
+        let id_token = issuer.id_token(json!({ "exp": now() - 3600 }));
+        assert!(provider
+            .verify_id_token(&id_token, Some(NONCE))
+            .await
+            .is_err());
+
+        let id_token = issuer.id_token(json!({ "exp": null }));
+        assert!(provider
+            .verify_id_token(&id_token, Some(NONCE))
+            .await
+            .is_err());
+    }
+
+    #[actix_web::test]
+    async fn id_tokens_from_another_login_are_rejected() {
+        let issuer = MockIssuer::start().await;
+        let provider = issuer.provider().await;
+
+        let id_token = issuer.id_token(json!({ "nonce": "another-login" }));
+        assert!(provider
+            .verify_id_token(&id_token, Some(NONCE))
+            .await
+            .is_err());
+
+        let id_token = issuer.id_token(json!({ "nonce": null }));
+        assert!(provider
+            .verify_id_token(&id_token, Some(NONCE))
+            .await
+            .is_err());
+    }
+
+    #[actix_web::test]
+    async fn id_tokens_signed_with_a_shared_secret_are_rejected() {
+        let issuer = MockIssuer::start().await;
+        let provider = issuer.provider().await;
+        let claims = issuer.claims(json!({}));
+
+        // Whether the secret is a guess or the provider's public key
+        let public_key = provider.jwks.read().unwrap().0.keys[0].clone();
+        for secret in [
+            b"guessed secret".to_vec(),
+            serde_json::to_vec(&public_key).unwrap(),
+        ] {
+            let id_token = encode(
+                &Header::new(Algorithm::HS256),
+                &claims,
+                &EncodingKey::from_secret(&secret),
+            )
+            .unwrap();
+
+            assert!(provider
+                .verify_id_token(&id_token, Some(NONCE))
+                .await
+                .is_err());
+        }
+    }
+
+    #[actix_web::test]
+    async fn id_tokens_signed_with_another_key_are_rejected() {
+        let issuer = MockIssuer::start().await;
+        let forger = MockIssuer::start().await;
+        let provider = issuer.provider().await;
+
+        // The forger's token claims to come from the issuer and uses its key id
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some("test-key".to_string());
+        let id_token = encode(&header, &issuer.claims(json!({})), &forger.signing_key).unwrap();
+
+        assert!(provider
+            .verify_id_token(&id_token, Some(NONCE))
+            .await
+            .is_err());
+    }
+}
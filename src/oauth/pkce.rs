@@ -0,0 +1,20 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::{distr::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+
+// RFC 7636 allows anywhere from 43 to 128 characters
+const CODE_VERIFIER_LENGTH: usize = 64;
+
+// A fresh secret for every login, only sent to the auth server with the token exchange
+pub fn generate_code_verifier() -> String {
+    rand::rng()
+        .sample_iter(Alphanumeric)
+        .take(CODE_VERIFIER_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+// The S256 challenge sent with the login redirect, base64url without padding
+pub fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
@@ -0,0 +1,570 @@
+use std::{fs, io::ErrorKind};
+
+use lazy_static::lazy_static;
+use reqwest::{Certificate, Client};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    server_functions::{decrypt_string, get_env_variable},
+    LoginProvider, UserInfo,
+};
+
+use super::{
+    auth_server::AuthServerProvider,
+    oidc::{subject, Claims, OidcError, OidcProvider},
+    SessionData,
+};
+
+lazy_static! {
+    static ref OAUTH_PROVIDERS_FILE: String = get_env_variable("OAUTH_PROVIDERS_FILE")
+        .unwrap_or_else(|| "oauth_providers.toml".to_string());
+    // Sends a PKCE challenge with every login, unless a provider turns it off
+    static ref OAUTH_PKCE: bool = get_env_variable("OAUTH_PKCE")
+        .map(|enabled| enabled != "false" && enabled != "0")
+        .unwrap_or(true);
+}
+
+pub type OauthError = OidcError;
+
+fn load_certificate() -> Result<Certificate, OauthError> {
+    Ok(Certificate::from_pem(&fs::read("/certs/cert.pem")?)?)
+}
+
+fn http_client() -> Result<Client, OauthError> {
+    Ok(Client::builder()
+        .danger_accept_invalid_hostnames(true)
+        .add_root_certificate(load_certificate()?)
+        .build()?)
+}
+
+#[derive(Deserialize)]
+struct ProvidersFile {
+    providers: Vec<ProviderConfig>,
+}
+
+// One [[providers]] entry of OAUTH_PROVIDERS_FILE
+#[derive(Deserialize)]
+struct ProviderConfig {
+    // Used in the /auth/{name} callback, so only lowercase letters, digits and dashes
+    name: String,
+    display_name: Option<String>,
+    client_id: String,
+    // The secret itself stays in the environment
+    client_secret_env: String,
+    pkce: Option<bool>,
+    #[serde(default)]
+    claims: ClaimMapping,
+    #[serde(flatten)]
+    kind: ProviderKindConfig,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum ProviderKindConfig {
+    AuthServer {
+        login_url: String,
+        api_url: String,
+    },
+    Oidc {
+        issuer_url: String,
+        redirect_uri: String,
+        scopes: Option<String>,
+        profile_url: Option<String>,
+    },
+}
+
+// Which claims fill in each part of the profile. Each is a list of claim names, the first one
+// present is used
+#[derive(Deserialize, Default, Clone)]
+pub struct ClaimMapping {
+    first_name: Option<Vec<String>>,
+    last_name: Option<Vec<String>>,
+    username: Option<Vec<String>>,
+}
+
+fn claim_names(mapping: &Option<Vec<String>>, default: &[&str]) -> Vec<String> {
+    mapping
+        .clone()
+        .unwrap_or_else(|| default.iter().map(|claim| claim.to_string()).collect())
+}
+
+impl ClaimMapping {
+    fn with_defaults(self, first_name: &[&str], last_name: &[&str], username: &[&str]) -> Self {
+        ClaimMapping {
+            first_name: Some(claim_names(&self.first_name, first_name)),
+            last_name: Some(claim_names(&self.last_name, last_name)),
+            username: Some(claim_names(&self.username, username)),
+        }
+    }
+
+    fn pick(claims: &Claims, names: &Option<Vec<String>>) -> String {
+        names
+            .iter()
+            .flatten()
+            .find_map(|name| claims.get(name).and_then(Value::as_str))
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    pub fn user_info(&self, claims: &Claims) -> UserInfo {
+        UserInfo {
+            first_name: Self::pick(claims, &self.first_name),
+            last_name: Self::pick(claims, &self.last_name),
+            username: Self::pick(claims, &self.username),
+        }
+    }
+}
+
+pub enum ProviderKind {
+    AuthServer(AuthServerProvider),
+    // Along with the provider's profile page, if it has one
+    Oidc(Box<OidcProvider>, Option<String>),
+}
+
+pub struct OauthProvider {
+    pub name: String,
+    pub display_name: String,
+    pub pkce: bool,
+    claims: ClaimMapping,
+    kind: ProviderKind,
+}
+
+impl OauthProvider {
+    async fn from_config(config: ProviderConfig, client: Client) -> Result<Self, OauthError> {
+        if config.name.is_empty()
+            || !config
+                .name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            return Err(format!("Invalid provider name: {}", config.name).into());
+        }
+
+        let client_secret = get_env_variable(&config.client_secret_env)
+            .ok_or_else(|| format!("{} is unset!", config.client_secret_env))?;
+
+        let (kind, claims) = match config.kind {
+            ProviderKindConfig::AuthServer { login_url, api_url } => (
+                ProviderKind::AuthServer(AuthServerProvider {
+                    client,
+                    login_url,
+                    api_url,
+                    client_id: config.client_id,
+                    client_secret,
+                }),
+                config
+                    .claims
+                    .with_defaults(&["first_name"], &["last_name"], &["username"]),
+            ),
+            ProviderKindConfig::Oidc {
+                issuer_url,
+                redirect_uri,
+                scopes,
+                profile_url,
+            } => (
+                ProviderKind::Oidc(
+                    Box::new(
+                        OidcProvider::discover(
+                            client,
+                            &issuer_url,
+                            config.client_id,
+                            client_secret,
+                            redirect_uri,
+                            scopes.unwrap_or_else(|| "openid profile email".to_string()),
+                        )
+                        .await?,
+                    ),
+                    profile_url,
+                ),
+                config.claims.with_defaults(
+                    &["given_name"],
+                    &["family_name"],
+                    &["preferred_username", "email", "sub"],
+                ),
+            ),
+        };
+
+        Ok(OauthProvider {
+            display_name: config.display_name.unwrap_or_else(|| config.name.clone()),
+            name: config.name,
+            pkce: config.pkce.unwrap_or_else(|| *OAUTH_PKCE),
+            claims,
+            kind,
+        })
+    }
+
+    // Only OpenID Connect ties its ID tokens to a login with a nonce
+    pub fn uses_nonce(&self) -> bool {
+        matches!(self.kind, ProviderKind::Oidc(..))
+    }
+
+    pub fn authorization_url(
+        &self,
+        state: &str,
+        nonce: Option<&str>,
+        code_challenge: Option<&str>,
+    ) -> Result<String, OauthError> {
+        match &self.kind {
+            ProviderKind::AuthServer(auth_server) => {
+                Ok(auth_server.authorization_url(state, code_challenge))
+            }
+            ProviderKind::Oidc(oidc, _) => oidc.authorization_url(
+                state,
+                nonce.ok_or("Login was started without a nonce")?,
+                code_challenge,
+            ),
+        }
+    }
+
+    // Exchanges the code for tokens. OpenID Connect logins also check the ID token that comes
+    // back was issued for this login
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: Option<&str>,
+        nonce: Option<&str>,
+    ) -> Result<SessionData, OauthError> {
+        let mut session_data = match &self.kind {
+            ProviderKind::AuthServer(auth_server) => {
+                SessionData::from(auth_server.exchange_code(code, code_verifier).await?)
+            }
+            ProviderKind::Oidc(oidc, _) => {
+                let nonce = nonce.ok_or("Login was started without a nonce")?;
+
+                let tokens = oidc.exchange_code(code, code_verifier).await?;
+
+                let id_token = tokens
+                    .id_token
+                    .as_deref()
+                    .ok_or("Token response has no ID token")?;
+
+                let claims = oidc.verify_id_token(id_token, Some(nonce)).await?;
+
+                SessionData::from_oidc(
+                    subject(&claims)?.to_string(),
+                    Some(self.claims.user_info(&claims)),
+                    tokens,
+                )
+            }
+        };
+
+        session_data.provider = self.name.clone();
+
+        Ok(session_data)
+    }
+
+    // Trades the session's refresh token for new tokens from the provider that issued it
+    pub async fn refresh(&self, session_data: SessionData) -> Result<SessionData, OauthError> {
+        let refresh_token = decrypt_string(
+            session_data
+                .refresh_token
+                .as_ref()
+                .ok_or("Session has no refresh token")?,
+        )
+        .map_err(|_| "Could not decrypt refresh token")?;
+
+        let mut refreshed = match &self.kind {
+            ProviderKind::AuthServer(auth_server) => {
+                SessionData::from(auth_server.refresh(&refresh_token).await?)
+            }
+            ProviderKind::Oidc(oidc, _) => {
+                let tokens = oidc.refresh(&refresh_token).await?;
+
+                // A new ID token has to be for the same user, nonce is only checked on login
+                let profile = match &tokens.id_token {
+                    Some(id_token) => {
+                        let claims = oidc.verify_id_token(id_token, None).await?;
+
+                        if subject(&claims)? != session_data.username {
+                            return Err("Refreshed ID token is for another user".into());
+                        }
+
+                        Some(self.claims.user_info(&claims))
+                    }
+                    None => session_data.profile.clone(),
+                };
+
+                let mut refreshed =
+                    SessionData::from_oidc(session_data.username.clone(), profile, tokens);
+                // Providers that don't rotate refresh tokens leave them out of the response
+                refreshed.refresh_token = refreshed.refresh_token.or(session_data.refresh_token);
+
+                refreshed
+            }
+        };
+
+        // Kept as it was, even for sessions from before providers were named, so the session key
+        // the user is logged in with doesn't change
+        refreshed.provider = session_data.provider;
+
+        Ok(refreshed)
+    }
+
+    // The latest profile from the provider. OpenID Connect providers without a userinfo endpoint
+    // fall back to the profile from the ID token
+    pub async fn user_info(&self, session_data: &SessionData) -> Result<UserInfo, OauthError> {
+        let access_token = decrypt_string(&session_data.access_token)
+            .map_err(|_| "Could not decrypt access token")?;
+
+        match &self.kind {
+            ProviderKind::AuthServer(auth_server) => {
+                let user_data = auth_server
+                    .user_info(&access_token, &session_data.username)
+                    .await?;
+
+                Ok(self.claims.user_info(&user_data))
+            }
+            ProviderKind::Oidc(oidc, _) => match oidc.user_info(&access_token).await? {
+                Some(claims) if subject(&claims)? != session_data.username => {
+                    Err("Userinfo response is for another user".into())
+                }
+                Some(claims) => Ok(self.claims.user_info(&claims)),
+                None => session_data
+                    .profile
+                    .clone()
+                    .ok_or_else(|| "Session has no profile".into()),
+            },
+        }
+    }
+
+    // OpenID Connect has no standard profile page, so those providers can set profile_url
+    pub fn profile_url(&self) -> String {
+        match &self.kind {
+            ProviderKind::AuthServer(auth_server) => auth_server.profile_url(),
+            ProviderKind::Oidc(_, profile_url) => {
+                profile_url.clone().unwrap_or_else(|| "/".to_string())
+            }
+        }
+    }
+}
+
+// Every provider users can log in with, in the order they're offered. The first one is the
+// default, which handles the plain /auth callback
+pub struct ProviderRegistry {
+    providers: Vec<OauthProvider>,
+}
+
+impl ProviderRegistry {
+    // Reads OAUTH_PROVIDERS_FILE, or builds a single provider from the older CLIENT_ID,
+    // OAUTH_REDIRECT_URL, OAUTH_TOKEN_URL and OIDC_* variables when there's no such file
+    pub async fn load() -> Result<Self, OauthError> {
+        let configs = match fs::read_to_string(OAUTH_PROVIDERS_FILE.as_str()) {
+            Ok(contents) => toml::from_str::<ProvidersFile>(&contents)?.providers,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                env_provider_config()?.into_iter().collect()
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut providers: Vec<OauthProvider> = Vec::new();
+
+        for config in configs {
+            if providers
+                .iter()
+                .any(|provider| provider.name == config.name)
+            {
+                return Err(format!("Provider {} is configured twice", config.name).into());
+            }
+
+            providers.push(OauthProvider::from_config(config, http_client()?).await?);
+        }
+
+        if providers.is_empty() {
+            println!("No OAuth providers configured, login is disabled");
+        } else {
+            println!(
+                "Loaded OAuth providers: {}",
+                providers
+                    .iter()
+                    .map(|provider| provider.name.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            );
+        }
+
+        Ok(ProviderRegistry { providers })
+    }
+
+    // Sessions and login states from before providers were named have none, and belong to the
+    // default provider
+    pub fn get(&self, name: &str) -> Option<&OauthProvider> {
+        match name {
+            "" => self.providers.first(),
+            name => self.providers.iter().find(|provider| provider.name == name),
+        }
+    }
+
+    pub fn login_providers(&self) -> Vec<LoginProvider> {
+        self.providers
+            .iter()
+            .map(|provider| LoginProvider {
+                name: provider.name.clone(),
+                display_name: provider.display_name.clone(),
+            })
+            .collect()
+    }
+}
+
+fn env_provider_config() -> Result<Option<ProviderConfig>, OauthError> {
+    let Some(client_id) = get_env_variable("CLIENT_ID") else {
+        return Ok(None);
+    };
+
+    let kind = match get_env_variable("OIDC_ISSUER_URL") {
+        Some(issuer_url) => ProviderKindConfig::Oidc {
+            issuer_url,
+            redirect_uri: get_env_variable("OIDC_REDIRECT_URI")
+                .ok_or("OIDC_REDIRECT_URI is unset!")?,
+            scopes: get_env_variable("OIDC_SCOPES"),
+            profile_url: get_env_variable("OIDC_PROFILE_URL"),
+        },
+        None => ProviderKindConfig::AuthServer {
+            login_url: get_env_variable("OAUTH_REDIRECT_URL")
+                .ok_or("OAUTH_REDIRECT_URL is unset!")?,
+            api_url: get_env_variable("OAUTH_TOKEN_URL").ok_or("OAUTH_TOKEN_URL is unset!")?,
+        },
+    };
+
+    Ok(Some(ProviderConfig {
+        name: "default".to_string(),
+        display_name: None,
+        client_id,
+        client_secret_env: "CLIENT_SECRET".to_string(),
+        pkce: None,
+        claims: ClaimMapping::default(),
+        kind,
+    }))
+}
+
+#[cfg(test)]
+impl ProviderRegistry {
+    // Providers from the contents of a providers file, talking to mock servers over plain HTTP
+    pub(crate) async fn from_toml(contents: &str) -> Result<Self, OauthError> {
+        let mut providers = Vec::new();
+
+        for config in toml::from_str::<ProvidersFile>(contents)?.providers {
+            providers.push(OauthProvider::from_config(config, Client::new()).await?);
+        }
+
+        Ok(ProviderRegistry { providers })
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use actix_web::{http::StatusCode, web};
+
+    use crate::services::token_store_service::LoginState;
+
+    use super::super::{
+        mock_auth_server::{callback, memory_store, registry, MockAuthServer},
+        TokenResponse,
+    };
+    use super::*;
+
+    // Two providers, "work" first so it's the default
+    async fn two_providers() -> (MockAuthServer, MockAuthServer, web::Data<ProviderRegistry>) {
+        let work = MockAuthServer::issuing_tokens().await;
+        let personal = MockAuthServer::issuing_tokens().await;
+        let registry = registry(&format!(
+            "{}{}",
+            work.provider("work", Some(true)),
+            personal.provider("personal", Some(true))
+        ))
+        .await;
+
+        (work, personal, registry)
+    }
+
+    fn session(provider: &str) -> SessionData {
+        let mut session_data = SessionData::from(TokenResponse {
+            success: true,
+            access_token: "old".to_string(),
+            refresh_token: "old-refresh".to_string(),
+            username: "octocat".to_string(),
+            expiry: 0,
+        });
+        session_data.provider = provider.to_string();
+
+        session_data
+    }
+
+    #[actix_web::test]
+    async fn refreshes_go_to_the_provider_that_issued_the_session() {
+        let (work, personal, registry) = two_providers().await;
+
+        let session_data = session("personal");
+        let refreshed = registry
+            .get(&session_data.provider)
+            .unwrap()
+            .refresh(session_data)
+            .await
+            .unwrap();
+
+        assert_eq!(refreshed.provider, "personal");
+        assert!(work.token_requests().is_empty());
+
+        let requests = personal.token_requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0]["grant_type"], "refresh_token");
+        assert_eq!(requests[0]["refresh_token"], "old-refresh");
+    }
+
+    #[actix_web::test]
+    async fn sessions_without_a_provider_are_refreshed_by_the_default() {
+        let (work, personal, registry) = two_providers().await;
+
+        let refreshed = registry
+            .get("")
+            .unwrap()
+            .refresh(session(""))
+            .await
+            .unwrap();
+
+        // Still without one, so the session key doesn't change
+        assert_eq!(refreshed.provider, "");
+        assert_eq!(work.token_requests().len(), 1);
+        assert!(personal.token_requests().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn unknown_provider_callbacks_are_rejected() {
+        let (work, personal, registry) = two_providers().await;
+        let store = memory_store();
+        assert!(registry.get("other").is_none());
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let login_state = LoginState {
+            provider: "work".to_string(),
+            ..Default::default()
+        };
+        store
+            .add_state("work-login", &login_state, now + 300)
+            .await
+            .unwrap();
+
+        let (status, location) =
+            callback(&store, &registry, "/auth/other?code=abc&state=work-login").await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        assert_eq!(location, "/login-failed?reason=unknown");
+
+        // A login started with one provider can't be finished through another's callback
+        let (status, location) = callback(
+            &store,
+            &registry,
+            "/auth/personal?code=abc&state=work-login",
+        )
+        .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        assert_eq!(location, "/login-failed?reason=expired&provider=personal");
+
+        assert!(work.token_requests().is_empty());
+        assert!(personal.token_requests().is_empty());
+    }
+}
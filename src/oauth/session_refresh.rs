@@ -0,0 +1,343 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use lazy_static::lazy_static;
+
+use crate::{server_functions::get_env_variable, services::token_store_service::TokenStore};
+
+use super::{
+    login_error::TokenEndpointError,
+    provider_registry::{OauthError, OauthProvider},
+    SessionData,
+};
+
+lazy_static! {
+    // Sessions are refreshed this long before their access tokens run out, so a token doesn't
+    // expire between being checked and being used
+    static ref OAUTH_REFRESH_MARGIN: i64 = get_env_variable("OAUTH_REFRESH_MARGIN_SECS")
+        .and_then(|margin| margin.parse().ok())
+        .unwrap_or(60);
+    // Sends users whose session ended back to their provider to log in again, rather than just
+    // logging them out
+    pub static ref OAUTH_RELOGIN: bool = get_env_variable("OAUTH_RELOGIN")
+        .map(|enabled| enabled == "true" || enabled == "1")
+        .unwrap_or(false);
+}
+
+// Refreshes sessions that are about to run out. Every session gets its own lock, so when several
+// requests for one user need a refresh at once only the first one asks the provider and the rest
+// pick up its tokens from the store
+#[derive(Default)]
+pub struct SessionRefresher {
+    locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl SessionRefresher {
+    // The session with tokens that are good for at least the margin, or None if the provider won't
+    // refresh it anymore. Ended sessions are removed from the store
+    pub async fn fresh_session(
+        &self,
+        session_data: SessionData,
+        provider: &OauthProvider,
+        store: &dyn TokenStore,
+        now: i64,
+    ) -> Result<Option<SessionData>, OauthError> {
+        if !needs_refresh(&session_data, now) {
+            return Ok(Some(session_data));
+        }
+
+        let key = session_data.key();
+        let entry = self.lock_for(&key)?;
+        let _guard = entry.lock.lock().await;
+
+        self.refresh_locked(&key, &session_data.access_token, provider, store, now)
+            .await
+    }
+
+    async fn refresh_locked(
+        &self,
+        key: &str,
+        // What the request saw before waiting for the lock
+        access_token: &str,
+        provider: &OauthProvider,
+        store: &dyn TokenStore,
+        now: i64,
+    ) -> Result<Option<SessionData>, OauthError> {
+        // Another request may have refreshed or ended the session while this one was waiting
+        let Some(session_data) = store.load_session(key).await? else {
+            return Ok(None);
+        };
+
+        if session_data.access_token != access_token {
+            return Ok(Some(session_data));
+        }
+
+        // Without a refresh token the session lasts as long as its access token
+        if session_data.refresh_token.is_none() {
+            if session_data.expiry > now {
+                return Ok(Some(session_data));
+            }
+
+            println!("Session for {key} expired without a refresh token");
+            store.delete_session(key).await?;
+            return Ok(None);
+        }
+
+        match provider.refresh(session_data.clone()).await {
+            Ok(refreshed) => {
+                store.save_session(&refreshed).await?;
+                Ok(Some(refreshed))
+            }
+            Err(err) if refresh_rejected(&err) => {
+                println!("Ending session for {key}, refresh was rejected: {err}");
+                store.delete_session(key).await?;
+                Ok(None)
+            }
+            // The provider may just be down for a moment, the current tokens are fine until they expire
+            Err(err) if session_data.expiry > now => {
+                println!("Could not refresh session for {key} early: {err}");
+                Ok(Some(session_data))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn lock_for(&self, key: &str) -> Result<LockEntry<'_>, OauthError> {
+        let mut locks = self
+            .locks
+            .lock()
+            .map_err(|_| "Session refresh locks poisoned")?;
+
+        Ok(LockEntry {
+            refresher: self,
+            key: key.to_string(),
+            lock: locks.entry(key.to_string()).or_default().clone(),
+        })
+    }
+}
+
+// A request's hold on a session's lock. Dropping it forgets the lock once no other request is
+// waiting on it, which also happens when the request is cancelled halfway through a refresh
+struct LockEntry<'a> {
+    refresher: &'a SessionRefresher,
+    key: String,
+    lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Drop for LockEntry<'_> {
+    fn drop(&mut self) {
+        let Ok(mut locks) = self.refresher.locks.lock() else {
+            return;
+        };
+
+        // This entry's own reference is only dropped after this, so it's the one left over
+        if locks
+            .get(&self.key)
+            .is_some_and(|lock| Arc::strong_count(lock) == 2)
+        {
+            locks.remove(&self.key);
+        }
+    }
+}
+
+fn needs_refresh(session_data: &SessionData, now: i64) -> bool {
+    session_data.expiry - *OAUTH_REFRESH_MARGIN <= now
+}
+
+// The refresh token was revoked or has expired, so trying again won't help
+fn refresh_rejected(err: &OauthError) -> bool {
+    err.downcast_ref::<TokenEndpointError>().is_some_and(|err| {
+        err.status == 401 || err.error_code().as_deref() == Some("invalid_grant")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use actix_web::web;
+    use futures_util::future::join_all;
+    use serde_json::{json, Value};
+
+    use crate::server_functions::decrypt_string;
+
+    use super::super::{
+        mock_auth_server::{memory_store, MockAuthServer},
+        provider_registry::ProviderRegistry,
+        TokenResponse,
+    };
+    use super::*;
+
+    const NOW: i64 = 1_000_000;
+
+    // A provider whose token endpoint answers refreshes with `responses`, and a stored session
+    // from it whose access token runs out at `expiry`
+    async fn setup(
+        responses: Vec<(u16, Value)>,
+        delay: Duration,
+        expiry: i64,
+    ) -> (
+        MockAuthServer,
+        web::Data<ProviderRegistry>,
+        web::Data<dyn TokenStore>,
+        SessionData,
+    ) {
+        let server = MockAuthServer::responding(responses, delay).await;
+        let registry = server.registry(Some(true)).await;
+        let store = memory_store();
+
+        let mut session_data = SessionData::from(TokenResponse {
+            success: true,
+            access_token: "old".to_string(),
+            refresh_token: "old-refresh".to_string(),
+            username: "octocat".to_string(),
+            expiry,
+        });
+        session_data.provider = "mock".to_string();
+        store.save_session(&session_data).await.unwrap();
+
+        (server, registry, store, session_data)
+    }
+
+    fn access_token(session_data: &SessionData) -> String {
+        decrypt_string(&session_data.access_token).unwrap()
+    }
+
+    fn refreshed() -> Vec<(u16, Value)> {
+        vec![(200, MockAuthServer::tokens("new", NOW + 3600))]
+    }
+
+    #[actix_web::test]
+    async fn sessions_are_refreshed_within_the_margin() {
+        let refresher = SessionRefresher::default();
+
+        // Outside the default margin of 60 seconds nothing is sent to the provider
+        let (server, registry, store, session_data) =
+            setup(refreshed(), Duration::ZERO, NOW + 61).await;
+        let provider = registry.get("mock").unwrap();
+
+        let fresh = refresher
+            .fresh_session(session_data, provider, store.get_ref(), NOW)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(access_token(&fresh), "old");
+        assert!(server.token_requests().is_empty());
+
+        // Inside it the refresh token is traded in and the new tokens are stored
+        let (server, registry, store, session_data) =
+            setup(refreshed(), Duration::ZERO, NOW + 60).await;
+        let provider = registry.get("mock").unwrap();
+
+        let fresh = refresher
+            .fresh_session(session_data, provider, store.get_ref(), NOW)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(access_token(&fresh), "new");
+        assert_eq!(fresh.provider, "mock");
+
+        let stored = store.load_session(&fresh.key()).await.unwrap().unwrap();
+        assert_eq!(access_token(&stored), "new");
+
+        let requests = server.token_requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0]["grant_type"], "refresh_token");
+        assert_eq!(requests[0]["refresh_token"], "old-refresh");
+    }
+
+    #[actix_web::test]
+    async fn concurrent_refreshes_are_coalesced() {
+        let refresher = SessionRefresher::default();
+
+        // Slow enough that every request is waiting while the first one refreshes
+        let (server, registry, store, session_data) =
+            setup(refreshed(), Duration::from_millis(200), NOW).await;
+        let provider = registry.get("mock").unwrap();
+
+        let sessions = join_all((0..5).map(|_| {
+            refresher.fresh_session(session_data.clone(), provider, store.get_ref(), NOW)
+        }))
+        .await;
+
+        for session in sessions {
+            assert_eq!(access_token(&session.unwrap().unwrap()), "new");
+        }
+
+        assert_eq!(server.token_requests().len(), 1);
+        assert!(refresher.locks.lock().unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn rejected_refreshes_end_the_session() {
+        let refresher = SessionRefresher::default();
+
+        for rejection in [
+            (400, json!({ "error": "invalid_grant" })),
+            (401, json!({ "error": "invalid_client" })),
+        ] {
+            let (_server, registry, store, session_data) =
+                setup(vec![rejection], Duration::ZERO, NOW).await;
+            let provider = registry.get("mock").unwrap();
+            let key = session_data.key();
+
+            let fresh = refresher
+                .fresh_session(session_data, provider, store.get_ref(), NOW)
+                .await
+                .unwrap();
+            assert!(fresh.is_none());
+            assert!(store.load_session(&key).await.unwrap().is_none());
+        }
+    }
+
+    #[actix_web::test]
+    async fn provider_errors_keep_the_session_until_it_expires() {
+        let refresher = SessionRefresher::default();
+        let outage = vec![(503, json!({ "error": "temporarily_unavailable" }))];
+
+        // Still good for a few seconds, so the current tokens are used
+        let (_server, registry, store, session_data) =
+            setup(outage.clone(), Duration::ZERO, NOW + 10).await;
+        let provider = registry.get("mock").unwrap();
+        let key = session_data.key();
+
+        let fresh = refresher
+            .fresh_session(session_data, provider, store.get_ref(), NOW)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(access_token(&fresh), "old");
+        assert!(store.load_session(&key).await.unwrap().is_some());
+
+        // Expired, so there's nothing to fall back on, but the session isn't ended either
+        let (_server, registry, store, session_data) = setup(outage, Duration::ZERO, NOW).await;
+        let provider = registry.get("mock").unwrap();
+
+        assert!(refresher
+            .fresh_session(session_data, provider, store.get_ref(), NOW)
+            .await
+            .is_err());
+        assert!(store.load_session(&key).await.unwrap().is_some());
+    }
+
+    #[actix_web::test]
+    async fn cancelled_refreshes_release_their_lock() {
+        let refresher = SessionRefresher::default();
+
+        let (_server, registry, store, session_data) =
+            setup(refreshed(), Duration::from_secs(5), NOW).await;
+        let provider = registry.get("mock").unwrap();
+
+        // Like a client disconnecting while the provider is still answering
+        let cancelled = tokio::time::timeout(
+            Duration::from_millis(100),
+            refresher.fresh_session(session_data, provider, store.get_ref(), NOW),
+        )
+        .await;
+
+        assert!(cancelled.is_err());
+        assert!(refresher.locks.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,454 @@
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::server_functions::get_env_variable;
+use crate::services::download_link_service::check_link;
+use crate::services::download_link_service::claim_link;
+use crate::services::download_link_service::record_download;
+use crate::services::download_link_service::DownloadRecord;
+use crate::services::download_link_service::LinkCheck;
+use crate::services::download_link_service::LinkClaim;
+use crate::services::download_link_service::LinkError;
+use crate::services::download_link_service::VerifiedLink;
+use crate::services::resume_export_service::to_json_resume;
+use crate::services::resume_export_service::to_markdown;
+use crate::services::resume_export_service::to_plain_text;
+use crate::services::resume_export_service::to_vcard;
+use crate::services::resume_export_service::ExportFormat;
+use crate::services::resume_history_service::{live_pdf_matches, load_history};
+use crate::services::resume_render_service::rendered_resume_path;
+use crate::services::resume_render_service::RenderTemplate;
+use crate::services::resume_variant_service::check_variant_name;
+use crate::services::resume_variant_service::pdf_path as uploaded_pdf_path;
+use crate::services::resume_variant_service::DEFAULT_VARIANT;
+use crate::services::token_store_service::TokenStore;
+use crate::Resume;
+use crate::ResumeCache;
+use actix_files::NamedFile;
+use actix_web::http::header::{
+    Accept, ContentDisposition, DispositionParam, DispositionType, Header, ACCEPT, LOCATION,
+    USER_AGENT,
+};
+use actix_web::http::StatusCode;
+use actix_web::web;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use actix_web::ResponseError;
+use lazy_static::lazy_static;
+use serde::Deserialize;
+
+lazy_static! {
+    // Either "uploaded" (the last uploaded PDF) or "generated" (rendered from the live resume)
+    static ref RESUME_PDF_SOURCE: String =
+        get_env_variable("RESUME_PDF_SOURCE").unwrap_or_else(|| "uploaded".to_string());
+    static ref RESUME_PDF_TEMPLATE: String =
+        get_env_variable("RESUME_PDF_TEMPLATE").unwrap_or_else(|| "classic".to_string());
+}
+
+// Everything that can go wrong while serving a download. The message is what the caller sees, the
+// details only go to the log
+#[derive(Debug)]
+pub enum DownloadError {
+    UnknownLink,
+    ExpiredLink,
+    // Every download the link allows has been used
+    UsedUpLink,
+    // The link was minted for a different variant or format
+    WrongVariant(String),
+    WrongFormat(String),
+    UnknownFormat(String),
+    UnknownVariant(String),
+    BadRequest(String),
+    NotAcceptable,
+    RedisUnavailable(String),
+    MissingFile(PathBuf),
+    Internal(String),
+}
+
+impl DownloadError {
+    // Used in the expired link page's query string
+    fn link_reason(&self) -> Option<&'static str> {
+        match self {
+            DownloadError::UnknownLink => Some("unknown"),
+            DownloadError::ExpiredLink => Some("expired"),
+            DownloadError::UsedUpLink => Some("used"),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DownloadError::UnknownLink => write!(f, "Unknown download link"),
+            DownloadError::ExpiredLink => write!(f, "Download link has expired"),
+            DownloadError::UsedUpLink => {
+                write!(f, "Download link has already been used the maximum number of times")
+            }
+            DownloadError::WrongVariant(allowed) => {
+                write!(f, "Download link is only valid for the {allowed} resume")
+            }
+            DownloadError::WrongFormat(allowed) => {
+                write!(f, "Download link is only valid for resume.{allowed}")
+            }
+            DownloadError::UnknownFormat(format) => write!(f, "Unknown resume format: {format}"),
+            DownloadError::UnknownVariant(variant) => {
+                write!(f, "Unknown resume variant: {variant}")
+            }
+            DownloadError::BadRequest(message) => write!(f, "{message}"),
+            DownloadError::NotAcceptable => write!(
+                f,
+                "Supported formats: application/pdf, application/json, text/markdown, text/plain, text/vcard"
+            ),
+            DownloadError::RedisUnavailable(_) => {
+                write!(f, "Downloads are unavailable right now, try again shortly")
+            }
+            DownloadError::MissingFile(_) | DownloadError::Internal(_) => {
+                write!(f, "Error serving resume")
+            }
+        }
+    }
+}
+
+impl ResponseError for DownloadError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            DownloadError::UnknownLink
+            | DownloadError::UnknownFormat(_)
+            | DownloadError::UnknownVariant(_) => StatusCode::NOT_FOUND,
+            DownloadError::ExpiredLink | DownloadError::UsedUpLink => StatusCode::GONE,
+            DownloadError::WrongVariant(_) | DownloadError::WrongFormat(_) => StatusCode::FORBIDDEN,
+            DownloadError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            DownloadError::NotAcceptable => StatusCode::NOT_ACCEPTABLE,
+            DownloadError::RedisUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            DownloadError::MissingFile(_) | DownloadError::Internal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+impl From<redis::RedisError> for DownloadError {
+    fn from(err: redis::RedisError) -> Self {
+        DownloadError::RedisUnavailable(err.to_string())
+    }
+}
+
+impl From<LinkError> for DownloadError {
+    fn from(err: LinkError) -> Self {
+        match err.downcast::<redis::RedisError>() {
+            Ok(err) => DownloadError::RedisUnavailable(err.to_string()),
+            Err(err) => DownloadError::Internal(err.to_string()),
+        }
+    }
+}
+
+// Tokens are as good as a password until they expire, so only the start of one is ever logged
+fn token_prefix(token: &str) -> String {
+    let prefix: String = token.chars().take(6).collect();
+
+    format!("{prefix}...")
+}
+
+#[derive(Deserialize)]
+pub struct PdfQuery {
+    pub variant: Option<String>,
+    pub source: Option<String>,
+    pub template: Option<String>,
+}
+
+impl PdfQuery {
+    fn variant(&self) -> Result<&str, DownloadError> {
+        let variant = self.variant.as_deref().unwrap_or(DEFAULT_VARIANT.as_str());
+
+        check_variant_name(variant).map_err(DownloadError::BadRequest)?;
+
+        Ok(variant)
+    }
+}
+
+// Every export format goes through the same link check as the PDF. Links minted for a variant or
+// format only serve that one. Nothing is counted yet, see claim_download
+async fn verify_link(
+    store: &dyn TokenStore,
+    uuid: &str,
+    format: ExportFormat,
+    query: &PdfQuery,
+) -> Result<(PdfQuery, VerifiedLink), DownloadError> {
+    let verified =
+        match check_link(store, uuid, format.extension(), query.variant.as_deref()).await? {
+            LinkCheck::Allowed(verified) => verified,
+            LinkCheck::Unknown => return Err(DownloadError::UnknownLink),
+            LinkCheck::Expired => return Err(DownloadError::ExpiredLink),
+            LinkCheck::UsedUp => return Err(DownloadError::UsedUpLink),
+            LinkCheck::WrongVariant(allowed) => return Err(DownloadError::WrongVariant(allowed)),
+            LinkCheck::WrongFormat(allowed) => return Err(DownloadError::WrongFormat(allowed)),
+        };
+
+    let query = PdfQuery {
+        variant: verified.variant.clone(),
+        source: query.source.clone(),
+        template: query.template.clone(),
+    };
+
+    Ok((query, verified))
+}
+
+// Counts the download against the link's maximum and logs it. Only called once the response is
+// ready, so a download that fails doesn't use up the link
+async fn claim_download(
+    req: &HttpRequest,
+    store: &dyn TokenStore,
+    uuid: &str,
+    format: ExportFormat,
+    query: &PdfQuery,
+    verified: &VerifiedLink,
+) -> Result<(), DownloadError> {
+    match claim_link(store, verified).await? {
+        LinkClaim::Allowed => {}
+        LinkClaim::Unknown => return Err(DownloadError::UnknownLink),
+        LinkClaim::Expired => return Err(DownloadError::ExpiredLink),
+        LinkClaim::Exhausted => return Err(DownloadError::UsedUpLink),
+    }
+
+    // Signed links aren't stored, so there is nowhere to log their downloads
+    if let Some(link) = &verified.tracked {
+        let record = DownloadRecord {
+            downloaded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            label: link.label.clone(),
+            user_agent: req
+                .headers()
+                .get(USER_AGENT)
+                .and_then(|user_agent| user_agent.to_str().ok())
+                .map(str::to_string),
+            variant: query.variant()?.to_string(),
+            format: format.extension().to_string(),
+        };
+
+        // The download is already counted, a missing log entry shouldn't stop it
+        if let Err(err) = record_download(store, link, &record).await {
+            println!(
+                "Error recording resume download for link {}: {err}",
+                token_prefix(uuid)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn cached_resume(resume_cache: &ResumeCache, variant: &str) -> Result<Resume, DownloadError> {
+    resume_cache
+        .variants
+        .read()
+        .map_err(|_| {
+            DownloadError::Internal("Error establishing read lock on resume cache".to_string())
+        })?
+        .get(variant)
+        .cloned()
+        .ok_or_else(|| DownloadError::UnknownVariant(variant.to_string()))
+}
+
+async fn pdf_path(query: &PdfQuery, resume_cache: &ResumeCache) -> Result<PathBuf, DownloadError> {
+    let variant = query.variant()?;
+    let mut source = query
+        .source
+        .as_deref()
+        .unwrap_or(RESUME_PDF_SOURCE.as_str());
+
+    // The uploaded PDF would be out of date, so the live resume is rendered instead
+    if source == "uploaded"
+        && !live_pdf_matches(variant)
+            .await
+            .map_err(|err| DownloadError::Internal(err.to_string()))?
+    {
+        source = "generated";
+    }
+
+    match source {
+        "uploaded" => Ok(uploaded_pdf_path(variant)),
+        "generated" => {
+            let template_name = query
+                .template
+                .as_deref()
+                .unwrap_or(RESUME_PDF_TEMPLATE.as_str());
+            let template = RenderTemplate::from_name(template_name).ok_or_else(|| {
+                DownloadError::BadRequest(format!("Unknown template: {template_name}"))
+            })?;
+
+            let resume = cached_resume(resume_cache, variant)?;
+            let rev = load_history(variant)
+                .await
+                .map_err(|err| DownloadError::Internal(err.to_string()))?
+                .live
+                .unwrap_or(0);
+            let variant = variant.to_string();
+
+            // Rendering is CPU bound, keep it off the worker thread
+            web::block(move || rendered_resume_path(&variant, rev, &resume, template))
+                .await
+                .map_err(|err| DownloadError::Internal(err.to_string()))?
+                .map_err(|err| DownloadError::Internal(format!("Error rendering resume: {err}")))
+        }
+        _ => Err(DownloadError::BadRequest(format!(
+            "Unknown PDF source: {source}"
+        ))),
+    }
+}
+
+async fn serve_resume(
+    req: &HttpRequest,
+    uuid: &str,
+    format: ExportFormat,
+    query: &PdfQuery,
+    store: &dyn TokenStore,
+    resume_cache: &ResumeCache,
+) -> Result<HttpResponse, DownloadError> {
+    println!(
+        "Serving {} resume for link {}",
+        format.extension(),
+        token_prefix(uuid)
+    );
+
+    let (query, verified) = verify_link(store, uuid, format, query).await?;
+    let query = &query;
+
+    let disposition = ContentDisposition {
+        disposition: DispositionType::Attachment,
+        parameters: vec![DispositionParam::Filename(format!(
+            "ChrisBratti_Resume.{}",
+            format.extension()
+        ))],
+    };
+
+    // The file is opened, or the body built, before the link is claimed
+    let response = if format == ExportFormat::Pdf {
+        let path = pdf_path(query, resume_cache).await?;
+        let file = NamedFile::open(&path).map_err(|err| match err.kind() {
+            ErrorKind::NotFound => DownloadError::MissingFile(path.clone()),
+            _ => DownloadError::Internal(format!("Error opening {}: {err}", path.display())),
+        })?;
+
+        file.set_content_disposition(disposition).into_response(req)
+    } else {
+        let resume = cached_resume(resume_cache, query.variant()?)?;
+
+        let body = match format {
+            ExportFormat::JsonResume => serde_json::to_string_pretty(&to_json_resume(&resume))
+                .map_err(|err| {
+                    DownloadError::Internal(format!("Error serializing resume: {err}"))
+                })?,
+            ExportFormat::Markdown => to_markdown(&resume),
+            ExportFormat::Text => to_plain_text(&resume),
+            ExportFormat::VCard => to_vcard(&resume),
+            ExportFormat::Pdf => unreachable!(),
+        };
+
+        HttpResponse::Ok()
+            .content_type(format.content_type())
+            .insert_header(disposition)
+            .body(body)
+    };
+
+    claim_download(req, store, uuid, format, query, &verified).await?;
+
+    Ok(response)
+}
+
+// Logs the failure, and sends people who opened a dead link in a browser to a page that can get them
+// a fresh one instead of a bare error
+fn handle_download_error(
+    req: &HttpRequest,
+    uuid: &str,
+    query: &PdfQuery,
+    result: Result<HttpResponse, DownloadError>,
+) -> Result<HttpResponse, DownloadError> {
+    let err = match result {
+        Ok(response) => return Ok(response),
+        Err(err) => err,
+    };
+
+    println!(
+        "Resume download for link {} failed: {err:?}",
+        token_prefix(uuid)
+    );
+
+    let wants_html = req
+        .headers()
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+
+    let Some(reason) = err.link_reason().filter(|_| wants_html) else {
+        return Err(err);
+    };
+
+    let location = match query.variant() {
+        Ok(variant) if query.variant.is_some() => {
+            format!("/link-expired?reason={reason}&variant={variant}")
+        }
+        _ => format!("/link-expired?reason={reason}"),
+    };
+
+    Ok(HttpResponse::SeeOther()
+        .insert_header((LOCATION, location))
+        .finish())
+}
+
+#[actix_web::get("/{uuid}/resume.{extension}")]
+pub async fn download_resume(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    query: web::Query<PdfQuery>,
+    store: web::Data<dyn TokenStore>,
+    resume_cache: web::Data<ResumeCache>,
+) -> Result<HttpResponse, DownloadError> {
+    let (uuid, extension) = path.into_inner();
+
+    let result = match ExportFormat::from_extension(&extension) {
+        Some(format) => {
+            serve_resume(&req, &uuid, format, &query, store.get_ref(), &resume_cache).await
+        }
+        None => Err(DownloadError::UnknownFormat(extension)),
+    };
+
+    handle_download_error(&req, &uuid, &query, result)
+}
+
+// Picks the format from the Accept header, falling back to the PDF for */* or no header
+#[actix_web::get("/{uuid}/resume")]
+pub async fn download_negotiated_resume(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<PdfQuery>,
+    store: web::Data<dyn TokenStore>,
+    resume_cache: web::Data<ResumeCache>,
+) -> Result<HttpResponse, DownloadError> {
+    let uuid = path.into_inner();
+
+    let format = match Accept::parse(&req) {
+        Ok(accept) if !accept.is_empty() => accept
+            .ranked()
+            .iter()
+            .find_map(|mime| match mime.essence_str() {
+                "*/*" | "application/*" => Some(ExportFormat::Pdf),
+                essence => ExportFormat::from_mime(essence),
+            })
+            .ok_or(DownloadError::NotAcceptable),
+        _ => Ok(ExportFormat::Pdf),
+    };
+
+    let result = match format {
+        Ok(format) => {
+            serve_resume(&req, &uuid, format, &query, store.get_ref(), &resume_cache).await
+        }
+        Err(err) => Err(err),
+    };
+
+    handle_download_error(&req, &uuid, &query, result)
+}
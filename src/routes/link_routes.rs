@@ -0,0 +1,65 @@
+use crate::services::download_link_service::link_details;
+use crate::services::download_link_service::list_links;
+use crate::services::download_link_service::mint_link;
+use crate::services::download_link_service::InvalidLinkOptions;
+use crate::services::download_link_service::LinkMode;
+use crate::services::download_link_service::LinkOptions;
+use crate::services::token_store_service::TokenStore;
+use actix_web::web;
+use actix_web::HttpResponse;
+use serde_json::json;
+
+// Mints a download link for a recruiter, e.g. `{"ttl_secs": 604800, "max_downloads": 1,
+// "label": "Acme recruiter", "format": "pdf"}`. Every field is optional
+#[cfg(feature = "ssr")]
+#[actix_web::post("/links")]
+pub async fn create_download_link(
+    options: web::Json<LinkOptions>,
+    store: web::Data<dyn TokenStore>,
+    link_mode: web::Data<LinkMode>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let link = mint_link(store.get_ref(), **link_mode, options.into_inner())
+        .await
+        .map_err(|err| match err.downcast_ref::<InvalidLinkOptions>() {
+            Some(invalid) => actix_web::error::ErrorBadRequest(invalid.to_string()),
+            // Store and signing errors are logged, their details aren't for the caller
+            None => {
+                println!("Error minting download link: {err}");
+                actix_web::error::ErrorInternalServerError("Error creating download link")
+            }
+        })?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "url": link.url(),
+        "link": link,
+    })))
+}
+
+// Every link that hasn't been cleaned up yet, with its downloads
+#[cfg(feature = "ssr")]
+#[actix_web::get("/links")]
+pub async fn get_download_links(
+    store: web::Data<dyn TokenStore>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let links = list_links(store.get_ref())
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Error loading links"))?;
+
+    Ok(HttpResponse::Ok().json(links))
+}
+
+#[cfg(feature = "ssr")]
+#[actix_web::get("/links/{token}")]
+pub async fn get_download_link(
+    path: web::Path<String>,
+    store: web::Data<dyn TokenStore>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let token = path.into_inner();
+
+    let details = link_details(store.get_ref(), &token)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Error loading link"))?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Unknown link"))?;
+
+    Ok(HttpResponse::Ok().json(details))
+}
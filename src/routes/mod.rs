@@ -1 +1,4 @@
+pub mod download_routes;
+pub mod link_routes;
 pub mod resume_routes;
+pub mod sweep_routes;
@@ -1,84 +1,652 @@
-use crate::services::resume_parsing_service::parse_resume;
-use crate::services::resume_parsing_service::save_resume_json;
-use crate::services::resume_parsing_service::update_current_resume;
-use crate::services::resume_parsing_service::update_resume_json;
+use crate::services::redis_repository::RedisRepository;
+use crate::services::resume_approval_service::pending_status;
+use crate::services::resume_approval_service::promote_pending_resume;
+use crate::services::resume_approval_service::reject_pending_resume;
+use crate::services::resume_diff_service::diff_resumes;
+use crate::services::resume_history_service::get_revision;
+use crate::services::resume_history_service::hash_bytes;
+use crate::services::resume_history_service::load_history;
+use crate::services::resume_history_service::rollback;
+use crate::services::resume_job_service::enqueue_upload;
+use crate::services::resume_job_service::load_job;
+use crate::services::resume_override_service::add_override;
+use crate::services::resume_override_service::apply_overrides;
+use crate::services::resume_override_service::load_overrides;
+use crate::services::resume_override_service::remove_override;
+use crate::services::resume_override_service::NewOverride;
+use crate::services::resume_parsing_service::load_parsed_resume;
+use crate::services::resume_parsing_service::load_pending_resume;
+use crate::services::resume_parsing_service::load_resume;
+use crate::services::resume_source_service::export_resume;
+use crate::services::resume_source_service::load_source_file;
+use crate::services::resume_source_service::parse_source;
+use crate::services::resume_source_service::SourceFormat;
+use crate::services::resume_staging_service::stage_pending_resume;
+use crate::services::resume_upload_service::check_pdf;
+use crate::services::resume_upload_service::find_duplicate_revision;
+use crate::services::resume_upload_service::MAX_TEXT_FIELD_BYTES;
+use crate::services::resume_upload_service::RESUME_MAX_UPLOAD_BYTES;
+use crate::services::resume_variant_service::check_variant_name;
+use crate::services::resume_variant_service::DEFAULT_VARIANT;
 use crate::ResumeCache;
-use actix_multipart::Multipart;
+use actix_multipart::{Field, Multipart};
+use actix_web::dev::Payload;
 use actix_web::web;
+use actix_web::FromRequest;
+use actix_web::HttpRequest;
 use actix_web::HttpResponse;
+use futures_util::future::{ready, Ready};
 use futures_util::StreamExt;
+use serde::Deserialize;
+use serde_json::json;
 
-// Upload resume
+// The resume variant a request works on. Every internal route can be called as
+// /resume/{variant}/..., routes without the variant segment use the default variant
+pub struct ResumeVariant(pub String);
+
+impl FromRequest for ResumeVariant {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let variant = req
+            .match_info()
+            .get("variant")
+            .unwrap_or(DEFAULT_VARIANT.as_str());
+
+        ready(
+            check_variant_name(variant)
+                .map(|_| ResumeVariant(variant.to_string()))
+                .map_err(actix_web::error::ErrorBadRequest),
+        )
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RevisionPath {
+    pub rev: u64,
+}
+
+#[derive(Deserialize)]
+pub struct OverridePath {
+    pub id: String,
+}
+
+#[derive(Deserialize)]
+pub struct ApproveQuery {
+    pub force: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    pub format: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SourceQuery {
+    pub format: Option<String>,
+    pub pending: Option<bool>,
+}
+
+// Reads a short text field like `variant` or `note`
+async fn read_text_field(field: &mut Field) -> Result<String, actix_web::Error> {
+    let mut bytes = Vec::new();
+
+    while let Some(chunk) = field.next().await {
+        let chunk =
+            chunk.map_err(|_| actix_web::error::ErrorBadRequest("Error reading form field"))?;
+
+        if bytes.len() + chunk.len() > MAX_TEXT_FIELD_BYTES {
+            return Err(actix_web::error::ErrorPayloadTooLarge(format!(
+                "Form fields must be at most {MAX_TEXT_FIELD_BYTES} bytes"
+            )));
+        }
+
+        bytes.extend_from_slice(&chunk);
+    }
+
+    String::from_utf8(bytes)
+        .map(|text| text.trim().to_string())
+        .map_err(|_| actix_web::error::ErrorBadRequest("Form fields must be UTF-8"))
+}
+
+// Stops reading as soon as the file goes over the size limit, rather than buffering it all first
+async fn read_file_field(field: &mut Field) -> Result<Vec<u8>, actix_web::Error> {
+    let max_bytes = *RESUME_MAX_UPLOAD_BYTES;
+    let mut file_bytes = Vec::new();
+
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk
+            .map_err(|_| actix_web::error::ErrorInternalServerError("Error reading file chunk"))?;
+
+        if file_bytes.len() + chunk.len() > max_bytes {
+            return Err(actix_web::error::ErrorPayloadTooLarge(format!(
+                "Resume must be at most {max_bytes} bytes"
+            )));
+        }
+
+        file_bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(file_bytes)
+}
+
+// Upload resume. Expects a `resume` file field, plus optional `variant` and `note` text fields.
+// Parsing can take a while, so the upload is queued as a job and its id returned straight away.
+// Poll /resume/jobs/{id} for the result
 #[cfg(feature = "ssr")]
-#[actix_web::post("/resume/update")]
-pub async fn upload_resume(mut payload: Multipart) -> Result<HttpResponse, actix_web::Error> {
+#[actix_web::routes]
+#[post("/resume/update")]
+#[post("/resume/{variant}/update")]
+pub async fn upload_resume(
+    request: HttpRequest,
+    ResumeVariant(variant): ResumeVariant,
+    redis: Option<web::Data<RedisRepository>>,
+    mut payload: Multipart,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut file_bytes: Option<Vec<u8>> = None;
+    let mut form_variant: Option<String> = None;
+    let mut note: Option<String> = None;
+
     while let Some(field) = payload.next().await {
         let mut field = field?;
+        let name = field.name().map(str::to_string);
 
-        // Content type has to be PDF
-        let content_type = field
-            .content_type()
-            .ok_or_else(|| {
-                actix_web::error::ErrorBadRequest("Content header could not be determined")
-            })?
-            .to_string();
-        if content_type != "application/pdf" {
-            return Err(actix_web::error::ErrorBadRequest("File must be PDF"));
+        match name.as_deref() {
+            Some("resume") if file_bytes.is_none() => {
+                file_bytes = Some(read_file_field(&mut field).await?)
+            }
+            Some("resume") => {
+                return Err(actix_web::error::ErrorBadRequest(
+                    "Only one resume can be uploaded at a time",
+                ))
+            }
+            Some("variant") => form_variant = Some(read_text_field(&mut field).await?),
+            Some("note") => {
+                note = Some(read_text_field(&mut field).await?).filter(|note| !note.is_empty())
+            }
+            Some(name) => {
+                return Err(actix_web::error::ErrorBadRequest(format!(
+                    "Unexpected form field: {name}"
+                )))
+            }
+            None => {
+                return Err(actix_web::error::ErrorBadRequest(
+                    "Form fields must be named",
+                ))
+            }
         }
+    }
 
-        let mut file_bytes = Vec::new();
-
-        while let Some(chunk) = field.next().await {
-            file_bytes.extend_from_slice(&chunk.map_err(|_| {
-                actix_web::error::ErrorInternalServerError("Error reading file chunk")
-            })?);
+    // The variant can come from the URL or the form, but they have to agree
+    let variant = match form_variant {
+        Some(form_variant)
+            if request
+                .match_info()
+                .get("variant")
+                .is_some_and(|path_variant| path_variant != form_variant) =>
+        {
+            return Err(actix_web::error::ErrorBadRequest(
+                "variant field doesn't match the variant in the URL",
+            ))
+        }
+        Some(form_variant) => {
+            check_variant_name(&form_variant).map_err(actix_web::error::ErrorBadRequest)?;
+            form_variant
         }
+        None => variant,
+    };
 
-        // Save PDF in background
-        let update_handle = tokio::spawn({
-            let file_bytes = file_bytes.clone();
-            async move { update_current_resume(file_bytes).await }
-        });
+    let file_bytes =
+        file_bytes.ok_or_else(|| actix_web::error::ErrorBadRequest("Missing resume file field"))?;
 
-        // Call resume parsing service
-        let response = parse_resume(file_bytes.clone())
-            .await
-            .map_err(|_| actix_web::error::ErrorInternalServerError("Error parsing resume!"))?;
+    // The declared content type can't be trusted, so check the file itself
+    check_pdf(&file_bytes).map_err(actix_web::error::ErrorBadRequest)?;
 
-        // Save the response as JSON
-        save_resume_json(&response)
-            .await
-            .map_err(|_| actix_web::error::ErrorInternalServerError("Error saving resume JSON"))?;
+    let sha256 = hash_bytes(&file_bytes);
 
-        // Wait for PDF task to finish
-        update_handle
-            .await
-            .map_err(|_| actix_web::error::ErrorInternalServerError("PDF save task panicked"))?
-            .map_err(|_| actix_web::error::ErrorInternalServerError("Error saving PDF file"))?;
+    if let Some(duplicate) = find_duplicate_revision(&variant, &sha256)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Error reading resume history"))?
+    {
+        println!(
+            "Ignoring upload of the {} {variant} resume, revision {}",
+            duplicate.status, duplicate.revision
+        );
 
-        return Ok(HttpResponse::Ok().json(response));
+        return Ok(HttpResponse::Ok().json(json!({
+            "duplicate": true,
+            "variant": variant,
+            "revision": duplicate.revision,
+            "status": duplicate.status,
+        })));
     }
 
-    // If here then payload was likely bad
-    Err(actix_web::error::ErrorBadRequest("Error uploading resume"))
+    let mut con = redis
+        .ok_or_else(|| {
+            actix_web::error::ErrorServiceUnavailable("Resume uploads need REDIS_CONNECTION_STRING")
+        })?
+        .connection();
+
+    // Uploading the same file again while it's still queued returns the queued job
+    let (job, queued) = enqueue_upload(&mut con, &variant, &file_bytes, note)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Error queueing resume upload"))?;
+
+    Ok(HttpResponse::Accepted().json(json!({
+        "duplicate": !queued,
+        "job": job,
+        "status_url": format!("/internal/resume/jobs/{}", job.id),
+    })))
 }
 
 #[cfg(feature = "ssr")]
-#[actix_web::post("/resume/approve")]
+#[actix_web::routes]
+#[post("/resume/approve")]
+#[post("/resume/{variant}/approve")]
 pub async fn approve_pending_resume(
+    ResumeVariant(variant): ResumeVariant,
+    query: web::Query<ApproveQuery>,
     resume_cache: web::Data<ResumeCache>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    use crate::services::resume_parsing_service::load_resume;
+    let status = pending_status(&variant)
+        .await
+        .map_err(|err| actix_web::error::ErrorInternalServerError(err.to_string()))?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("No pending resume to approve"))?;
 
-    update_resume_json()?;
+    // Invalid resumes can still be pushed live with `?force=true`
+    if !status.validation.is_valid() && !query.force.unwrap_or(false) {
+        return Ok(HttpResponse::UnprocessableEntity().json(status.validation));
+    }
 
-    let updated_resume = load_resume().await?;
+    // Only the revision that was just validated is approved, not one staged in the meantime
+    let promoted = promote_pending_resume(&variant, status.revision, &resume_cache, false)
+        .await
+        .map_err(|err| actix_web::error::ErrorInternalServerError(err.to_string()))?;
 
-    let mut write_to_cache = resume_cache.resume.write().map_err(|_| {
+    if !promoted {
+        return Err(actix_web::error::ErrorConflict(
+            "The pending resume changed, check it again before approving",
+        ));
+    }
+
+    let unmatched = unmatched_overrides(&variant).await?;
+
+    if !unmatched.is_empty() {
+        return Ok(HttpResponse::Ok().body(format!(
+            "Pending {variant} resume JSON is now live, but {} override(s) no longer match: {}",
+            unmatched.len(),
+            unmatched.join(", ")
+        )));
+    }
+
+    Ok(HttpResponse::Ok().body(format!("Pending {variant} resume JSON is now live")))
+}
+
+// Drops the pending resume, which also stops it from being approved automatically
+#[cfg(feature = "ssr")]
+#[actix_web::routes]
+#[post("/resume/reject")]
+#[post("/resume/{variant}/reject")]
+pub async fn reject_resume(
+    ResumeVariant(variant): ResumeVariant,
+) -> Result<HttpResponse, actix_web::Error> {
+    let rejected = reject_pending_resume(&variant)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Error rejecting pending resume"))?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("No pending resume to reject"))?;
+
+    Ok(HttpResponse::Ok().body(format!(
+        "Pending {variant} resume revision {rejected} was rejected"
+    )))
+}
+
+// The pending revision, its validation report and when it will be approved automatically
+#[cfg(feature = "ssr")]
+#[actix_web::routes]
+#[get("/resume/pending")]
+#[get("/resume/{variant}/pending")]
+pub async fn get_pending_resume(
+    ResumeVariant(variant): ResumeVariant,
+) -> Result<HttpResponse, actix_web::Error> {
+    let status = pending_status(&variant)
+        .await
+        .map_err(|err| actix_web::error::ErrorInternalServerError(err.to_string()))?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("No pending resume"))?;
+
+    Ok(HttpResponse::Ok().json(status))
+}
+
+// Reloads a variant's live resume (with overrides applied) into the cache
+async fn refresh_resume_cache(
+    variant: &str,
+    resume_cache: &ResumeCache,
+) -> Result<(), actix_web::Error> {
+    let updated_resume = load_resume(variant).await?;
+
+    let mut write_to_cache = resume_cache.variants.write().map_err(|_| {
         actix_web::error::ErrorInternalServerError("Error establishing write lock on resume cache")
     })?;
 
-    *write_to_cache = updated_resume;
+    write_to_cache.insert(variant.to_string(), updated_resume);
+
+    Ok(())
+}
+
+// Overrides can be edited before a variant is first approved, in which case there is nothing live
+// to refresh yet
+async fn refresh_live_variant(
+    variant: &str,
+    resume_cache: &ResumeCache,
+) -> Result<(), actix_web::Error> {
+    if load_parsed_resume(variant).await.is_err() {
+        return Ok(());
+    }
+
+    refresh_resume_cache(variant, resume_cache).await
+}
+
+// Ids of overrides that don't apply to the live parsed resume
+async fn unmatched_overrides(variant: &str) -> Result<Vec<String>, actix_web::Error> {
+    // A variant that has never been approved has nothing for its overrides to match yet
+    let mut parsed = match load_parsed_resume(variant).await {
+        Ok(parsed) => parsed,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    let overrides = load_overrides(variant)
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Error loading overrides"))?;
+
+    Ok(apply_overrides(&mut parsed, &overrides)
+        .unmatched
+        .into_iter()
+        .map(|resume_override| resume_override.id)
+        .collect())
+}
+
+#[cfg(feature = "ssr")]
+#[actix_web::get("/resume/jobs/{id}")]
+pub async fn get_resume_job(
+    path: web::Path<String>,
+    redis: Option<web::Data<RedisRepository>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let id = path.into_inner();
+
+    let mut con = redis
+        .ok_or_else(|| {
+            actix_web::error::ErrorServiceUnavailable("Resume uploads need REDIS_CONNECTION_STRING")
+        })?
+        .connection();
+
+    let job = load_job(&mut con, &id)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Error loading resume job"))?
+        .ok_or_else(|| actix_web::error::ErrorNotFound(format!("No resume job {id}")))?;
+
+    Ok(HttpResponse::Ok().json(job))
+}
+
+#[cfg(feature = "ssr")]
+#[actix_web::routes]
+#[get("/resume/history")]
+#[get("/resume/{variant}/history")]
+pub async fn get_resume_history(
+    ResumeVariant(variant): ResumeVariant,
+) -> Result<HttpResponse, actix_web::Error> {
+    let history = load_history(&variant)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Error reading resume history"))?;
+
+    Ok(HttpResponse::Ok().json(history))
+}
+
+#[cfg(feature = "ssr")]
+#[actix_web::routes]
+#[get("/resume/{rev:\\d+}")]
+#[get("/resume/{variant}/{rev:\\d+}")]
+pub async fn get_resume_revision(
+    ResumeVariant(variant): ResumeVariant,
+    path: web::Path<RevisionPath>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let rev = path.rev;
+
+    let revision = get_revision(&variant, rev)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Error reading resume revision"))?
+        .ok_or_else(|| actix_web::error::ErrorNotFound(format!("No revision {rev}")))?;
+
+    Ok(HttpResponse::Ok().json(revision))
+}
+
+#[cfg(feature = "ssr")]
+#[actix_web::routes]
+#[post("/resume/rollback/{rev}")]
+#[post("/resume/{variant}/rollback/{rev}")]
+pub async fn rollback_resume(
+    ResumeVariant(variant): ResumeVariant,
+    path: web::Path<RevisionPath>,
+    resume_cache: web::Data<ResumeCache>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let rev = path.rev;
+
+    let revision = rollback(&variant, rev, &resume_cache)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Error rolling back resume"))?
+        .ok_or_else(|| actix_web::error::ErrorNotFound(format!("No revision {rev}")))?;
+
+    Ok(HttpResponse::Ok().json(revision))
+}
+
+// Shows what changed between the live resume and the pending one. Defaults to JSON, pass
+// `?format=text` for a plain text report
+#[cfg(feature = "ssr")]
+#[actix_web::routes]
+#[get("/resume/diff")]
+#[get("/resume/{variant}/diff")]
+pub async fn diff_pending_resume(
+    ResumeVariant(variant): ResumeVariant,
+    query: web::Query<DiffQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let live = load_parsed_resume(&variant)
+        .await
+        .map_err(|_| actix_web::error::ErrorNotFound("No live resume to compare against"))?;
+
+    let pending = load_pending_resume(&variant)
+        .await
+        .map_err(|_| actix_web::error::ErrorNotFound("No pending resume to compare"))?;
+
+    let diff = diff_resumes(&live, &pending);
+
+    match query.format.as_deref() {
+        Some("text") => Ok(HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .body(diff.to_string())),
+        None | Some("json") => Ok(HttpResponse::Ok().json(diff)),
+        Some(format) => Err(actix_web::error::ErrorBadRequest(format!(
+            "Unsupported diff format: {format}"
+        ))),
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[actix_web::routes]
+#[get("/resume/overrides")]
+#[get("/resume/{variant}/overrides")]
+pub async fn list_overrides(
+    ResumeVariant(variant): ResumeVariant,
+) -> Result<HttpResponse, actix_web::Error> {
+    let overrides = load_overrides(&variant)
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Error loading overrides"))?;
+
+    let unmatched = unmatched_overrides(&variant).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "overrides": overrides,
+        "unmatched": unmatched,
+    })))
+}
+
+#[cfg(feature = "ssr")]
+#[actix_web::routes]
+#[post("/resume/overrides")]
+#[post("/resume/{variant}/overrides")]
+pub async fn create_override(
+    ResumeVariant(variant): ResumeVariant,
+    new_override: web::Json<NewOverride>,
+    resume_cache: web::Data<ResumeCache>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let new_override = new_override.into_inner();
+
+    new_override
+        .check()
+        .map_err(actix_web::error::ErrorBadRequest)?;
+
+    let resume_override = add_override(&variant, new_override)
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Error saving override"))?;
+
+    refresh_live_variant(&variant, &resume_cache).await?;
+
+    let matched = !unmatched_overrides(&variant)
+        .await?
+        .contains(&resume_override.id);
+
+    Ok(HttpResponse::Created().json(json!({
+        "override": resume_override,
+        "matched": matched,
+    })))
+}
+
+#[cfg(feature = "ssr")]
+#[actix_web::routes]
+#[delete("/resume/overrides/{id}")]
+#[delete("/resume/{variant}/overrides/{id}")]
+pub async fn delete_override(
+    ResumeVariant(variant): ResumeVariant,
+    path: web::Path<OverridePath>,
+    resume_cache: web::Data<ResumeCache>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let id = &path.id;
+
+    let removed = remove_override(&variant, id)
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Error removing override"))?;
+
+    if !removed {
+        return Err(actix_web::error::ErrorNotFound(format!("No override {id}")));
+    }
+
+    refresh_live_variant(&variant, &resume_cache).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Picks the source format from `?format=`, falling back to the request's Content-Type
+fn source_format(
+    query: &SourceQuery,
+    request: Option<&HttpRequest>,
+) -> Result<SourceFormat, actix_web::Error> {
+    if let Some(format) = query.format.as_deref() {
+        return SourceFormat::from_name(format).ok_or_else(|| {
+            let supported: Vec<&str> = SourceFormat::ALL.iter().map(SourceFormat::name).collect();
+
+            actix_web::error::ErrorBadRequest(format!(
+                "Unsupported resume format: {format}, use one of {}",
+                supported.join(", ")
+            ))
+        });
+    }
+
+    request
+        .and_then(|request| request.headers().get("content-type"))
+        .and_then(|content_type| content_type.to_str().ok())
+        .and_then(SourceFormat::from_content_type)
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("Resume format could not be determined"))
+}
+
+// Stages a hand-written TOML or YAML resume as pending, skipping ParseCV entirely
+#[cfg(feature = "ssr")]
+#[actix_web::routes]
+#[post("/resume/import")]
+#[post("/resume/{variant}/import")]
+pub async fn import_resume(
+    ResumeVariant(variant): ResumeVariant,
+    request: HttpRequest,
+    query: web::Query<SourceQuery>,
+    body: String,
+) -> Result<HttpResponse, actix_web::Error> {
+    let format = source_format(&query, Some(&request))?;
+
+    let resume = parse_source(&body, format).map_err(|err| {
+        actix_web::error::ErrorBadRequest(format!("Invalid {} resume: {err}", format.name()))
+    })?;
+
+    let response = stage_pending_resume(&variant, resume, None, format.name(), None)
+        .await
+        .map_err(|err| actix_web::error::ErrorInternalServerError(err.to_string()))?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+// Same as `import_resume`, but reads the variant's source file (RESUME_SOURCE_FILE for the
+// default variant)
+#[cfg(feature = "ssr")]
+#[actix_web::routes]
+#[post("/resume/import/file")]
+#[post("/resume/{variant}/import/file")]
+pub async fn import_resume_file(
+    ResumeVariant(variant): ResumeVariant,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (resume, format) = load_source_file(&variant).map_err(|err| {
+        actix_web::error::ErrorBadRequest(format!("Error reading resume source file: {err}"))
+    })?;
+
+    let response = stage_pending_resume(&variant, resume, None, format.name(), None)
+        .await
+        .map_err(|err| actix_web::error::ErrorInternalServerError(err.to_string()))?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+// Exports the live resume (or the pending one with `?pending=true`) so it can be edited and
+// re-imported
+#[cfg(feature = "ssr")]
+#[actix_web::routes]
+#[get("/resume/export")]
+#[get("/resume/{variant}/export")]
+pub async fn export_resume_source(
+    ResumeVariant(variant): ResumeVariant,
+    query: web::Query<SourceQuery>,
+    resume_cache: web::Data<ResumeCache>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // TOML unless another format is asked for
+    let format = match query.format {
+        Some(_) => source_format(&query, None)?,
+        None => SourceFormat::Toml,
+    };
+
+    let resume = if query.pending.unwrap_or(false) {
+        load_pending_resume(&variant)
+            .await
+            .map_err(|_| actix_web::error::ErrorNotFound("No pending resume to export"))?
+    } else {
+        resume_cache
+            .variants
+            .read()
+            .map_err(|_| {
+                actix_web::error::ErrorInternalServerError(
+                    "Error establishing read lock on resume cache",
+                )
+            })?
+            .get(&variant)
+            .cloned()
+            .ok_or_else(|| {
+                actix_web::error::ErrorNotFound(format!("No live {variant} resume to export"))
+            })?
+    };
+
+    let source = export_resume(&resume, format)
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Error exporting resume"))?;
 
-    Ok(HttpResponse::Ok().body("Pending resume JSON is now live"))
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .body(source))
 }
@@ -0,0 +1,28 @@
+use crate::services::redis_sweep_service::run_sweep;
+use crate::services::redis_sweep_service::sweep_metrics;
+use crate::services::token_store_service::TokenStore;
+use actix_web::web;
+use actix_web::HttpResponse;
+
+// Sweeps expired entries right away instead of waiting for the background sweeper
+#[cfg(feature = "ssr")]
+#[actix_web::post("/redis/sweep")]
+pub async fn trigger_sweep(
+    store: web::Data<dyn TokenStore>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let report = run_sweep(store.get_ref())
+        .await
+        .map_err(|_| actix_web::error::ErrorServiceUnavailable("Error sweeping expired entries"))?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// How many entries have been pruned since start-up, and by the last sweep
+#[cfg(feature = "ssr")]
+#[actix_web::get("/redis/sweep")]
+pub async fn get_sweep_metrics() -> Result<HttpResponse, actix_web::Error> {
+    let metrics = sweep_metrics()
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Error loading sweep metrics"))?;
+
+    Ok(HttpResponse::Ok().json(metrics))
+}
@@ -1,9 +1,7 @@
-use std::sync::Arc;
-
 use cfg_if::cfg_if;
 use leptos::{prelude::ServerFnError, server};
 
-use crate::{PersonalInfo, ResumeCache, UserInfo};
+use crate::{PersonalInfo, Resume, UserInfo};
 
 // Backend dependencies and functions
 cfg_if! {
@@ -19,16 +17,17 @@ cfg_if! {
         use maud::html;
         use actix_web::web;
         use leptos_actix::extract;
-        use redis::Client;
-        use redis::Commands;
         use aes_gcm::{
             Aes256Gcm, Key, Nonce,
             aead::{Aead, AeadCore, KeyInit, OsRng},
         };
         use actix_identity::Identity;
-        use std::time::{SystemTime, UNIX_EPOCH};
-        use crate::oauth::{oauth_client::call_user_endpoint, SessionData};
+        use crate::oauth::oauth_client::{call_user_endpoint, end_session};
         use crate::SmtpInfo;
+        use crate::ResumeCache;
+        use crate::services::resume_variant_service::{check_variant_name, DEFAULT_VARIANT};
+        use crate::services::download_link_service::{mint_link, LinkMode, LinkOptions};
+        use crate::services::token_store_service::TokenStore;
 
         use lazy_static::lazy_static;
         lazy_static!{
@@ -234,52 +233,75 @@ pub async fn get_info() -> Result<PersonalInfo, ServerFnError> {
 }
 
 #[server]
-pub async fn generate_pdf_link() -> Result<String, ServerFnError> {
-    let redis_client: web::Data<Client> = extract().await?;
-
-    let mut con = redis_client.get_connection().unwrap();
-    let uuid = generate_token();
+pub async fn generate_pdf_link(variant: Option<String>) -> Result<String, ServerFnError> {
+    let store: web::Data<dyn TokenStore> = extract().await?;
+    let link_mode: web::Data<LinkMode> = extract().await?;
+
+    let link = mint_link(
+        store.get_ref(),
+        **link_mode,
+        LinkOptions {
+            variant: variant.filter(|variant| check_variant_name(variant).is_ok()),
+            ..LinkOptions::default()
+        },
+    )
+    .await
+    .map_err(|err| ServerFnError::new(format!("Error creating download link!: {err}")))?;
+
+    Ok(link.url())
+}
 
-    let ttl_seconds = 300;
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-    let expiry_time = now + ttl_seconds;
+// Used by the expired link page, sends the visitor straight to a fresh link
+#[server]
+pub async fn refresh_pdf_link(variant: Option<String>) -> Result<(), ServerFnError> {
+    let url = generate_pdf_link(variant).await?;
 
-    () = con.zadd("pdf_links", &uuid, expiry_time).map_err(|err| {
-        ServerFnError::new(format!("Error adding to redis cache!: {}", err.to_string()))
-    })?;
+    leptos_actix::redirect(&url);
 
-    let url = format!("/{}/resume.pdf", uuid);
-    Ok(url)
+    Ok(())
 }
 
 #[server]
 pub async fn get_user_info() -> Result<Option<UserInfo>, ServerFnError> {
     println!("Fetching user session");
-    let redis_client: web::Data<Client> = extract().await?;
-
-    let mut con = redis_client.get_connection().unwrap();
+    let store: web::Data<dyn TokenStore> = extract().await?;
 
     let user: Option<Identity> = extract().await?;
 
     if let Some(user) = user {
-        let session_str: String = con.get(user.id().unwrap())?;
-        let session_data: SessionData = serde_json::from_str(&session_str).unwrap();
-
-        let user_info_response = call_user_endpoint(session_data).await?;
+        let session_data = store
+            .load_session(&user.id()?)
+            .await
+            .map_err(|err| ServerFnError::new(format!("Error loading session!: {err}")))?;
+
+        let Some(session_data) = session_data else {
+            println!("No session found for logged in user");
+            end_session().await?;
+            return Ok(None);
+        };
 
-        Ok(Some(UserInfo::from(user_info_response.user_data)))
+        call_user_endpoint(session_data).await
     } else {
         println!("No user found");
         Ok(None)
     }
 }
 
+// Unknown variants fall back to the default resume rather than breaking the page
 #[server]
-pub async fn get_resume_info() -> Result<Arc<ResumeCache>, ServerFnError> {
-    let resume: web::Data<ResumeCache> = extract().await?;
+pub async fn get_resume_info(variant: Option<String>) -> Result<Resume, ServerFnError> {
+    let resume_cache: web::Data<ResumeCache> = extract().await?;
+
+    let variants = resume_cache
+        .variants
+        .read()
+        .map_err(|_| ServerFnError::new("Error establishing read lock on resume cache"))?;
+
+    let resume = variant
+        .as_deref()
+        .and_then(|variant| variants.get(variant))
+        .or_else(|| variants.get(DEFAULT_VARIANT.as_str()))
+        .ok_or_else(|| ServerFnError::new("No resume available"))?;
 
-    Ok(resume.into_inner())
+    Ok(resume.clone())
 }
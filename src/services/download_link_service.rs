@@ -0,0 +1,726 @@
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    server_functions::{generate_token, get_env_variable},
+    services::{
+        resume_export_service::ExportFormat,
+        resume_variant_service::check_variant_name,
+        signed_link_service::{
+            can_sign, is_signed_token, sign_link, verify_signed_token, SignedLink,
+        },
+        token_store_service::TokenStore,
+    },
+};
+
+lazy_static! {
+    // Longest TTL the internal API will mint a link with
+    static ref RESUME_LINK_MAX_TTL: u64 = get_env_variable("RESUME_LINK_MAX_TTL_SECS")
+        .and_then(|ttl| ttl.parse().ok())
+        .unwrap_or(60 * 60 * 24 * 30);
+    // How long a link's details and download log are kept after the link expires
+    static ref RESUME_LINK_LOG_RETENTION: u64 =
+        get_env_variable("RESUME_LINK_LOG_RETENTION_SECS")
+            .and_then(|retention| retention.parse().ok())
+            .unwrap_or(60 * 60 * 24 * 30);
+}
+
+// Links minted by the site itself
+pub const DEFAULT_LINK_TTL: u64 = 300;
+const MAX_LABEL_LENGTH: usize = 100;
+
+pub type LinkError = Box<dyn std::error::Error + Send + Sync>;
+
+// Options a link can't be minted with. The message is safe to return to the caller, unlike the
+// store and signing errors minting can also fail with
+#[derive(Debug)]
+pub struct InvalidLinkOptions(pub String);
+
+impl fmt::Display for InvalidLinkOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidLinkOptions {}
+
+// How new links are minted. Stored links are kept in the token store and can limit and track
+// downloads, signed links carry their own expiry, variant and format and aren't stored anywhere
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkMode {
+    Stored,
+    Signed,
+}
+
+impl LinkMode {
+    // RESUME_LINK_MODE is either "redis" (the default) or "signed", which needs
+    // RESUME_LINK_SIGNING_KEY. Read once on start-up, so a bad value stops the server instead of
+    // failing every download
+    pub fn configured() -> Result<Self, LinkError> {
+        let mode = match get_env_variable("RESUME_LINK_MODE").as_deref() {
+            None | Some("redis") | Some("stored") => LinkMode::Stored,
+            Some("signed") => LinkMode::Signed,
+            Some(mode) => return Err(format!("Unknown RESUME_LINK_MODE: {mode}").into()),
+        };
+
+        if mode == LinkMode::Signed && !can_sign() {
+            return Err("RESUME_LINK_MODE=signed needs RESUME_LINK_SIGNING_KEY".into());
+        }
+
+        Ok(mode)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LinkMode::Stored => "stored",
+            LinkMode::Signed => "signed",
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct LinkOptions {
+    pub ttl_secs: Option<u64>,
+    // 1 for a single use link, unlimited when not set
+    pub max_downloads: Option<u32>,
+    // Who the link was made for, e.g. "Acme recruiter"
+    pub label: Option<String>,
+    pub variant: Option<String>,
+    // File extension, e.g. "pdf" or "md". Any format can be downloaded when not set
+    pub format: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DownloadLink {
+    pub token: String,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub max_downloads: Option<u32>,
+    pub label: Option<String>,
+    pub variant: Option<String>,
+    pub format: Option<String>,
+}
+
+impl DownloadLink {
+    pub fn url(&self) -> String {
+        let extension = self.format.as_deref().unwrap_or("pdf");
+
+        match &self.variant {
+            Some(variant) => format!("/{}/resume.{extension}?variant={variant}", self.token),
+            None => format!("/{}/resume.{extension}", self.token),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DownloadRecord {
+    pub downloaded_at: u64,
+    pub label: Option<String>,
+    pub user_agent: Option<String>,
+    pub variant: String,
+    pub format: String,
+}
+
+#[derive(Serialize)]
+pub struct LinkDetails {
+    #[serde(flatten)]
+    pub link: DownloadLink,
+    pub downloads: u32,
+    pub log: Vec<DownloadRecord>,
+}
+
+pub enum LinkClaim {
+    Unknown,
+    // Expired links whose token has already been cleaned up are still recognized by their details
+    Expired,
+    // Every allowed download has been used
+    Exhausted,
+    Allowed,
+}
+
+// The outcome of checking a link, the same for both link modes
+pub enum LinkCheck {
+    Allowed(VerifiedLink),
+    Unknown,
+    Expired,
+    // Every allowed download has been used
+    UsedUp,
+    // The link was minted for a different variant or format
+    WrongVariant(String),
+    WrongFormat(String),
+}
+
+pub struct VerifiedLink {
+    // The variant to serve, the requested one or else the one the link was minted for
+    pub variant: Option<String>,
+    // Details of stored links, their downloads are recorded
+    pub tracked: Option<DownloadLink>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+// Error messages are safe to return to the caller
+fn check_options(options: &LinkOptions) -> Result<(), String> {
+    if let Some(ttl) = options.ttl_secs {
+        if ttl == 0 || ttl > *RESUME_LINK_MAX_TTL {
+            return Err(format!(
+                "ttl_secs has to be between 1 and {}",
+                *RESUME_LINK_MAX_TTL
+            ));
+        }
+    }
+
+    if options.max_downloads == Some(0) {
+        return Err("max_downloads has to be at least 1".to_string());
+    }
+
+    if let Some(label) = &options.label {
+        if label.trim().is_empty() || label.chars().count() > MAX_LABEL_LENGTH {
+            return Err(format!(
+                "label has to be between 1 and {MAX_LABEL_LENGTH} characters"
+            ));
+        }
+    }
+
+    if let Some(variant) = &options.variant {
+        check_variant_name(variant)?;
+    }
+
+    if let Some(format) = &options.format {
+        ExportFormat::from_extension(format)
+            .ok_or_else(|| format!("Unknown resume format: {format}"))?;
+    }
+
+    Ok(())
+}
+
+// Mints a link in the given mode. Signed links can't limit or track downloads
+pub async fn mint_link(
+    store: &dyn TokenStore,
+    mode: LinkMode,
+    options: LinkOptions,
+) -> Result<DownloadLink, LinkError> {
+    check_options(&options).map_err(InvalidLinkOptions)?;
+
+    let created_at = now();
+
+    let mut link = DownloadLink {
+        token: String::new(),
+        created_at,
+        expires_at: created_at + options.ttl_secs.unwrap_or(DEFAULT_LINK_TTL),
+        max_downloads: options.max_downloads,
+        label: options.label.map(|label| label.trim().to_string()),
+        variant: options.variant,
+        format: options
+            .format
+            .as_deref()
+            .and_then(ExportFormat::from_extension)
+            .map(|format| format.extension().to_string()),
+    };
+
+    match mode {
+        LinkMode::Signed => {
+            if link.max_downloads.is_some() || link.label.is_some() {
+                return Err(InvalidLinkOptions(
+                    "max_downloads and label need RESUME_LINK_MODE=redis".to_string(),
+                )
+                .into());
+            }
+
+            link.token = sign_link(&SignedLink {
+                expires_at: link.expires_at,
+                variant: link.variant.clone(),
+                format: link.format.clone(),
+            })?;
+        }
+        LinkMode::Stored => {
+            link.token = generate_token();
+
+            store
+                .save_link(&link, link.expires_at + *RESUME_LINK_LOG_RETENTION)
+                .await?;
+        }
+    }
+
+    Ok(link)
+}
+
+// Links minted for a variant or format only serve that one
+fn check_restrictions(
+    variant: Option<&str>,
+    format: Option<&str>,
+    requested_variant: Option<&str>,
+    requested_format: &str,
+) -> Option<LinkCheck> {
+    if let (Some(requested), Some(allowed)) = (requested_variant, variant) {
+        if requested != allowed {
+            return Some(LinkCheck::WrongVariant(allowed.to_string()));
+        }
+    }
+
+    match format {
+        Some(allowed) if allowed != requested_format => {
+            Some(LinkCheck::WrongFormat(allowed.to_string()))
+        }
+        _ => None,
+    }
+}
+
+// Checks a link of either kind, whatever RESUME_LINK_MODE is currently set to, so switching modes
+// doesn't break links that are already out there. Nothing is counted here, see claim_link
+pub async fn check_link(
+    store: &dyn TokenStore,
+    token: &str,
+    requested_format: &str,
+    requested_variant: Option<&str>,
+) -> Result<LinkCheck, LinkError> {
+    if is_signed_token(token) {
+        let Some(link) = verify_signed_token(token) else {
+            return Ok(LinkCheck::Unknown);
+        };
+
+        if let Some(rejected) = check_restrictions(
+            link.variant.as_deref(),
+            link.format.as_deref(),
+            requested_variant,
+            requested_format,
+        ) {
+            return Ok(rejected);
+        }
+
+        if link.expires_at < now() {
+            return Ok(LinkCheck::Expired);
+        }
+
+        return Ok(LinkCheck::Allowed(VerifiedLink {
+            variant: requested_variant.map(str::to_string).or(link.variant),
+            tracked: None,
+        }));
+    }
+
+    let Some(link) = store.load_link(token).await? else {
+        return Ok(LinkCheck::Unknown);
+    };
+
+    if let Some(rejected) = check_restrictions(
+        link.variant.as_deref(),
+        link.format.as_deref(),
+        requested_variant,
+        requested_format,
+    ) {
+        return Ok(rejected);
+    }
+
+    if link.expires_at < now() {
+        return Ok(LinkCheck::Expired);
+    }
+
+    if let Some(max_downloads) = link.max_downloads {
+        let (downloads, _) = store.link_downloads(token).await?;
+
+        if downloads >= max_downloads {
+            return Ok(LinkCheck::UsedUp);
+        }
+    }
+
+    Ok(LinkCheck::Allowed(VerifiedLink {
+        variant: requested_variant
+            .map(str::to_string)
+            .or_else(|| link.variant.clone()),
+        tracked: Some(link),
+    }))
+}
+
+// Counts a download of a checked link. It's only called once the download is ready to be sent, so
+// a request that fails on its way never uses up a link. Expiry and the download limit are checked
+// again here, in the same step as the count, since both can change after check_link
+pub async fn claim_link(
+    store: &dyn TokenStore,
+    verified: &VerifiedLink,
+) -> Result<LinkClaim, LinkError> {
+    match &verified.tracked {
+        Some(link) => store.claim_download(&link.token, now()).await,
+        // Signed links have no download limit
+        None => Ok(LinkClaim::Allowed),
+    }
+}
+
+pub async fn record_download(
+    store: &dyn TokenStore,
+    link: &DownloadLink,
+    record: &DownloadRecord,
+) -> Result<(), LinkError> {
+    store
+        .record_download(
+            &link.token,
+            record,
+            link.expires_at + *RESUME_LINK_LOG_RETENTION,
+        )
+        .await
+}
+
+pub async fn link_details(
+    store: &dyn TokenStore,
+    token: &str,
+) -> Result<Option<LinkDetails>, LinkError> {
+    let Some(link) = store.load_link(token).await? else {
+        return Ok(None);
+    };
+
+    let (downloads, log) = store.link_downloads(token).await?;
+
+    Ok(Some(LinkDetails {
+        link,
+        downloads,
+        log,
+    }))
+}
+
+// Every link whose details are still kept, newest first
+pub async fn list_links(store: &dyn TokenStore) -> Result<Vec<LinkDetails>, LinkError> {
+    let tokens = store.link_tokens(now()).await?;
+
+    let mut links = Vec::new();
+    for token in tokens {
+        if let Some(details) = link_details(store, &token).await? {
+            links.push(details);
+        }
+    }
+
+    links.sort_by(|a, b| b.link.created_at.cmp(&a.link.created_at));
+
+    Ok(links)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::services::{
+        failing_token_store::FailingTokenStore, memory_token_store::MemoryTokenStore,
+    };
+
+    use super::*;
+
+    const MODES: [LinkMode; 2] = [LinkMode::Stored, LinkMode::Signed];
+
+    fn set_signing_key() {
+        std::env::set_var("RESUME_LINK_SIGNING_KEY", "test signing key");
+    }
+
+    fn stored_link(token: &str, max_downloads: Option<u32>) -> DownloadLink {
+        DownloadLink {
+            token: token.to_string(),
+            created_at: now(),
+            expires_at: now() + DEFAULT_LINK_TTL,
+            max_downloads,
+            label: None,
+            variant: None,
+            format: None,
+        }
+    }
+
+    async fn mint(store: &dyn TokenStore, mode: LinkMode, options: LinkOptions) -> DownloadLink {
+        mint_link(store, mode, options).await.unwrap()
+    }
+
+    async fn check(
+        store: &dyn TokenStore,
+        token: &str,
+        format: &str,
+        variant: Option<&str>,
+    ) -> LinkCheck {
+        check_link(store, token, format, variant).await.unwrap()
+    }
+
+    // Flips the last character, which is part of the MAC for signed tokens
+    fn tamper(token: &str) -> String {
+        let mut tampered = token.to_string();
+        let last = tampered.pop().unwrap();
+        tampered.push(if last == '0' { '1' } else { '0' });
+        tampered
+    }
+
+    #[tokio::test]
+    async fn minted_links_verify() {
+        set_signing_key();
+
+        for mode in MODES {
+            let store = MemoryTokenStore::default();
+            let link = mint(&store, mode, LinkOptions::default()).await;
+
+            assert_eq!(is_signed_token(&link.token), mode == LinkMode::Signed);
+
+            let LinkCheck::Allowed(verified) = check(&store, &link.token, "pdf", None).await else {
+                panic!("{mode:?} link should be allowed");
+            };
+            assert_eq!(verified.variant, None);
+            assert_eq!(verified.tracked.is_some(), mode == LinkMode::Stored);
+
+            // Links without restrictions serve any format and variant
+            let LinkCheck::Allowed(verified) =
+                check(&store, &link.token, "md", Some("backend")).await
+            else {
+                panic!("{mode:?} link should be allowed");
+            };
+            assert_eq!(verified.variant.as_deref(), Some("backend"));
+        }
+    }
+
+    #[tokio::test]
+    async fn tampered_and_unknown_links_are_rejected() {
+        set_signing_key();
+
+        for mode in MODES {
+            let store = MemoryTokenStore::default();
+            let link = mint(&store, mode, LinkOptions::default()).await;
+
+            assert!(matches!(
+                check(&store, &tamper(&link.token), "pdf", None).await,
+                LinkCheck::Unknown
+            ));
+        }
+
+        // Changing a signed field invalidates the MAC
+        let store = MemoryTokenStore::default();
+        let link = mint(&store, LinkMode::Signed, LinkOptions::default()).await;
+        let (payload, signature) = link.token.rsplit_once('.').unwrap();
+        let (payload, _) = payload.rsplit_once('.').unwrap();
+        let forged = format!("{payload}.pdf.{signature}");
+
+        assert!(verify_signed_token(&forged).is_none());
+        assert!(matches!(
+            check(&store, &forged, "pdf", None).await,
+            LinkCheck::Unknown
+        ));
+
+        assert!(matches!(
+            check(&store, "not-a-link", "pdf", None).await,
+            LinkCheck::Unknown
+        ));
+    }
+
+    #[tokio::test]
+    async fn links_only_serve_their_variant_and_format() {
+        set_signing_key();
+
+        for mode in MODES {
+            let store = MemoryTokenStore::default();
+            let link = mint(
+                &store,
+                mode,
+                LinkOptions {
+                    variant: Some("backend".to_string()),
+                    format: Some("md".to_string()),
+                    ..LinkOptions::default()
+                },
+            )
+            .await;
+
+            let LinkCheck::Allowed(verified) = check(&store, &link.token, "md", None).await else {
+                panic!("{mode:?} link should be allowed");
+            };
+            assert_eq!(verified.variant.as_deref(), Some("backend"));
+
+            assert!(matches!(
+                check(&store, &link.token, "md", Some("backend")).await,
+                LinkCheck::Allowed(_)
+            ));
+            assert!(matches!(
+                check(&store, &link.token, "md", Some("frontend")).await,
+                LinkCheck::WrongVariant(allowed) if allowed == "backend"
+            ));
+            assert!(matches!(
+                check(&store, &link.token, "pdf", None).await,
+                LinkCheck::WrongFormat(allowed) if allowed == "md"
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn links_expire() {
+        set_signing_key();
+
+        let store = MemoryTokenStore::default();
+        let mut links = Vec::new();
+
+        for mode in MODES {
+            let link = mint(
+                &store,
+                mode,
+                LinkOptions {
+                    ttl_secs: Some(1),
+                    ..LinkOptions::default()
+                },
+            )
+            .await;
+
+            assert!(matches!(
+                check(&store, &link.token, "pdf", None).await,
+                LinkCheck::Allowed(_)
+            ));
+
+            links.push(link);
+        }
+
+        // Links are still good during the second they expire at
+        tokio::time::sleep(Duration::from_millis(2100)).await;
+
+        for link in links {
+            assert!(matches!(
+                check(&store, &link.token, "pdf", None).await,
+                LinkCheck::Expired
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn signed_links_cant_be_limited() {
+        set_signing_key();
+
+        let store = MemoryTokenStore::default();
+
+        for options in [
+            LinkOptions {
+                max_downloads: Some(1),
+                ..LinkOptions::default()
+            },
+            LinkOptions {
+                label: Some("Acme recruiter".to_string()),
+                ..LinkOptions::default()
+            },
+        ] {
+            let err = mint_link(&store, LinkMode::Signed, options)
+                .await
+                .err()
+                .unwrap();
+            assert!(err.is::<InvalidLinkOptions>());
+        }
+    }
+
+    // Only options the caller got wrong are reported back to them, a store that can't save the
+    // link is the server's problem
+    #[tokio::test]
+    async fn invalid_options_are_told_apart_from_failures() {
+        set_signing_key();
+
+        let store = MemoryTokenStore::default();
+
+        let invalid_options = || {
+            [
+                LinkOptions {
+                    ttl_secs: Some(0),
+                    ..LinkOptions::default()
+                },
+                LinkOptions {
+                    max_downloads: Some(0),
+                    ..LinkOptions::default()
+                },
+                LinkOptions {
+                    label: Some(" ".to_string()),
+                    ..LinkOptions::default()
+                },
+                LinkOptions {
+                    format: Some("docx".to_string()),
+                    ..LinkOptions::default()
+                },
+            ]
+        };
+
+        for mode in MODES {
+            for options in invalid_options() {
+                let err = mint_link(&store, mode, options).await.err().unwrap();
+                assert!(err.is::<InvalidLinkOptions>(), "{err}");
+            }
+        }
+
+        let err = mint_link(
+            &FailingTokenStore::new("save_link"),
+            LinkMode::Stored,
+            LinkOptions::default(),
+        )
+        .await
+        .err()
+        .unwrap();
+        assert!(!err.is::<InvalidLinkOptions>());
+    }
+
+    #[test]
+    fn link_mode_is_checked() {
+        set_signing_key();
+
+        // Nothing else reads RESUME_LINK_MODE, the tests pass the mode in
+        std::env::set_var("RESUME_LINK_MODE", "bogus");
+        assert!(LinkMode::configured().is_err());
+
+        std::env::set_var("RESUME_LINK_MODE", "signed");
+        assert_eq!(LinkMode::configured().unwrap(), LinkMode::Signed);
+
+        std::env::set_var("RESUME_LINK_MODE", "redis");
+        assert_eq!(LinkMode::configured().unwrap(), LinkMode::Stored);
+
+        std::env::remove_var("RESUME_LINK_MODE");
+    }
+
+    #[test]
+    fn signed_fields_round_trip() {
+        set_signing_key();
+
+        let token = sign_link(&SignedLink {
+            expires_at: 1_800_000_000,
+            variant: Some("backend".to_string()),
+            format: None,
+        })
+        .unwrap();
+
+        let link = verify_signed_token(&token).unwrap();
+
+        assert_eq!(link.expires_at, 1_800_000_000);
+        assert_eq!(link.variant.as_deref(), Some("backend"));
+        assert_eq!(link.format, None);
+    }
+
+    #[tokio::test]
+    async fn checking_a_link_does_not_use_it_up() {
+        let store = MemoryTokenStore::default();
+        let link = stored_link("single-use", Some(1));
+        store.save_link(&link, link.expires_at).await.unwrap();
+
+        for _ in 0..2 {
+            assert!(matches!(
+                check_link(&store, "single-use", "pdf", None).await.unwrap(),
+                LinkCheck::Allowed(_)
+            ));
+        }
+
+        let LinkCheck::Allowed(verified) =
+            check_link(&store, "single-use", "pdf", None).await.unwrap()
+        else {
+            panic!("Link should be allowed");
+        };
+
+        assert!(matches!(
+            claim_link(&store, &verified).await.unwrap(),
+            LinkClaim::Allowed
+        ));
+        assert!(matches!(
+            check_link(&store, "single-use", "pdf", None).await.unwrap(),
+            LinkCheck::UsedUp
+        ));
+        // A request that passed the check before the last download was claimed still can't have it
+        assert!(matches!(
+            claim_link(&store, &verified).await.unwrap(),
+            LinkClaim::Exhausted
+        ));
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+
+use crate::oauth::SessionData;
+
+use super::{
+    download_link_service::{DownloadLink, DownloadRecord, LinkClaim},
+    memory_token_store::MemoryTokenStore,
+    token_store_service::{LoginState, TokenStore, TokenStoreError},
+};
+
+// A memory store that can't be reached for one step, named after the method that fails, e.g.
+// "save_session". Everything else works as usual
+pub(crate) struct FailingTokenStore {
+    inner: MemoryTokenStore,
+    failing: &'static str,
+}
+
+impl FailingTokenStore {
+    pub fn new(failing: &'static str) -> Self {
+        FailingTokenStore {
+            inner: MemoryTokenStore::default(),
+            failing,
+        }
+    }
+
+    fn check(&self, step: &str) -> Result<(), TokenStoreError> {
+        match self.failing == step {
+            true => Err(format!("Connection refused during {step}").into()),
+            false => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl TokenStore for FailingTokenStore {
+    fn name(&self) -> &'static str {
+        "failing"
+    }
+
+    async fn add_state(
+        &self,
+        state: &str,
+        login_state: &LoginState,
+        expires_at: u64,
+    ) -> Result<(), TokenStoreError> {
+        self.inner.add_state(state, login_state, expires_at).await
+    }
+
+    async fn take_state(
+        &self,
+        state: &str,
+        now: u64,
+    ) -> Result<Option<LoginState>, TokenStoreError> {
+        self.check("take_state")?;
+        self.inner.take_state(state, now).await
+    }
+
+    async fn save_session(&self, session_data: &SessionData) -> Result<(), TokenStoreError> {
+        self.check("save_session")?;
+        self.inner.save_session(session_data).await
+    }
+
+    async fn load_session(&self, key: &str) -> Result<Option<SessionData>, TokenStoreError> {
+        self.inner.load_session(key).await
+    }
+
+    async fn delete_session(&self, key: &str) -> Result<(), TokenStoreError> {
+        self.inner.delete_session(key).await
+    }
+
+    async fn save_link(&self, link: &DownloadLink, keep_until: u64) -> Result<(), TokenStoreError> {
+        self.check("save_link")?;
+        self.inner.save_link(link, keep_until).await
+    }
+
+    async fn load_link(&self, token: &str) -> Result<Option<DownloadLink>, TokenStoreError> {
+        self.inner.load_link(token).await
+    }
+
+    async fn claim_download(&self, token: &str, now: u64) -> Result<LinkClaim, TokenStoreError> {
+        self.inner.claim_download(token, now).await
+    }
+
+    async fn record_download(
+        &self,
+        token: &str,
+        record: &DownloadRecord,
+        keep_until: u64,
+    ) -> Result<(), TokenStoreError> {
+        self.inner.record_download(token, record, keep_until).await
+    }
+
+    async fn link_downloads(
+        &self,
+        token: &str,
+    ) -> Result<(u32, Vec<DownloadRecord>), TokenStoreError> {
+        self.inner.link_downloads(token).await
+    }
+
+    async fn link_tokens(&self, now: u64) -> Result<Vec<String>, TokenStoreError> {
+        self.inner.link_tokens(now).await
+    }
+
+    async fn sweep_expired(
+        &self,
+        now: u64,
+    ) -> Result<BTreeMap<&'static str, u64>, TokenStoreError> {
+        self.inner.sweep_expired(now).await
+    }
+}
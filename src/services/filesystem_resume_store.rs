@@ -0,0 +1,318 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    services::{
+        resume_history_service::{write_atomically, NewRevision, ResumeHistory, ResumeRevision},
+        resume_store_service::{ResumeStore, StoreError},
+        resume_variant_service::{
+            history_dir, known_variants_in, parsed_resume_path, pending_resume_path, resume_dir,
+            stored_variants_in,
+        },
+    },
+    Resume,
+};
+
+// A revision as it's kept in history/index.json. Its JSON and PDF are files in the revision's
+// directory
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct IndexedRevision {
+    rev: u64,
+    uploaded_at: u64,
+    sha256: String,
+    pdf_file: Option<String>,
+    json_file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct HistoryIndex {
+    live: Option<u64>,
+    pending: Option<u64>,
+    revisions: Vec<IndexedRevision>,
+}
+
+impl From<IndexedRevision> for ResumeRevision {
+    fn from(value: IndexedRevision) -> Self {
+        ResumeRevision {
+            rev: value.rev,
+            uploaded_at: value.uploaded_at,
+            sha256: value.sha256,
+            has_pdf: value.pdf_file.is_some(),
+            note: value.note,
+        }
+    }
+}
+
+// The original layout: parsed_resume.json and parsed_resume_pending.json in each variant's
+// directory, and the revision history under its history directory. Paths are relative to `root`,
+// which is the working directory unless the files are kept somewhere else
+#[derive(Default)]
+pub struct FilesystemResumeStore {
+    root: PathBuf,
+    // Guards read-modify-write cycles on the history index
+    index_lock: Mutex<()>,
+}
+
+impl FilesystemResumeStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FilesystemResumeStore {
+            root: root.into(),
+            index_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn has_live(&self, variant: &str) -> bool {
+        self.path(parsed_resume_path(variant)).is_file()
+    }
+
+    pub fn has_pending(&self, variant: &str) -> bool {
+        self.path(pending_resume_path(variant)).is_file()
+    }
+
+    fn path(&self, relative: PathBuf) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    fn index_path(&self, variant: &str) -> PathBuf {
+        self.path(history_dir(variant)).join("index.json")
+    }
+
+    fn read_index(&self, variant: &str) -> Result<HistoryIndex, StoreError> {
+        let path = self.index_path(variant);
+
+        if !path.is_file() {
+            return Ok(HistoryIndex::default());
+        }
+
+        let index_string = fs::read_to_string(path)?;
+
+        Ok(serde_json::from_str::<HistoryIndex>(&index_string)?)
+    }
+
+    fn write_index(&self, variant: &str, index: &HistoryIndex) -> Result<(), StoreError> {
+        fs::create_dir_all(self.path(history_dir(variant)))?;
+
+        write_atomically(
+            &self.index_path(variant),
+            serde_json::to_string(index)?.as_bytes(),
+        )
+    }
+
+    // Runs a read-modify-write cycle on the index while holding the lock
+    fn update_index<T>(
+        &self,
+        variant: &str,
+        update: impl FnOnce(&mut HistoryIndex) -> Result<T, StoreError>,
+    ) -> Result<T, StoreError> {
+        let _guard = self
+            .index_lock
+            .lock()
+            .map_err(|_| "History lock poisoned")?;
+
+        let mut index = self.read_index(variant)?;
+        let result = update(&mut index)?;
+        self.write_index(variant, &index)?;
+
+        Ok(result)
+    }
+
+    fn find_revision(
+        &self,
+        variant: &str,
+        rev: u64,
+    ) -> Result<Option<IndexedRevision>, StoreError> {
+        let _guard = self
+            .index_lock
+            .lock()
+            .map_err(|_| "History lock poisoned")?;
+
+        Ok(self
+            .read_index(variant)?
+            .revisions
+            .into_iter()
+            .find(|revision| revision.rev == rev))
+    }
+}
+
+fn read_resume(path: &Path) -> Result<Resume, StoreError> {
+    let resume_string = fs::read_to_string(path)?;
+
+    Ok(serde_json::from_str::<Resume>(&resume_string)?)
+}
+
+#[async_trait]
+impl ResumeStore for FilesystemResumeStore {
+    fn name(&self) -> &'static str {
+        "filesystem"
+    }
+
+    fn keeps_files(&self) -> bool {
+        true
+    }
+
+    async fn live_variants(&self) -> Result<Vec<String>, StoreError> {
+        Ok(stored_variants_in(&self.root))
+    }
+
+    async fn known_variants(&self) -> Result<Vec<String>, StoreError> {
+        Ok(known_variants_in(&self.root))
+    }
+
+    async fn load_live(&self, variant: &str) -> Result<Resume, StoreError> {
+        read_resume(&self.path(parsed_resume_path(variant)))
+    }
+
+    async fn load_pending(&self, variant: &str) -> Result<Resume, StoreError> {
+        read_resume(&self.path(pending_resume_path(variant)))
+    }
+
+    async fn save_pending(&self, variant: &str, resume: &Resume) -> Result<(), StoreError> {
+        fs::create_dir_all(self.path(resume_dir(variant)))?;
+
+        // Overwrites existing parsed_resume_pending.json file
+        write_atomically(
+            &self.path(pending_resume_path(variant)),
+            serde_json::to_string(resume)?.as_bytes(),
+        )
+    }
+
+    async fn save_live(&self, variant: &str, resume: &Resume) -> Result<(), StoreError> {
+        fs::create_dir_all(self.path(resume_dir(variant)))?;
+
+        write_atomically(
+            &self.path(parsed_resume_path(variant)),
+            serde_json::to_string(resume)?.as_bytes(),
+        )
+    }
+
+    async fn promote_pending(&self, variant: &str) -> Result<(), StoreError> {
+        fs::copy(
+            self.path(pending_resume_path(variant)),
+            self.path(parsed_resume_path(variant)),
+        )?;
+
+        Ok(())
+    }
+
+    async fn discard_pending(&self, variant: &str) -> Result<(), StoreError> {
+        match fs::remove_file(self.path(pending_resume_path(variant))) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn load_history(&self, variant: &str) -> Result<ResumeHistory, StoreError> {
+        let _guard = self
+            .index_lock
+            .lock()
+            .map_err(|_| "History lock poisoned")?;
+
+        let index = self.read_index(variant)?;
+
+        Ok(ResumeHistory {
+            live: index.live,
+            pending: index.pending,
+            revisions: index.revisions.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    async fn add_revision(
+        &self,
+        variant: &str,
+        revision: NewRevision<'_>,
+    ) -> Result<ResumeRevision, StoreError> {
+        self.update_index(variant, |index| {
+            let rev = index
+                .revisions
+                .iter()
+                .map(|revision| revision.rev)
+                .max()
+                .unwrap_or(0)
+                + 1;
+
+            let revision_dir = self.path(history_dir(variant)).join(rev.to_string());
+            fs::create_dir_all(&revision_dir)?;
+
+            let json_path = revision_dir.join("resume.json");
+            write_atomically(
+                &json_path,
+                serde_json::to_string(revision.resume)?.as_bytes(),
+            )?;
+
+            let pdf_file = match revision.pdf_bytes {
+                Some(pdf_bytes) => {
+                    let pdf_path = revision_dir.join("resume.pdf");
+                    write_atomically(&pdf_path, pdf_bytes)?;
+                    Some(pdf_path.to_string_lossy().to_string())
+                }
+                None => None,
+            };
+
+            let indexed = IndexedRevision {
+                rev,
+                uploaded_at: revision.uploaded_at,
+                sha256: revision.sha256,
+                pdf_file,
+                json_file: json_path.to_string_lossy().to_string(),
+                note: revision.note.map(str::to_string),
+            };
+
+            index.revisions.push(indexed.clone());
+            index.pending = Some(rev);
+
+            Ok(indexed.into())
+        })
+    }
+
+    async fn load_revision(&self, variant: &str, rev: u64) -> Result<Option<Resume>, StoreError> {
+        match self.find_revision(variant, rev)? {
+            Some(revision) => Ok(Some(read_resume(Path::new(&revision.json_file))?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn load_revision_pdf(
+        &self,
+        variant: &str,
+        rev: u64,
+    ) -> Result<Option<Vec<u8>>, StoreError> {
+        match self
+            .find_revision(variant, rev)?
+            .and_then(|revision| revision.pdf_file)
+        {
+            Some(pdf_file) => Ok(Some(fs::read(pdf_file)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn set_live_revision(&self, variant: &str, rev: u64) -> Result<(), StoreError> {
+        self.update_index(variant, |index| {
+            index.live = Some(rev);
+
+            if index.pending == Some(rev) {
+                index.pending = None;
+            }
+
+            Ok(())
+        })
+    }
+
+    async fn clear_pending_revision(&self, variant: &str) -> Result<Option<u64>, StoreError> {
+        // Nothing is written when nothing is pending, so unknown variants don't get a directory
+        if self.load_history(variant).await?.pending.is_none() {
+            return Ok(None);
+        }
+
+        self.update_index(variant, |index| Ok(index.pending.take()))
+    }
+}
@@ -0,0 +1,379 @@
+use async_trait::async_trait;
+
+use crate::{
+    services::resume_parsing_service::{InvalidResume, ParseError, ResumeParser},
+    ApplicantInfo, Education, Experience, Resume, Skills,
+};
+
+// In-process fallback for when ParseCV is unavailable. Extracts the PDF text and splits it into
+// sections based on common resume headings. Much less accurate than ParseCV's NER model, but
+// good enough to keep uploads working
+pub struct LocalPdfParser;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Header,
+    Overview,
+    Experience,
+    Education,
+    Skills,
+    Other,
+}
+
+const BULLETS: [char; 6] = ['•', '-', '*', '▪', '●', '◦'];
+
+fn detect_heading(line: &str) -> Option<Section> {
+    let heading = line.trim_end_matches(':').trim().to_lowercase();
+
+    let section = match heading.as_str() {
+        "summary" | "overview" | "profile" | "about" | "about me" | "professional summary" => {
+            Section::Overview
+        }
+        "experience"
+        | "work experience"
+        | "professional experience"
+        | "employment"
+        | "employment history"
+        | "work history" => Section::Experience,
+        "education" => Section::Education,
+        "skills" | "technical skills" | "skills and technologies" | "technologies" => {
+            Section::Skills
+        }
+        "projects" | "certifications" | "awards" | "interests" | "publications" => Section::Other,
+        _ => return None,
+    };
+
+    Some(section)
+}
+
+fn strip_bullet(line: &str) -> Option<&str> {
+    line.strip_prefix(BULLETS).map(str::trim)
+}
+
+fn looks_like_duration(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    let has_year = text
+        .split(|c: char| !c.is_ascii_digit())
+        .any(|digits| digits.len() == 4 && (digits.starts_with("19") || digits.starts_with("20")));
+
+    has_year || lower.contains("present") || lower.contains("current")
+}
+
+fn parse_header(lines: &[&str], info: &mut ApplicantInfo) {
+    for line in lines {
+        for token in line.split(['|', '•', ',']).flat_map(str::split_whitespace) {
+            let lower = token.to_lowercase();
+
+            if token.contains('@') && info.email.is_none() {
+                info.email = Some(token.to_string());
+            } else if lower.contains("github.com") && info.github.is_none() {
+                info.github = Some(token.to_string());
+            } else if lower.contains("linkedin.com") && info.linkedin.is_none() {
+                info.linkedin = Some(token.to_string());
+            } else if (lower.starts_with("http") || lower.starts_with("www."))
+                && info.website.is_none()
+            {
+                info.website = Some(token.to_string());
+            }
+        }
+
+        // Phone numbers are often split by spaces, so count digits across each segment
+        for segment in line.split(['|', '•']).map(str::trim) {
+            let digits = segment.chars().filter(char::is_ascii_digit).count();
+            if info.phone.is_none() && (10..=15).contains(&digits) && !segment.contains('@') {
+                info.phone = Some(segment.to_string());
+            }
+        }
+    }
+
+    info.name = lines
+        .first()
+        .filter(|line| !line.contains('@') && !line.chars().any(|c| c.is_ascii_digit()))
+        .map(|line| line.trim().to_string());
+}
+
+fn new_experience() -> Experience {
+    Experience {
+        company: None,
+        title: None,
+        duration: None,
+        location: None,
+        desc: None,
+    }
+}
+
+// Role lines look like "Acme | Engineer | Jan 2020 – Present". Dashes inside a field are usually
+// part of a date range, so only pipes and spaced em dashes separate fields, and a range that was
+// split anyway is joined back together
+fn split_role_line(line: &str) -> Vec<String> {
+    let mut parts: Vec<String> = Vec::new();
+
+    for part in line.split('|').flat_map(|part| part.split(" — ")) {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
+
+        match parts.last_mut() {
+            Some(last) if looks_like_duration(last) && looks_like_duration(part) => {
+                last.push_str(" – ");
+                last.push_str(part);
+            }
+            _ => parts.push(part.to_string()),
+        }
+    }
+
+    parts
+}
+
+// Non-bullet lines describe the role, bullet lines describe the work. A non-bullet line after
+// some bullets starts a new entry
+fn parse_experience(lines: &[&str]) -> Vec<Experience> {
+    let mut entries: Vec<Experience> = Vec::new();
+    let mut current = new_experience();
+
+    for line in lines {
+        if let Some(item) = strip_bullet(line) {
+            current
+                .desc
+                .get_or_insert_with(Vec::new)
+                .push(item.to_string());
+            continue;
+        }
+
+        // Long bullets wrap onto lines without a bullet, these usually start in lowercase
+        if line.starts_with(char::is_lowercase) {
+            if let Some(last) = current.desc.as_mut().and_then(|desc| desc.last_mut()) {
+                last.push(' ');
+                last.push_str(line);
+                continue;
+            }
+        }
+
+        if current.desc.is_some() {
+            entries.push(std::mem::replace(&mut current, new_experience()));
+        }
+
+        for part in split_role_line(line) {
+            if looks_like_duration(&part) && current.duration.is_none() {
+                current.duration = Some(part);
+            } else if current.company.is_none() {
+                current.company = Some(part);
+            } else if current.title.is_none() {
+                current.title = Some(part);
+            } else if current.location.is_none() {
+                current.location = Some(part);
+            }
+        }
+    }
+
+    if current.company.is_some() || current.desc.is_some() {
+        entries.push(current);
+    }
+
+    entries
+}
+
+fn parse_education(lines: &[&str]) -> Vec<Education> {
+    const SCHOOLS: [&str; 4] = ["university", "college", "institute", "school"];
+    const DEGREES: [&str; 8] = [
+        "bachelor",
+        "master",
+        "associate",
+        "ph.d",
+        "b.s",
+        "m.s",
+        "b.a",
+        "m.a",
+    ];
+
+    let mut entries: Vec<Education> = Vec::new();
+
+    for line in lines {
+        let line = strip_bullet(line).unwrap_or(line);
+        let lower = line.to_lowercase();
+
+        if SCHOOLS.iter().any(|school| lower.contains(school)) {
+            entries.push(Education {
+                college: Some(line.to_string()),
+                degree: None,
+                major: None,
+            });
+        } else if DEGREES.iter().any(|degree| lower.contains(degree)) {
+            if entries.last().is_none_or(|entry| entry.degree.is_some()) {
+                entries.push(Education {
+                    college: None,
+                    degree: None,
+                    major: None,
+                });
+            }
+
+            let entry = entries.last_mut().unwrap();
+
+            match line.split_once(" in ") {
+                Some((degree, major)) => {
+                    entry.degree = Some(degree.trim().to_string());
+                    entry.major = Some(major.trim().to_string());
+                }
+                None => entry.degree = Some(line.to_string()),
+            }
+        }
+    }
+
+    entries
+}
+
+// Expects lines like "Languages: Rust, Java, Python". The label decides the category
+fn parse_skills(lines: &[&str]) -> Skills {
+    let mut skills = Skills {
+        languages: None,
+        frameworks: None,
+        devops: None,
+        database: None,
+        dev_tools: None,
+    };
+
+    for line in lines {
+        let line = strip_bullet(line).unwrap_or(line);
+        let (label, items) = line.split_once(':').unwrap_or(("", line));
+        let label = label.to_lowercase();
+
+        let category = if label.contains("language") {
+            &mut skills.languages
+        } else if label.contains("database") || label.contains("data") {
+            &mut skills.database
+        } else if label.contains("devops")
+            || label.contains("cloud")
+            || label.contains("deploy")
+            || label.contains("infrastructure")
+        {
+            &mut skills.devops
+        } else if label.contains("tool") && !label.contains("framework") {
+            &mut skills.dev_tools
+        } else {
+            &mut skills.frameworks
+        };
+
+        category.get_or_insert_with(Vec::new).extend(
+            items
+                .split([',', ';', '|'])
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string),
+        );
+    }
+
+    skills
+}
+
+pub fn parse_resume_text(text: &str) -> Resume {
+    let mut sections: Vec<(Section, Vec<&str>)> = vec![(Section::Header, Vec::new())];
+
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        match detect_heading(line) {
+            Some(section) => sections.push((section, Vec::new())),
+            None => sections.last_mut().unwrap().1.push(line),
+        }
+    }
+
+    let mut info = ApplicantInfo {
+        name: None,
+        phone: None,
+        email: None,
+        github: None,
+        linkedin: None,
+        website: None,
+    };
+    let mut overview = Vec::new();
+    let mut experience = Vec::new();
+    let mut education = Vec::new();
+    let mut skills_lines = Vec::new();
+
+    for (section, lines) in &sections {
+        match section {
+            Section::Header => parse_header(lines, &mut info),
+            Section::Overview => overview.extend(lines.iter().copied()),
+            Section::Experience => experience.extend(parse_experience(lines)),
+            Section::Education => education.extend(parse_education(lines)),
+            Section::Skills => skills_lines.extend(lines.iter().copied()),
+            Section::Other => {}
+        }
+    }
+
+    Resume {
+        info,
+        skills: parse_skills(&skills_lines),
+        overview: overview.join(" "),
+        experience,
+        education,
+    }
+}
+
+#[async_trait]
+impl ResumeParser for LocalPdfParser {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn parse(&self, file_bytes: &[u8]) -> Result<Resume, ParseError> {
+        let file_bytes = file_bytes.to_vec();
+
+        // Text extraction is CPU bound and can panic on malformed PDFs, so keep it off the
+        // actix worker thread
+        let text =
+            tokio::task::spawn_blocking(move || pdf_extract::extract_text_from_mem(&file_bytes))
+                .await
+                .map_err(|_| InvalidResume("PDF text extraction panicked".to_string()))?
+                .map_err(|err| InvalidResume(format!("Could not read PDF text: {err}")))?;
+
+        let resume = parse_resume_text(&text);
+
+        if resume.experience.is_empty() && resume.education.is_empty() {
+            return Err(Box::new(InvalidResume(
+                "Could not find any resume sections in PDF text".to_string(),
+            )));
+        }
+
+        Ok(resume)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_date_ranges_together() {
+        let entries = parse_experience(&[
+            "Acme | Engineer | Jan 2020 – Present | Boston, MA",
+            "• Built things",
+            "Globex — Developer — 2016 — 2019",
+            "• Fixed things",
+        ]);
+
+        assert_eq!(entries[0].company.as_deref(), Some("Acme"));
+        assert_eq!(entries[0].title.as_deref(), Some("Engineer"));
+        assert_eq!(entries[0].duration.as_deref(), Some("Jan 2020 – Present"));
+        assert_eq!(entries[0].location.as_deref(), Some("Boston, MA"));
+
+        assert_eq!(entries[1].company.as_deref(), Some("Globex"));
+        assert_eq!(entries[1].title.as_deref(), Some("Developer"));
+        assert_eq!(entries[1].duration.as_deref(), Some("2016 – 2019"));
+        assert_eq!(entries[1].location, None);
+    }
+
+    #[test]
+    fn joins_wrapped_bullets() {
+        let entries = parse_experience(&[
+            "Acme | Engineer | 2020 - 2021",
+            "• Built a very long",
+            "sentence that wraps",
+        ]);
+
+        assert_eq!(entries[0].duration.as_deref(), Some("2020 - 2021"));
+        assert_eq!(
+            entries[0].desc.as_deref(),
+            Some(&["Built a very long sentence that wraps".to_string()][..])
+        );
+    }
+}
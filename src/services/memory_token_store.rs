@@ -0,0 +1,297 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, MutexGuard},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    oauth::SessionData,
+    services::{
+        download_link_service::{DownloadLink, DownloadRecord, LinkClaim},
+        token_store_service::{
+            LoginState, TokenStore, TokenStoreError, LINKS, LINK_RECORDS, SESSIONS, SESSION_TTL,
+            STATES,
+        },
+    },
+};
+
+struct StoredLink {
+    link: DownloadLink,
+    downloads: u32,
+    log: Vec<DownloadRecord>,
+    keep_until: u64,
+}
+
+// Laid out like the redis keys, so expiry and sweeps behave the same way
+#[derive(Default)]
+struct Entries {
+    // State -> expiry and login details
+    states: HashMap<String, (u64, LoginState)>,
+    // Session key -> expiry and session JSON
+    sessions: HashMap<String, (u64, String)>,
+    // Token -> expiry, dropped once the link expires
+    links: HashMap<String, u64>,
+    // Token -> details, kept until the download log can be dropped
+    link_records: HashMap<String, StoredLink>,
+}
+
+// Keeps everything in process, for running without redis. Expired entries are ignored straight
+// away and removed by the sweeper
+#[derive(Default)]
+pub struct MemoryTokenStore {
+    entries: Mutex<Entries>,
+}
+
+impl MemoryTokenStore {
+    fn entries(&self) -> Result<MutexGuard<'_, Entries>, TokenStoreError> {
+        self.entries
+            .lock()
+            .map_err(|_| "Memory token store lock poisoned".into())
+    }
+}
+
+fn live_record<'a>(entries: &'a mut Entries, token: &str, now: u64) -> Option<&'a mut StoredLink> {
+    entries
+        .link_records
+        .get_mut(token)
+        .filter(|record| record.keep_until >= now)
+}
+
+fn live_session<'a>(entries: &'a Entries, key: &str, now: u64) -> Option<&'a String> {
+    entries
+        .sessions
+        .get(key)
+        .filter(|(expires_at, _)| *expires_at >= now)
+        .map(|(_, session_string)| session_string)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[async_trait]
+impl TokenStore for MemoryTokenStore {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn add_state(
+        &self,
+        state: &str,
+        login_state: &LoginState,
+        expires_at: u64,
+    ) -> Result<(), TokenStoreError> {
+        self.entries()?
+            .states
+            .insert(state.to_string(), (expires_at, login_state.clone()));
+
+        Ok(())
+    }
+
+    async fn take_state(
+        &self,
+        state: &str,
+        now: u64,
+    ) -> Result<Option<LoginState>, TokenStoreError> {
+        let entry = self.entries()?.states.remove(state);
+
+        Ok(entry
+            .filter(|(expires_at, _)| *expires_at >= now)
+            .map(|(_, login_state)| login_state))
+    }
+
+    async fn save_session(&self, session_data: &SessionData) -> Result<(), TokenStoreError> {
+        let session_string = serde_json::to_string(session_data)?;
+
+        self.entries()?
+            .sessions
+            .insert(session_data.key(), (now() + SESSION_TTL, session_string));
+
+        Ok(())
+    }
+
+    async fn load_session(&self, key: &str) -> Result<Option<SessionData>, TokenStoreError> {
+        match live_session(&*self.entries()?, key, now()) {
+            Some(session_string) => Ok(Some(serde_json::from_str(session_string)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn delete_session(&self, key: &str) -> Result<(), TokenStoreError> {
+        self.entries()?.sessions.remove(key);
+
+        Ok(())
+    }
+
+    async fn save_link(&self, link: &DownloadLink, keep_until: u64) -> Result<(), TokenStoreError> {
+        let mut entries = self.entries()?;
+
+        entries.link_records.insert(
+            link.token.clone(),
+            StoredLink {
+                link: link.clone(),
+                downloads: 0,
+                log: Vec::new(),
+                keep_until,
+            },
+        );
+        entries.links.insert(link.token.clone(), link.expires_at);
+
+        Ok(())
+    }
+
+    async fn load_link(&self, token: &str) -> Result<Option<DownloadLink>, TokenStoreError> {
+        let mut entries = self.entries()?;
+
+        Ok(live_record(&mut entries, token, now()).map(|record| record.link.clone()))
+    }
+
+    async fn claim_download(&self, token: &str, now: u64) -> Result<LinkClaim, TokenStoreError> {
+        let mut entries = self.entries()?;
+
+        let expires_at = entries.links.get(token).copied();
+
+        let Some(record) = live_record(&mut entries, token, now) else {
+            return Ok(LinkClaim::Unknown);
+        };
+
+        if expires_at.is_none_or(|expires_at| expires_at < now) {
+            return Ok(LinkClaim::Expired);
+        }
+
+        if record
+            .link
+            .max_downloads
+            .is_some_and(|max_downloads| record.downloads >= max_downloads)
+        {
+            return Ok(LinkClaim::Exhausted);
+        }
+
+        record.downloads += 1;
+
+        Ok(LinkClaim::Allowed)
+    }
+
+    async fn record_download(
+        &self,
+        token: &str,
+        record: &DownloadRecord,
+        keep_until: u64,
+    ) -> Result<(), TokenStoreError> {
+        let mut entries = self.entries()?;
+
+        if let Some(stored) = live_record(&mut entries, token, now()) {
+            stored.log.push(record.clone());
+            stored.keep_until = stored.keep_until.max(keep_until);
+        }
+
+        Ok(())
+    }
+
+    async fn link_downloads(
+        &self,
+        token: &str,
+    ) -> Result<(u32, Vec<DownloadRecord>), TokenStoreError> {
+        let mut entries = self.entries()?;
+
+        Ok(live_record(&mut entries, token, now())
+            .map(|record| (record.downloads, record.log.clone()))
+            .unwrap_or_default())
+    }
+
+    async fn link_tokens(&self, now: u64) -> Result<Vec<String>, TokenStoreError> {
+        let entries = self.entries()?;
+
+        Ok(entries
+            .link_records
+            .iter()
+            .filter(|(_, record)| record.keep_until >= now)
+            .map(|(token, _)| token.clone())
+            .collect())
+    }
+
+    async fn sweep_expired(
+        &self,
+        now: u64,
+    ) -> Result<BTreeMap<&'static str, u64>, TokenStoreError> {
+        let mut entries = self.entries()?;
+        let mut pruned = BTreeMap::new();
+
+        let before = entries.states.len();
+        entries
+            .states
+            .retain(|_, (expires_at, _)| *expires_at >= now);
+        pruned.insert(STATES, (before - entries.states.len()) as u64);
+
+        let before = entries.sessions.len();
+        entries
+            .sessions
+            .retain(|_, (expires_at, _)| *expires_at >= now);
+        pruned.insert(SESSIONS, (before - entries.sessions.len()) as u64);
+
+        let before = entries.links.len();
+        entries.links.retain(|_, expires_at| *expires_at >= now);
+        pruned.insert(LINKS, (before - entries.links.len()) as u64);
+
+        let before = entries.link_records.len();
+        entries
+            .link_records
+            .retain(|_, record| record.keep_until >= now);
+        pruned.insert(LINK_RECORDS, (before - entries.link_records.len()) as u64);
+
+        Ok(pruned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> SessionData {
+        SessionData {
+            provider: "github".to_string(),
+            username: "octocat".to_string(),
+            access_token: "access".to_string(),
+            refresh_token: None,
+            expiry: 0,
+            profile: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn sessions_expire() {
+        let store = MemoryTokenStore::default();
+        let key = session().key();
+
+        store.save_session(&session()).await.unwrap();
+        assert!(store.load_session(&key).await.unwrap().is_some());
+
+        let expired = now() + SESSION_TTL + 1;
+        assert!(live_session(&store.entries().unwrap(), &key, expired).is_none());
+
+        let pruned = store.sweep_expired(expired).await.unwrap();
+        assert_eq!(pruned[SESSIONS], 1);
+        assert!(store.load_session(&key).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn saving_a_session_extends_it() {
+        let store = MemoryTokenStore::default();
+        let key = session().key();
+
+        store.save_session(&session()).await.unwrap();
+        let (first_expiry, _) = store.entries().unwrap().sessions[&key].clone();
+
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        store.save_session(&session()).await.unwrap();
+
+        let pruned = store.sweep_expired(first_expiry + 1).await.unwrap();
+        assert_eq!(pruned[SESSIONS], 0);
+        assert!(store.load_session(&key).await.unwrap().is_some());
+    }
+}
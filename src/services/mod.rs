@@ -1 +1,28 @@
+pub mod download_link_service;
+#[cfg(test)]
+pub(crate) mod failing_token_store;
+pub mod filesystem_resume_store;
+pub mod local_resume_parser;
+pub mod memory_token_store;
+pub mod notification_service;
+pub mod postgres_resume_store;
+pub mod redis_repository;
+pub mod redis_sweep_service;
+pub mod resume_approval_service;
+pub mod resume_diff_service;
+pub mod resume_export_service;
+pub mod resume_history_service;
+pub mod resume_job_service;
+pub mod resume_override_service;
 pub mod resume_parsing_service;
+pub mod resume_render_service;
+pub mod resume_source_service;
+pub mod resume_staging_service;
+pub mod resume_store_service;
+pub mod resume_upload_service;
+pub mod resume_validation_service;
+pub mod resume_variant_service;
+pub mod resume_watch_service;
+pub mod session_store_service;
+pub mod signed_link_service;
+pub mod token_store_service;
@@ -0,0 +1,136 @@
+use chrono::DateTime;
+use lazy_static::lazy_static;
+use lettre::{
+    message::header, transport::smtp::authentication::Credentials, Message, SmtpTransport,
+    Transport,
+};
+
+use crate::{
+    server_functions::get_env_variable,
+    services::{resume_staging_service::UploadResponse, resume_variant_service::is_default},
+    PersonalInfo, SmtpInfo,
+};
+
+lazy_static! {
+    static ref RESUME_NOTIFICATIONS: bool = get_env_variable("RESUME_NOTIFICATIONS")
+        .map(|enabled| enabled == "true" || enabled == "1")
+        .unwrap_or(false);
+}
+
+pub fn format_timestamp(timestamp: u64) -> String {
+    DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+fn variant_route(variant: &str, action: &str) -> String {
+    if is_default(variant) {
+        format!("/internal/resume/{action}")
+    } else {
+        format!("/internal/resume/{variant}/{action}")
+    }
+}
+
+// Sent to PERSONAL_EMAIL through the same SMTP account as the contact form. Sending blocks, so
+// it runs on a blocking thread and failures are only logged
+fn send_notification(subject: String, body: String) {
+    if !*RESUME_NOTIFICATIONS {
+        return;
+    }
+
+    tokio::task::spawn_blocking(move || {
+        let personal_info = PersonalInfo::new();
+        let smtp_info = SmtpInfo::new();
+
+        let email = match Message::builder()
+            .from(
+                format!("ChrisBratti.com <{}>", smtp_info.email)
+                    .parse()
+                    .unwrap(),
+            )
+            .to(format!("Chris Bratti <{}>", personal_info.email)
+                .parse()
+                .unwrap())
+            .subject(subject)
+            .header(header::ContentType::TEXT_PLAIN)
+            .body(body)
+        {
+            Ok(email) => email,
+            Err(err) => {
+                println!("Error building resume notification email: {err}");
+                return;
+            }
+        };
+
+        let creds = Credentials::new(smtp_info.email, smtp_info.key);
+
+        let mailer = SmtpTransport::relay("smtp.gmail.com")
+            .unwrap()
+            .credentials(creds)
+            .build();
+
+        if let Err(err) = mailer.send(&email) {
+            println!("Error sending resume notification email: {err}");
+        }
+    });
+}
+
+// `approves_at` is set when the resume will go live on its own after the review window
+pub fn notify_resume_staged(response: &UploadResponse, approves_at: Option<u64>) {
+    let variant = &response.variant;
+
+    let mut body = format!(
+        "Revision {} of the {variant} resume is pending review (parsed with {}).\n\n",
+        response.revision, response.parser
+    );
+
+    if !response.validation.is_valid() {
+        body.push_str(&format!(
+            "It has {} validation error(s) and can only be approved with ?force=true:\n",
+            response.validation.errors.len()
+        ));
+        for issue in &response.validation.errors {
+            body.push_str(&format!("- {}: {}\n", issue.field, issue.message));
+        }
+    } else if let Some(approves_at) = approves_at {
+        body.push_str(&format!(
+            "It will go live automatically at {}. POST {} to stop it.\n",
+            format_timestamp(approves_at),
+            variant_route(variant, "reject")
+        ));
+    } else {
+        body.push_str(&format!(
+            "POST {} to make it live.\n",
+            variant_route(variant, "approve")
+        ));
+    }
+
+    body.push_str(&format!(
+        "\nSee what changed at GET {}\n",
+        variant_route(variant, "diff")
+    ));
+
+    send_notification(
+        format!(
+            "New {variant} resume pending review (revision {})",
+            response.revision
+        ),
+        body,
+    );
+}
+
+pub fn notify_resume_live(variant: &str, revision: Option<u64>, automatic: bool) {
+    let revision = revision
+        .map(|revision| format!("revision {revision}"))
+        .unwrap_or_else(|| "the pending revision".to_string());
+    let how = if automatic {
+        "automatically after its review window"
+    } else {
+        "by an explicit approval"
+    };
+
+    send_notification(
+        format!("The {variant} resume is now live"),
+        format!("The {variant} resume ({revision}) was made live {how}.\n"),
+    );
+}
@@ -0,0 +1,613 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    PgConnection, PgPool,
+};
+
+use crate::{
+    services::{
+        filesystem_resume_store::FilesystemResumeStore,
+        resume_history_service::{NewRevision, ResumeHistory, ResumeRevision},
+        resume_store_service::{ResumeStore, StoreError},
+        resume_variant_service::{check_variant_name, is_default, DEFAULT_VARIANT},
+    },
+    ApplicantInfo, Education, Experience, Resume, Skills,
+};
+
+// Migrations in /migrations are embedded into the binary and run on startup
+static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!("./migrations");
+
+type ResumeRow = (
+    String,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+type ExperienceRow = (
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<Vec<String>>,
+);
+
+type EducationRow = (Option<String>, Option<String>, Option<String>);
+
+type RevisionRow = (i64, i64, String, bool, Option<String>);
+
+fn to_revision((rev, uploaded_at, sha256, has_pdf, note): RevisionRow) -> ResumeRevision {
+    ResumeRevision {
+        rev: rev as u64,
+        uploaded_at: uploaded_at as u64,
+        sha256,
+        has_pdf,
+        note,
+    }
+}
+
+pub struct PostgresResumeStore {
+    pool: PgPool,
+}
+
+impl PostgresResumeStore {
+    // Connections are only opened once the store is first used
+    pub fn new(database_url: &str) -> Self {
+        Self::with_options(database_url.parse().expect("Invalid DATABASE_URL!"))
+    }
+
+    pub fn with_options(options: PgConnectOptions) -> Self {
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .connect_lazy_with(options);
+
+        PostgresResumeStore { pool }
+    }
+
+    pub(crate) async fn migrate(&self) -> Result<(), StoreError> {
+        MIGRATOR.run(&self.pool).await?;
+
+        Ok(())
+    }
+
+    async fn load_by_id(&self, id: i64) -> Result<Resume, StoreError> {
+        let (overview, name, phone, email, github, linkedin, website): ResumeRow = sqlx::query_as(
+            "SELECT overview, name, phone, email, github, linkedin, website
+            FROM resumes WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let experience: Vec<ExperienceRow> = sqlx::query_as(
+            "SELECT company, title, duration, location, description
+            FROM resume_experiences WHERE resume_id = $1 ORDER BY position",
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let education: Vec<EducationRow> = sqlx::query_as(
+            "SELECT college, degree, major
+            FROM resume_education WHERE resume_id = $1 ORDER BY position",
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut skills: HashMap<String, Vec<String>> =
+            sqlx::query_as("SELECT category, skills FROM resume_skills WHERE resume_id = $1")
+                .bind(id)
+                .fetch_all(&self.pool)
+                .await?
+                .into_iter()
+                .collect();
+
+        Ok(Resume {
+            info: ApplicantInfo {
+                name,
+                phone,
+                email,
+                github,
+                linkedin,
+                website,
+            },
+            skills: Skills {
+                languages: skills.remove("languages"),
+                frameworks: skills.remove("frameworks"),
+                devops: skills.remove("devops"),
+                database: skills.remove("database"),
+                dev_tools: skills.remove("dev_tools"),
+            },
+            overview,
+            experience: experience
+                .into_iter()
+                .map(|(company, title, duration, location, desc)| Experience {
+                    company,
+                    title,
+                    duration,
+                    location,
+                    desc,
+                })
+                .collect(),
+            education: education
+                .into_iter()
+                .map(|(college, degree, major)| Education {
+                    college,
+                    degree,
+                    major,
+                })
+                .collect(),
+        })
+    }
+
+    // Live or pending resume id of a variant
+    async fn state_id(&self, variant: &str, pending: bool) -> Result<Option<i64>, StoreError> {
+        let query = if pending {
+            "SELECT pending_resume_id FROM resume_state WHERE variant = $1"
+        } else {
+            "SELECT live_resume_id FROM resume_state WHERE variant = $1"
+        };
+
+        let id: Option<Option<i64>> = sqlx::query_scalar(query)
+            .bind(variant)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(id.flatten())
+    }
+
+    // Stores the resume and points the variant's live or pending state at it, in one transaction
+    async fn save(&self, variant: &str, resume: &Resume, pending: bool) -> Result<(), StoreError> {
+        let mut tx = self.pool.begin().await?;
+
+        let id = insert_resume(&mut tx, variant, resume).await?;
+
+        let query = if pending {
+            "INSERT INTO resume_state (variant, pending_resume_id) VALUES ($1, $2)
+            ON CONFLICT (variant)
+            DO UPDATE SET pending_resume_id = EXCLUDED.pending_resume_id, updated_at = now()"
+        } else {
+            "INSERT INTO resume_state (variant, live_resume_id) VALUES ($1, $2)
+            ON CONFLICT (variant)
+            DO UPDATE SET live_resume_id = EXCLUDED.live_resume_id, updated_at = now()"
+        };
+
+        sqlx::query(query)
+            .bind(variant)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // Copies the resumes and revision history of a deployment that used the filesystem store. A
+    // variant is only imported while the database knows nothing about it, so this runs once per
+    // variant and never overwrites anything stored since
+    pub(crate) async fn import_from(
+        &self,
+        source: &FilesystemResumeStore,
+    ) -> Result<(), StoreError> {
+        for variant in source.known_variants().await? {
+            let history = source.load_history(&variant).await?;
+            let live = source.has_live(&variant);
+            let pending = source.has_pending(&variant);
+
+            if !live && !pending && history.revisions.is_empty() {
+                continue;
+            }
+
+            let mut tx = self.pool.begin().await?;
+
+            // The same lock as uploads, so instances starting at the same time import the
+            // variant only once. There's no row to lock before the first import
+            sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+                .bind(&variant)
+                .execute(&mut *tx)
+                .await?;
+
+            let imported: Option<String> =
+                sqlx::query_scalar("SELECT variant FROM resume_state WHERE variant = $1")
+                    .bind(&variant)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+
+            if imported.is_some() {
+                continue;
+            }
+
+            for revision in &history.revisions {
+                let resume = source
+                    .load_revision(&variant, revision.rev)
+                    .await?
+                    .ok_or_else(|| format!("Revision {} has no resume", revision.rev))?;
+                let pdf = source.load_revision_pdf(&variant, revision.rev).await?;
+
+                let resume_id = insert_resume(&mut tx, &variant, &resume).await?;
+
+                sqlx::query(
+                    "INSERT INTO resume_revisions
+                    (variant, rev, resume_id, uploaded_at, sha256, pdf, note)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                )
+                .bind(&variant)
+                .bind(revision.rev as i64)
+                .bind(resume_id)
+                .bind(revision.uploaded_at as i64)
+                .bind(&revision.sha256)
+                .bind(pdf)
+                .bind(&revision.note)
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            let live_id = match live {
+                true => Some(
+                    insert_resume(&mut tx, &variant, &source.load_live(&variant).await?).await?,
+                ),
+                false => None,
+            };
+            let pending_id = match pending {
+                true => Some(
+                    insert_resume(&mut tx, &variant, &source.load_pending(&variant).await?).await?,
+                ),
+                false => None,
+            };
+
+            sqlx::query(
+                "INSERT INTO resume_state
+                (variant, live_resume_id, pending_resume_id, live_rev, pending_rev)
+                VALUES ($1, $2, $3, $4, $5)",
+            )
+            .bind(&variant)
+            .bind(live_id)
+            .bind(pending_id)
+            .bind(history.live.map(|rev| rev as i64))
+            .bind(history.pending.map(|rev| rev as i64))
+            .execute(&mut *tx)
+            .await?;
+
+            tx.commit().await?;
+
+            println!(
+                "Imported the {variant} resume and {} revisions from the filesystem store",
+                history.revisions.len()
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn revision_resume_id(&self, variant: &str, rev: u64) -> Result<Option<i64>, StoreError> {
+        Ok(sqlx::query_scalar(
+            "SELECT resume_id FROM resume_revisions WHERE variant = $1 AND rev = $2",
+        )
+        .bind(variant)
+        .bind(rev as i64)
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+}
+
+async fn insert_resume(
+    con: &mut PgConnection,
+    variant: &str,
+    resume: &Resume,
+) -> Result<i64, StoreError> {
+    let info = &resume.info;
+
+    let id: i64 = sqlx::query_scalar(
+        "INSERT INTO resumes (variant, overview, name, phone, email, github, linkedin, website)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
+    )
+    .bind(variant)
+    .bind(&resume.overview)
+    .bind(&info.name)
+    .bind(&info.phone)
+    .bind(&info.email)
+    .bind(&info.github)
+    .bind(&info.linkedin)
+    .bind(&info.website)
+    .fetch_one(&mut *con)
+    .await?;
+
+    for (position, experience) in resume.experience.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO resume_experiences
+            (resume_id, position, company, title, duration, location, description)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(id)
+        .bind(position as i32)
+        .bind(&experience.company)
+        .bind(&experience.title)
+        .bind(&experience.duration)
+        .bind(&experience.location)
+        .bind(&experience.desc)
+        .execute(&mut *con)
+        .await?;
+    }
+
+    for (position, education) in resume.education.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO resume_education (resume_id, position, college, degree, major)
+            VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(id)
+        .bind(position as i32)
+        .bind(&education.college)
+        .bind(&education.degree)
+        .bind(&education.major)
+        .execute(&mut *con)
+        .await?;
+    }
+
+    let skills = &resume.skills;
+    let categories = [
+        ("languages", &skills.languages),
+        ("frameworks", &skills.frameworks),
+        ("devops", &skills.devops),
+        ("database", &skills.database),
+        ("dev_tools", &skills.dev_tools),
+    ];
+
+    for (category, skills) in categories {
+        let Some(skills) = skills else {
+            continue;
+        };
+
+        sqlx::query("INSERT INTO resume_skills (resume_id, category, skills) VALUES ($1, $2, $3)")
+            .bind(id)
+            .bind(category)
+            .bind(skills)
+            .execute(&mut *con)
+            .await?;
+    }
+
+    Ok(id)
+}
+
+#[async_trait]
+impl ResumeStore for PostgresResumeStore {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    async fn init(&self) -> Result<(), StoreError> {
+        self.migrate().await?;
+
+        self.import_from(&FilesystemResumeStore::default()).await
+    }
+
+    async fn live_variants(&self) -> Result<Vec<String>, StoreError> {
+        let stored: Vec<String> = sqlx::query_scalar(
+            "SELECT variant FROM resume_state WHERE live_resume_id IS NOT NULL ORDER BY variant",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut variants = vec![DEFAULT_VARIANT.to_string()];
+
+        variants.extend(
+            stored
+                .into_iter()
+                .filter(|variant| check_variant_name(variant).is_ok() && !is_default(variant)),
+        );
+
+        Ok(variants)
+    }
+
+    async fn known_variants(&self) -> Result<Vec<String>, StoreError> {
+        let stored: Vec<String> =
+            sqlx::query_scalar("SELECT variant FROM resume_state ORDER BY variant")
+                .fetch_all(&self.pool)
+                .await?;
+
+        let mut variants = vec![DEFAULT_VARIANT.to_string()];
+
+        variants.extend(
+            stored
+                .into_iter()
+                .filter(|variant| check_variant_name(variant).is_ok() && !is_default(variant)),
+        );
+
+        Ok(variants)
+    }
+
+    async fn load_live(&self, variant: &str) -> Result<Resume, StoreError> {
+        let id = self
+            .state_id(variant, false)
+            .await?
+            .ok_or_else(|| format!("No live {variant} resume"))?;
+
+        self.load_by_id(id).await
+    }
+
+    async fn load_pending(&self, variant: &str) -> Result<Resume, StoreError> {
+        let id = self
+            .state_id(variant, true)
+            .await?
+            .ok_or_else(|| format!("No pending {variant} resume"))?;
+
+        self.load_by_id(id).await
+    }
+
+    async fn save_pending(&self, variant: &str, resume: &Resume) -> Result<(), StoreError> {
+        self.save(variant, resume, true).await
+    }
+
+    async fn save_live(&self, variant: &str, resume: &Resume) -> Result<(), StoreError> {
+        self.save(variant, resume, false).await
+    }
+
+    async fn promote_pending(&self, variant: &str) -> Result<(), StoreError> {
+        let result = sqlx::query(
+            "UPDATE resume_state SET live_resume_id = pending_resume_id, updated_at = now()
+            WHERE variant = $1 AND pending_resume_id IS NOT NULL",
+        )
+        .bind(variant)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(format!("No pending {variant} resume").into());
+        }
+
+        Ok(())
+    }
+
+    async fn discard_pending(&self, variant: &str) -> Result<(), StoreError> {
+        sqlx::query(
+            "UPDATE resume_state SET pending_resume_id = NULL, updated_at = now()
+            WHERE variant = $1",
+        )
+        .bind(variant)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn load_history(&self, variant: &str) -> Result<ResumeHistory, StoreError> {
+        let state: Option<(Option<i64>, Option<i64>)> =
+            sqlx::query_as("SELECT live_rev, pending_rev FROM resume_state WHERE variant = $1")
+                .bind(variant)
+                .fetch_optional(&self.pool)
+                .await?;
+        let (live, pending) = state.unwrap_or_default();
+
+        let revisions: Vec<RevisionRow> = sqlx::query_as(
+            "SELECT rev, uploaded_at, sha256, pdf IS NOT NULL, note
+            FROM resume_revisions WHERE variant = $1 ORDER BY rev",
+        )
+        .bind(variant)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(ResumeHistory {
+            live: live.map(|rev| rev as u64),
+            pending: pending.map(|rev| rev as u64),
+            revisions: revisions.into_iter().map(to_revision).collect(),
+        })
+    }
+
+    async fn add_revision(
+        &self,
+        variant: &str,
+        revision: NewRevision<'_>,
+    ) -> Result<ResumeRevision, StoreError> {
+        let mut tx = self.pool.begin().await?;
+
+        // Serializes uploads to the same variant so two of them can't take the same revision id
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+            .bind(variant)
+            .execute(&mut *tx)
+            .await?;
+
+        let resume_id = insert_resume(&mut tx, variant, revision.resume).await?;
+
+        let rev: i64 = sqlx::query_scalar(
+            "SELECT COALESCE(MAX(rev), 0) + 1 FROM resume_revisions WHERE variant = $1",
+        )
+        .bind(variant)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO resume_revisions (variant, rev, resume_id, uploaded_at, sha256, pdf, note)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(variant)
+        .bind(rev)
+        .bind(resume_id)
+        .bind(revision.uploaded_at as i64)
+        .bind(&revision.sha256)
+        .bind(revision.pdf_bytes)
+        .bind(revision.note)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO resume_state (variant, pending_rev) VALUES ($1, $2)
+            ON CONFLICT (variant)
+            DO UPDATE SET pending_rev = EXCLUDED.pending_rev, updated_at = now()",
+        )
+        .bind(variant)
+        .bind(rev)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(ResumeRevision {
+            rev: rev as u64,
+            uploaded_at: revision.uploaded_at,
+            sha256: revision.sha256,
+            has_pdf: revision.pdf_bytes.is_some(),
+            note: revision.note.map(str::to_string),
+        })
+    }
+
+    async fn load_revision(&self, variant: &str, rev: u64) -> Result<Option<Resume>, StoreError> {
+        match self.revision_resume_id(variant, rev).await? {
+            Some(id) => Ok(Some(self.load_by_id(id).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn load_revision_pdf(
+        &self,
+        variant: &str,
+        rev: u64,
+    ) -> Result<Option<Vec<u8>>, StoreError> {
+        let pdf: Option<Option<Vec<u8>>> =
+            sqlx::query_scalar("SELECT pdf FROM resume_revisions WHERE variant = $1 AND rev = $2")
+                .bind(variant)
+                .bind(rev as i64)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(pdf.flatten())
+    }
+
+    async fn set_live_revision(&self, variant: &str, rev: u64) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO resume_state (variant, live_rev) VALUES ($1, $2)
+            ON CONFLICT (variant)
+            DO UPDATE SET live_rev = EXCLUDED.live_rev,
+                pending_rev = CASE WHEN resume_state.pending_rev = EXCLUDED.live_rev
+                    THEN NULL ELSE resume_state.pending_rev END,
+                updated_at = now()",
+        )
+        .bind(variant)
+        .bind(rev as i64)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn clear_pending_revision(&self, variant: &str) -> Result<Option<u64>, StoreError> {
+        let pending: Option<Option<i64>> = sqlx::query_scalar(
+            "WITH previous AS (
+                SELECT variant, pending_rev FROM resume_state WHERE variant = $1 FOR UPDATE
+            )
+            UPDATE resume_state SET pending_rev = NULL, updated_at = now()
+            FROM previous WHERE resume_state.variant = previous.variant
+            RETURNING previous.pending_rev",
+        )
+        .bind(variant)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(pending.flatten().map(|rev| rev as u64))
+    }
+}
@@ -0,0 +1,296 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use redis::{
+    aio::{ConnectionManager, ConnectionManagerConfig},
+    AsyncCommands, Client, RedisError, Script,
+};
+
+use crate::{
+    oauth::SessionData,
+    server_functions::get_env_variable,
+    services::{
+        download_link_service::{DownloadLink, DownloadRecord, LinkClaim},
+        token_store_service::{
+            LoginState, TokenStore, TokenStoreError, LINKS, LINK_RECORDS, SESSION_TTL, STATES,
+        },
+    },
+};
+
+lazy_static! {
+    // Redis calls that take longer than this fail instead of holding up the request
+    static ref REDIS_RESPONSE_TIMEOUT: u64 = get_env_variable("REDIS_RESPONSE_TIMEOUT_MS")
+        .and_then(|timeout| timeout.parse().ok())
+        .unwrap_or(2000);
+    static ref CLAIM_DOWNLOAD: Script = Script::new(
+        r"
+        local expiry = redis.call('ZSCORE', KEYS[1], ARGV[1])
+        if not expiry then
+            if redis.call('EXISTS', KEYS[2]) == 1 then
+                return -3
+            end
+            return -1
+        end
+        -- Expired by the same rule as is_live
+        if tonumber(expiry) < tonumber(ARGV[2]) then
+            return -3
+        end
+        if redis.call('EXISTS', KEYS[2]) == 0 then
+            return 0
+        end
+        local max_downloads = redis.call('HGET', KEYS[2], 'max_downloads')
+        local downloads = tonumber(redis.call('HGET', KEYS[2], 'downloads') or '0')
+        if max_downloads and downloads >= tonumber(max_downloads) then
+            return -2
+        end
+        return redis.call('HINCRBY', KEYS[2], 'downloads', 1)
+        ",
+    );
+}
+
+// Every sorted set scored by expiry time
+const EXPIRING_SETS: [&str; 3] = [LINKS, LINK_RECORDS, STATES];
+
+// Entries expiring this very second are still valid. Lookups, listings and the sweeper all split the
+// expiring sets here, so nothing is listed that a lookup would reject or swept while still valid
+fn is_live(expires_at: u64, now: u64) -> bool {
+    expires_at >= now
+}
+
+// Score range of the entries that are still valid
+fn live_scores(now: u64) -> (u64, &'static str) {
+    (now, "+inf")
+}
+
+// Score range of the entries that have expired, the upper bound is exclusive
+fn expired_scores(now: u64) -> (&'static str, String) {
+    ("-inf", format!("({now}"))
+}
+
+// Details of a login, expiring together with its entry in the states set
+fn state_key(state: &str) -> String {
+    format!("oauth_state:{state}")
+}
+
+fn link_key(token: &str) -> String {
+    format!("pdf_link:{token}")
+}
+
+fn log_key(token: &str) -> String {
+    format!("pdf_link:{token}:downloads")
+}
+
+// One multiplexed async connection shared by every handler and background task. Clones share the
+// same connection, which reconnects on its own if redis goes away
+#[derive(Clone)]
+pub struct RedisRepository {
+    manager: ConnectionManager,
+}
+
+impl RedisRepository {
+    pub async fn connect(connection_string: &str) -> Result<Self, RedisError> {
+        let client = Client::open(connection_string)?;
+
+        let config = ConnectionManagerConfig::new()
+            .set_connection_timeout(Duration::from_millis(*REDIS_RESPONSE_TIMEOUT))
+            .set_response_timeout(Duration::from_millis(*REDIS_RESPONSE_TIMEOUT))
+            .set_number_of_retries(2);
+
+        Ok(RedisRepository {
+            manager: ConnectionManager::new_with_config(client, config).await?,
+        })
+    }
+
+    // For services that manage their own keys, like resume jobs
+    pub fn connection(&self) -> ConnectionManager {
+        self.manager.clone()
+    }
+}
+
+#[async_trait]
+impl TokenStore for RedisRepository {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn add_state(
+        &self,
+        state: &str,
+        login_state: &LoginState,
+        expires_at: u64,
+    ) -> Result<(), TokenStoreError> {
+        let key = state_key(state);
+
+        () = redis::pipe()
+            .atomic()
+            .set(&key, serde_json::to_string(login_state)?)
+            .expire_at(&key, expires_at as i64)
+            .zadd(STATES, state, expires_at)
+            .query_async(&mut self.connection())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn take_state(
+        &self,
+        state: &str,
+        now: u64,
+    ) -> Result<Option<LoginState>, TokenStoreError> {
+        let key = state_key(state);
+
+        let (expires_at, _, login_state, _): (Option<u64>, i64, Option<String>, i64) =
+            redis::pipe()
+                .atomic()
+                .zscore(STATES, state)
+                .zrem(STATES, state)
+                .get(&key)
+                .del(&key)
+                .query_async(&mut self.connection())
+                .await?;
+
+        if !expires_at.is_some_and(|expires_at| is_live(expires_at, now)) {
+            return Ok(None);
+        }
+
+        match login_state {
+            Some(login_state) => Ok(Some(serde_json::from_str(&login_state)?)),
+            None => Ok(Some(LoginState::default())),
+        }
+    }
+
+    async fn save_session(&self, session_data: &SessionData) -> Result<(), TokenStoreError> {
+        let session_string = serde_json::to_string(session_data)?;
+
+        () = self
+            .connection()
+            .set_ex(session_data.key(), session_string, SESSION_TTL)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn load_session(&self, key: &str) -> Result<Option<SessionData>, TokenStoreError> {
+        let session_string: Option<String> = self.connection().get(key).await?;
+
+        match session_string {
+            Some(session_string) => Ok(Some(serde_json::from_str(&session_string)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn delete_session(&self, key: &str) -> Result<(), TokenStoreError> {
+        () = self.connection().del(key).await?;
+
+        Ok(())
+    }
+
+    async fn save_link(&self, link: &DownloadLink, keep_until: u64) -> Result<(), TokenStoreError> {
+        let key = link_key(&link.token);
+
+        let mut fields = vec![
+            ("link", serde_json::to_string(link)?),
+            ("downloads", "0".to_string()),
+        ];
+        if let Some(max_downloads) = link.max_downloads {
+            fields.push(("max_downloads", max_downloads.to_string()));
+        }
+
+        () = redis::pipe()
+            .atomic()
+            .hset_multiple(&key, &fields)
+            .expire_at(&key, keep_until as i64)
+            .zadd(LINK_RECORDS, &link.token, keep_until)
+            .zadd(LINKS, &link.token, link.expires_at)
+            .query_async(&mut self.connection())
+            .await?;
+
+        Ok(())
+    }
+
+    // Links minted before link details were stored don't have any
+    async fn load_link(&self, token: &str) -> Result<Option<DownloadLink>, TokenStoreError> {
+        let link: Option<String> = self.connection().hget(link_key(token), "link").await?;
+
+        match link {
+            Some(link) => Ok(Some(serde_json::from_str::<DownloadLink>(&link)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn claim_download(&self, token: &str, now: u64) -> Result<LinkClaim, TokenStoreError> {
+        let claimed: i64 = CLAIM_DOWNLOAD
+            .key(LINKS)
+            .key(link_key(token))
+            .arg(token)
+            .arg(now)
+            .invoke_async(&mut self.connection())
+            .await?;
+
+        match claimed {
+            -1 => Ok(LinkClaim::Unknown),
+            -2 => Ok(LinkClaim::Exhausted),
+            -3 => Ok(LinkClaim::Expired),
+            _ => Ok(LinkClaim::Allowed),
+        }
+    }
+
+    async fn record_download(
+        &self,
+        token: &str,
+        record: &DownloadRecord,
+        keep_until: u64,
+    ) -> Result<(), TokenStoreError> {
+        let mut con = self.connection();
+        let key = log_key(token);
+
+        () = con.rpush(&key, serde_json::to_string(record)?).await?;
+        () = con.expire_at(&key, keep_until as i64).await?;
+
+        Ok(())
+    }
+
+    async fn link_downloads(
+        &self,
+        token: &str,
+    ) -> Result<(u32, Vec<DownloadRecord>), TokenStoreError> {
+        let mut con = self.connection();
+
+        let downloads: Option<u32> = con.hget(link_key(token), "downloads").await?;
+        let log: Vec<String> = con.lrange(log_key(token), 0, -1).await?;
+
+        Ok((
+            downloads.unwrap_or(0),
+            log.iter()
+                .filter_map(|record| serde_json::from_str(record).ok())
+                .collect(),
+        ))
+    }
+
+    // Read only, expired records are left for the sweeper
+    async fn link_tokens(&self, now: u64) -> Result<Vec<String>, TokenStoreError> {
+        let (min, max) = live_scores(now);
+
+        Ok(self
+            .connection()
+            .zrangebyscore(LINK_RECORDS, min, max)
+            .await?)
+    }
+
+    async fn sweep_expired(
+        &self,
+        now: u64,
+    ) -> Result<BTreeMap<&'static str, u64>, TokenStoreError> {
+        let mut con = self.connection();
+        let mut pruned = BTreeMap::new();
+
+        for key in EXPIRING_SETS {
+            let (min, max) = expired_scores(now);
+            let removed: u64 = con.zrembyscore(key, min, max).await?;
+            pruned.insert(key, removed);
+        }
+
+        Ok(pruned)
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+
+use crate::{server_functions::get_env_variable, services::token_store_service::TokenStore};
+
+lazy_static! {
+    // 0 turns the background sweeper off, sweeps can still be triggered manually
+    static ref REDIS_SWEEP_INTERVAL: u64 = get_env_variable("REDIS_SWEEP_INTERVAL_SECS")
+        .and_then(|interval| interval.parse().ok())
+        .unwrap_or(600);
+    static ref SWEEP_METRICS: Mutex<SweepMetrics> = Mutex::new(SweepMetrics::default());
+}
+
+type SweepError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Serialize, Clone)]
+pub struct SweepReport {
+    pub swept_at: u64,
+    // Entries removed from each expiring collection
+    pub pruned: BTreeMap<&'static str, u64>,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct SweepMetrics {
+    pub sweeps: u64,
+    pub failed_sweeps: u64,
+    pub last_sweep: Option<SweepReport>,
+    pub total_pruned: BTreeMap<&'static str, u64>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+// Removes every entry that expired before now
+pub async fn sweep_expired(store: &dyn TokenStore) -> Result<SweepReport, SweepError> {
+    let swept_at = now();
+    let pruned = store.sweep_expired(swept_at).await?;
+
+    let report = SweepReport { swept_at, pruned };
+
+    let mut metrics = SWEEP_METRICS
+        .lock()
+        .map_err(|_| "Sweep metrics lock poisoned")?;
+
+    metrics.sweeps += 1;
+    for (key, removed) in &report.pruned {
+        *metrics.total_pruned.entry(key).or_insert(0) += removed;
+    }
+    metrics.last_sweep = Some(report.clone());
+
+    Ok(report)
+}
+
+fn record_failed_sweep() {
+    if let Ok(mut metrics) = SWEEP_METRICS.lock() {
+        metrics.failed_sweeps += 1;
+    }
+}
+
+pub fn sweep_metrics() -> Result<SweepMetrics, SweepError> {
+    Ok(SWEEP_METRICS
+        .lock()
+        .map_err(|_| "Sweep metrics lock poisoned")?
+        .clone())
+}
+
+pub async fn run_sweep(store: &dyn TokenStore) -> Result<SweepReport, SweepError> {
+    let result = sweep_expired(store).await;
+
+    match &result {
+        Ok(report) => {
+            let total: u64 = report.pruned.values().sum();
+            if total > 0 {
+                println!(
+                    "Pruned {total} expired token store entries: {:?}",
+                    report.pruned
+                );
+            }
+        }
+        Err(err) => {
+            record_failed_sweep();
+            println!("Error sweeping expired token store entries: {err}");
+        }
+    }
+
+    result
+}
+
+pub async fn run_redis_sweeper(store: Arc<dyn TokenStore>) {
+    if *REDIS_SWEEP_INTERVAL == 0 {
+        return;
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs(*REDIS_SWEEP_INTERVAL));
+
+    loop {
+        interval.tick().await;
+
+        // Errors are logged and counted, the next tick tries again
+        let _ = run_sweep(store.as_ref()).await;
+    }
+}
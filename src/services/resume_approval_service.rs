@@ -0,0 +1,230 @@
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+
+use crate::{
+    server_functions::get_env_variable,
+    services::{
+        notification_service::notify_resume_live,
+        resume_history_service::{clear_pending, load_history, ROLLBACK_LOCK},
+        resume_parsing_service::{load_resume, replace_current_pdf},
+        resume_staging_service::validate_with_overrides,
+        resume_store_service::resume_store,
+        resume_validation_service::ValidationReport,
+    },
+    ResumeCache,
+};
+
+lazy_static! {
+    // Pending resumes that pass validation go live on their own once the review window is over
+    pub static ref RESUME_AUTO_APPROVE: bool = get_env_variable("RESUME_AUTO_APPROVE")
+        .map(|enabled| enabled == "true" || enabled == "1")
+        .unwrap_or(false);
+    static ref RESUME_REVIEW_WINDOW: u64 = get_env_variable("RESUME_REVIEW_WINDOW_SECS")
+        .and_then(|window| window.parse().ok())
+        .unwrap_or(60 * 60 * 24);
+}
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+type ApprovalError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Serialize)]
+pub struct PendingStatus {
+    pub variant: String,
+    pub revision: u64,
+    pub staged_at: u64,
+    pub note: Option<String>,
+    pub validation: ValidationReport,
+    pub auto_approve: bool,
+    // Only set when the resume will go live on its own, i.e. auto approval is on and it is valid
+    pub approves_at: Option<u64>,
+    pub seconds_remaining: Option<u64>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+// When a resume staged at `staged_at` will be approved automatically, if ever
+pub fn auto_approve_time(staged_at: u64, validation: &ValidationReport) -> Option<u64> {
+    (*RESUME_AUTO_APPROVE && validation.is_valid()).then(|| staged_at + *RESUME_REVIEW_WINDOW)
+}
+
+pub async fn pending_status(variant: &str) -> Result<Option<PendingStatus>, ApprovalError> {
+    let history = load_history(variant).await?;
+
+    let Some(revision) = history
+        .pending
+        .and_then(|pending| history.revisions.into_iter().find(|rev| rev.rev == pending))
+    else {
+        return Ok(None);
+    };
+
+    // The revision's own copy, the pending JSON may already belong to a newer upload
+    let pending = resume_store()
+        .load_revision(variant, revision.rev)
+        .await?
+        .ok_or("Error loading pending resume")?;
+    let validation = validate_with_overrides(variant, &pending)?;
+    let approves_at = auto_approve_time(revision.uploaded_at, &validation);
+
+    Ok(Some(PendingStatus {
+        variant: variant.to_string(),
+        revision: revision.rev,
+        staged_at: revision.uploaded_at,
+        note: revision.note,
+        validation,
+        auto_approve: *RESUME_AUTO_APPROVE,
+        approves_at,
+        seconds_remaining: approves_at.map(|approves_at| approves_at.saturating_sub(now())),
+    }))
+}
+
+// Makes pending revision `rev` live, serves its PDF and reloads it (with overrides applied) into
+// the cache. Pending resumes without a PDF, like hand-written ones, keep the current PDF. Returns
+// false without changing anything when `rev` isn't pending anymore, i.e. it was rejected or a
+// newer upload was staged after it was checked
+pub async fn promote_pending_resume(
+    variant: &str,
+    rev: u64,
+    resume_cache: &ResumeCache,
+    automatic: bool,
+) -> Result<bool, ApprovalError> {
+    // Approvals swap the live resume and served PDF just like rollbacks do
+    let _rollback_guard = ROLLBACK_LOCK.lock().await;
+
+    if load_history(variant).await?.pending != Some(rev) {
+        return Ok(false);
+    }
+
+    let parsed = resume_store()
+        .load_revision(variant, rev)
+        .await?
+        .ok_or("Error loading pending resume")?;
+    let pdf_bytes = resume_store()
+        .load_revision_pdf(variant, rev)
+        .await
+        .map_err(|_| "Error reading pending PDF")?;
+
+    resume_store()
+        .save_live(variant, &parsed)
+        .await
+        .map_err(|_| "Error updating resume JSON")?;
+
+    resume_store()
+        .set_live_revision(variant, rev)
+        .await
+        .map_err(|_| "Error updating resume history")?;
+
+    let resume = load_resume(variant)
+        .await
+        .map_err(|_| "Error loading live resume")?;
+
+    {
+        let mut write_to_cache = resume_cache
+            .variants
+            .write()
+            .map_err(|_| "Error establishing write lock on resume cache")?;
+
+        if let Some(pdf_bytes) = &pdf_bytes {
+            replace_current_pdf(variant, pdf_bytes).map_err(|_| "Error saving PDF file")?;
+        }
+
+        write_to_cache.insert(variant.to_string(), resume);
+    }
+
+    notify_resume_live(variant, Some(rev), automatic);
+
+    Ok(true)
+}
+
+// Drops the pending resume so it is never approved, automatically or otherwise. Its PDF was never
+// served, so there is nothing to restore. Returns the revision that was pending
+pub async fn reject_pending_resume(variant: &str) -> Result<Option<u64>, ApprovalError> {
+    // Not while the pending revision is being approved
+    let _rollback_guard = ROLLBACK_LOCK.lock().await;
+
+    let rejected = clear_pending(variant).await?;
+
+    resume_store().discard_pending(variant).await?;
+
+    if let Some(rev) = rejected {
+        println!("Rejected pending {variant} resume revision {rev}");
+    }
+
+    Ok(rejected)
+}
+
+// Approves every pending resume whose review window is over
+pub async fn run_auto_approval(resume_cache: Arc<ResumeCache>) {
+    if !*RESUME_AUTO_APPROVE {
+        return;
+    }
+
+    println!(
+        "Auto approving pending resumes after {}s",
+        *RESUME_REVIEW_WINDOW
+    );
+
+    // Invalid revisions are only reported once rather than on every check
+    let mut reported: HashSet<(String, u64)> = HashSet::new();
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let variants = match resume_store().known_variants().await {
+            Ok(variants) => variants,
+            Err(err) => {
+                println!("Error listing resume variants: {err}");
+                continue;
+            }
+        };
+
+        for variant in variants {
+            let status = match pending_status(&variant).await {
+                Ok(Some(status)) => status,
+                Ok(None) => continue,
+                Err(err) => {
+                    println!("Error checking pending {variant} resume: {err}");
+                    continue;
+                }
+            };
+
+            let Some(approves_at) = status.approves_at else {
+                if reported.insert((variant.clone(), status.revision)) {
+                    println!(
+                        "Pending {variant} resume revision {} has validation errors and won't be approved automatically",
+                        status.revision
+                    );
+                }
+                continue;
+            };
+
+            if approves_at > now() {
+                continue;
+            }
+
+            match promote_pending_resume(&variant, status.revision, &resume_cache, true).await {
+                Ok(true) => println!(
+                    "Automatically approved {variant} resume revision {}",
+                    status.revision
+                ),
+                Ok(false) => println!(
+                    "Pending {variant} resume revision {} changed before it could be approved",
+                    status.revision
+                ),
+                Err(err) => println!("Error automatically approving {variant} resume: {err}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use std::fmt::{self, Display, Write};
+
+use serde::Serialize;
+
+use crate::{ApplicantInfo, Education, Experience, Resume, Skills};
+
+#[derive(Serialize, Debug)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ExperienceChange {
+    pub company: Option<String>,
+    pub changes: Vec<FieldChange>,
+    pub added_desc: Vec<String>,
+    pub removed_desc: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SkillsChange {
+    pub category: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct ResumeDiff {
+    pub info: Vec<FieldChange>,
+    pub overview: Option<FieldChange>,
+    pub experience_added: Vec<Experience>,
+    pub experience_removed: Vec<Experience>,
+    pub experience_changed: Vec<ExperienceChange>,
+    pub skills: Vec<SkillsChange>,
+    pub education_added: Vec<Education>,
+    pub education_removed: Vec<Education>,
+}
+
+impl ResumeDiff {
+    pub fn is_empty(&self) -> bool {
+        self.info.is_empty()
+            && self.overview.is_none()
+            && self.experience_added.is_empty()
+            && self.experience_removed.is_empty()
+            && self.experience_changed.is_empty()
+            && self.skills.is_empty()
+            && self.education_added.is_empty()
+            && self.education_removed.is_empty()
+    }
+}
+
+fn field_change(field: &str, old: &Option<String>, new: &Option<String>) -> Option<FieldChange> {
+    if old == new {
+        return None;
+    }
+
+    Some(FieldChange {
+        field: field.to_string(),
+        old: old.clone(),
+        new: new.clone(),
+    })
+}
+
+// Items in `new` but not `old`, and items in `old` but not `new`
+fn list_changes(old: &[String], new: &[String]) -> (Vec<String>, Vec<String>) {
+    let added = new
+        .iter()
+        .filter(|item| !old.contains(item))
+        .cloned()
+        .collect();
+    let removed = old
+        .iter()
+        .filter(|item| !new.contains(item))
+        .cloned()
+        .collect();
+
+    (added, removed)
+}
+
+fn diff_info(old: &ApplicantInfo, new: &ApplicantInfo) -> Vec<FieldChange> {
+    [
+        field_change("name", &old.name, &new.name),
+        field_change("phone", &old.phone, &new.phone),
+        field_change("email", &old.email, &new.email),
+        field_change("github", &old.github, &new.github),
+        field_change("linkedin", &old.linkedin, &new.linkedin),
+        field_change("website", &old.website, &new.website),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+fn diff_skills(old: &Skills, new: &Skills) -> Vec<SkillsChange> {
+    [
+        ("languages", &old.languages, &new.languages),
+        ("frameworks", &old.frameworks, &new.frameworks),
+        ("devops", &old.devops, &new.devops),
+        ("database", &old.database, &new.database),
+        ("dev_tools", &old.dev_tools, &new.dev_tools),
+    ]
+    .into_iter()
+    .filter_map(|(category, old, new)| {
+        let (added, removed) = list_changes(
+            old.as_deref().unwrap_or_default(),
+            new.as_deref().unwrap_or_default(),
+        );
+
+        if added.is_empty() && removed.is_empty() {
+            return None;
+        }
+
+        Some(SkillsChange {
+            category: category.to_string(),
+            added,
+            removed,
+        })
+    })
+    .collect()
+}
+
+fn diff_experience_entry(old: &Experience, new: &Experience) -> Option<ExperienceChange> {
+    let changes: Vec<FieldChange> = [
+        field_change("company", &old.company, &new.company),
+        field_change("title", &old.title, &new.title),
+        field_change("duration", &old.duration, &new.duration),
+        field_change("location", &old.location, &new.location),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    let (added_desc, removed_desc) = list_changes(
+        old.desc.as_deref().unwrap_or_default(),
+        new.desc.as_deref().unwrap_or_default(),
+    );
+
+    if changes.is_empty() && added_desc.is_empty() && removed_desc.is_empty() {
+        return None;
+    }
+
+    Some(ExperienceChange {
+        company: new.company.clone().or(old.company.clone()),
+        changes,
+        added_desc,
+        removed_desc,
+    })
+}
+
+// Entries are paired by company and title first, then by company alone so a corrected title
+// shows up as a change rather than as one removed and one added entry
+fn diff_experience(old: &[Experience], new: &[Experience], diff: &mut ResumeDiff) {
+    let mut old_matched = vec![false; old.len()];
+    let mut pairs: Vec<(usize, Option<usize>)> = Vec::new();
+
+    for (new_index, new_entry) in new.iter().enumerate() {
+        let exact = old.iter().enumerate().position(|(old_index, old_entry)| {
+            !old_matched[old_index]
+                && old_entry.company == new_entry.company
+                && old_entry.title == new_entry.title
+        });
+
+        if let Some(old_index) = exact {
+            old_matched[old_index] = true;
+        }
+
+        pairs.push((new_index, exact));
+    }
+
+    for (new_index, old_index) in pairs.iter_mut() {
+        if old_index.is_some() || new[*new_index].company.is_none() {
+            continue;
+        }
+
+        *old_index = old.iter().enumerate().position(|(index, old_entry)| {
+            !old_matched[index] && old_entry.company == new[*new_index].company
+        });
+
+        if let Some(index) = old_index {
+            old_matched[*index] = true;
+        }
+    }
+
+    for (new_index, old_index) in pairs {
+        match old_index {
+            Some(old_index) => {
+                if let Some(change) = diff_experience_entry(&old[old_index], &new[new_index]) {
+                    diff.experience_changed.push(change);
+                }
+            }
+            None => diff.experience_added.push(new[new_index].clone()),
+        }
+    }
+
+    diff.experience_removed = old
+        .iter()
+        .zip(old_matched)
+        .filter(|(_, matched)| !matched)
+        .map(|(entry, _)| entry.clone())
+        .collect();
+}
+
+// Produces a field-level diff describing how to get from `old` to `new`
+pub fn diff_resumes(old: &Resume, new: &Resume) -> ResumeDiff {
+    let mut diff = ResumeDiff {
+        info: diff_info(&old.info, &new.info),
+        skills: diff_skills(&old.skills, &new.skills),
+        ..Default::default()
+    };
+
+    if old.overview != new.overview {
+        diff.overview = Some(FieldChange {
+            field: "overview".to_string(),
+            old: Some(old.overview.clone()),
+            new: Some(new.overview.clone()),
+        });
+    }
+
+    diff_experience(&old.experience, &new.experience, &mut diff);
+
+    diff.education_added = new
+        .education
+        .iter()
+        .filter(|entry| !old.education.contains(entry))
+        .cloned()
+        .collect();
+    diff.education_removed = old
+        .education
+        .iter()
+        .filter(|entry| !new.education.contains(entry))
+        .cloned()
+        .collect();
+
+    diff
+}
+
+fn display_value(value: &Option<String>) -> &str {
+    value.as_deref().unwrap_or("<none>")
+}
+
+fn describe_experience(entry: &Experience) -> String {
+    format!(
+        "{} - {} [{}]",
+        display_value(&entry.company),
+        display_value(&entry.title),
+        display_value(&entry.duration)
+    )
+}
+
+fn describe_education(entry: &Education) -> String {
+    format!(
+        "{} - {} {}",
+        display_value(&entry.college),
+        display_value(&entry.degree),
+        display_value(&entry.major)
+    )
+}
+
+fn write_field_change(f: &mut impl Write, indent: &str, change: &FieldChange) -> fmt::Result {
+    writeln!(
+        f,
+        "{indent}~ {}: \"{}\" -> \"{}\"",
+        change.field,
+        display_value(&change.old),
+        display_value(&change.new)
+    )
+}
+
+// Plain text report, one line per change
+impl Display for ResumeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes");
+        }
+
+        if !self.info.is_empty() {
+            writeln!(f, "Applicant info")?;
+            for change in &self.info {
+                write_field_change(f, "  ", change)?;
+            }
+        }
+
+        if let Some(change) = &self.overview {
+            writeln!(f, "Overview")?;
+            write_field_change(f, "  ", change)?;
+        }
+
+        if !self.experience_added.is_empty()
+            || !self.experience_removed.is_empty()
+            || !self.experience_changed.is_empty()
+        {
+            writeln!(f, "Experience")?;
+            for entry in &self.experience_added {
+                writeln!(f, "  + {}", describe_experience(entry))?;
+            }
+            for entry in &self.experience_removed {
+                writeln!(f, "  - {}", describe_experience(entry))?;
+            }
+            for change in &self.experience_changed {
+                writeln!(f, "  ~ {}", display_value(&change.company))?;
+                for field in &change.changes {
+                    write_field_change(f, "    ", field)?;
+                }
+                for item in &change.added_desc {
+                    writeln!(f, "    + {item}")?;
+                }
+                for item in &change.removed_desc {
+                    writeln!(f, "    - {item}")?;
+                }
+            }
+        }
+
+        if !self.skills.is_empty() {
+            writeln!(f, "Skills")?;
+            for change in &self.skills {
+                writeln!(f, "  {}", change.category)?;
+                for skill in &change.added {
+                    writeln!(f, "    + {skill}")?;
+                }
+                for skill in &change.removed {
+                    writeln!(f, "    - {skill}")?;
+                }
+            }
+        }
+
+        if !self.education_added.is_empty() || !self.education_removed.is_empty() {
+            writeln!(f, "Education")?;
+            for entry in &self.education_added {
+                writeln!(f, "  + {}", describe_education(entry))?;
+            }
+            for entry in &self.education_removed {
+                writeln!(f, "  - {}", describe_education(entry))?;
+            }
+        }
+
+        Ok(())
+    }
+}
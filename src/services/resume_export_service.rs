@@ -0,0 +1,384 @@
+use std::fmt::Write;
+
+use serde_json::{json, Map, Value};
+
+use crate::{Experience, Resume};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Pdf,
+    JsonResume,
+    Markdown,
+    Text,
+    VCard,
+}
+
+impl ExportFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "pdf" => Some(ExportFormat::Pdf),
+            "json" => Some(ExportFormat::JsonResume),
+            "md" => Some(ExportFormat::Markdown),
+            "txt" => Some(ExportFormat::Text),
+            "vcf" => Some(ExportFormat::VCard),
+            _ => None,
+        }
+    }
+
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        match mime {
+            "application/pdf" => Some(ExportFormat::Pdf),
+            "application/json" => Some(ExportFormat::JsonResume),
+            "text/markdown" => Some(ExportFormat::Markdown),
+            "text/plain" => Some(ExportFormat::Text),
+            "text/vcard" | "text/x-vcard" => Some(ExportFormat::VCard),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Pdf => "pdf",
+            ExportFormat::JsonResume => "json",
+            ExportFormat::Markdown => "md",
+            ExportFormat::Text => "txt",
+            ExportFormat::VCard => "vcf",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Pdf => "application/pdf",
+            ExportFormat::JsonResume => "application/json",
+            ExportFormat::Markdown => "text/markdown; charset=utf-8",
+            ExportFormat::Text => "text/plain; charset=utf-8",
+            ExportFormat::VCard => "text/vcard; charset=utf-8",
+        }
+    }
+}
+
+fn skill_categories(resume: &Resume) -> Vec<(&'static str, &Vec<String>)> {
+    let skills = &resume.skills;
+
+    [
+        ("Languages", &skills.languages),
+        ("Tools and Frameworks", &skills.frameworks),
+        ("Database", &skills.database),
+        ("Devops and Deployment", &skills.devops),
+        ("Development Tools", &skills.dev_tools),
+    ]
+    .into_iter()
+    .filter_map(|(label, values)| Some((label, values.as_ref().filter(|v| !v.is_empty())?)))
+    .collect()
+}
+
+fn contact_details(resume: &Resume) -> Vec<&str> {
+    let info = &resume.info;
+
+    [
+        &info.email,
+        &info.phone,
+        &info.github,
+        &info.linkedin,
+        &info.website,
+    ]
+    .into_iter()
+    .filter_map(|value| value.as_deref())
+    .collect()
+}
+
+fn role_line(entry: &Experience) -> String {
+    [&entry.title, &entry.duration, &entry.location]
+        .into_iter()
+        .filter_map(|value| value.as_deref())
+        .collect::<Vec<&str>>()
+        .join(" | ")
+}
+
+// Turns "Jan 2020" or "2020" into the ISO 8601 dates used by JSON Resume
+fn iso_date(date: &str) -> Option<String> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+
+    let mut month = None;
+    let mut year = None;
+
+    for part in date.split(|c: char| c.is_whitespace() || c == '/' || c == ',') {
+        let lower = part.to_lowercase();
+
+        if part.len() == 4 && part.chars().all(|c| c.is_ascii_digit()) {
+            year = Some(part.to_string());
+        } else if let Some(index) = MONTHS.iter().position(|month| lower.starts_with(month)) {
+            month = Some(index + 1);
+        } else if let Ok(number) = part.parse::<usize>() {
+            if (1..=12).contains(&number) {
+                month = Some(number);
+            }
+        }
+    }
+
+    match (year?, month) {
+        (year, Some(month)) => Some(format!("{year}-{month:02}")),
+        (year, None) => Some(year),
+    }
+}
+
+// Splits "Jan 2020 - Present" into a start and (optional) end date
+fn duration_dates(duration: &str) -> (Option<String>, Option<String>) {
+    let (start, end) = duration
+        .split_once(" - ")
+        .or_else(|| duration.split_once('–'))
+        .or_else(|| duration.split_once('—'))
+        .or_else(|| duration.split_once('-'))
+        .unwrap_or((duration, ""));
+
+    (iso_date(start.trim()), iso_date(end.trim()))
+}
+
+fn insert_some(object: &mut Map<String, Value>, key: &str, value: Option<impl Into<Value>>) {
+    if let Some(value) = value {
+        object.insert(key.to_string(), value.into());
+    }
+}
+
+// https://jsonresume.org/schema
+pub fn to_json_resume(resume: &Resume) -> Value {
+    let info = &resume.info;
+
+    let mut basics = Map::new();
+    insert_some(&mut basics, "name", info.name.clone());
+    insert_some(&mut basics, "email", info.email.clone());
+    insert_some(&mut basics, "phone", info.phone.clone());
+    insert_some(&mut basics, "url", info.website.clone());
+    basics.insert("summary".to_string(), resume.overview.clone().into());
+
+    let profiles: Vec<Value> = [("GitHub", &info.github), ("LinkedIn", &info.linkedin)]
+        .into_iter()
+        .filter_map(|(network, url)| Some(json!({ "network": network, "url": url.as_ref()? })))
+        .collect();
+    basics.insert("profiles".to_string(), profiles.into());
+
+    let work: Vec<Value> = resume
+        .experience
+        .iter()
+        .map(|entry| {
+            let mut work = Map::new();
+            insert_some(&mut work, "name", entry.company.clone());
+            insert_some(&mut work, "position", entry.title.clone());
+            insert_some(&mut work, "location", entry.location.clone());
+
+            if let Some(duration) = &entry.duration {
+                let (start, end) = duration_dates(duration);
+                insert_some(&mut work, "startDate", start);
+                insert_some(&mut work, "endDate", end);
+            }
+
+            work.insert(
+                "highlights".to_string(),
+                entry.desc.clone().unwrap_or_default().into(),
+            );
+
+            Value::Object(work)
+        })
+        .collect();
+
+    let education: Vec<Value> = resume
+        .education
+        .iter()
+        .map(|entry| {
+            let mut education = Map::new();
+            insert_some(&mut education, "institution", entry.college.clone());
+            insert_some(&mut education, "studyType", entry.degree.clone());
+            insert_some(&mut education, "area", entry.major.clone());
+
+            Value::Object(education)
+        })
+        .collect();
+
+    let skills: Vec<Value> = skill_categories(resume)
+        .into_iter()
+        .map(|(label, values)| json!({ "name": label, "keywords": values }))
+        .collect();
+
+    json!({
+        "$schema": "https://raw.githubusercontent.com/jsonresume/resume-schema/v1.0.0/schema.json",
+        "basics": basics,
+        "work": work,
+        "education": education,
+        "skills": skills,
+    })
+}
+
+pub fn to_markdown(resume: &Resume) -> String {
+    let mut markdown = String::new();
+
+    let _ = writeln!(
+        markdown,
+        "# {}\n",
+        resume.info.name.as_deref().unwrap_or("Resume")
+    );
+
+    let contact = contact_details(resume);
+    if !contact.is_empty() {
+        let _ = writeln!(markdown, "{}\n", contact.join(" | "));
+    }
+
+    if !resume.overview.trim().is_empty() {
+        let _ = writeln!(markdown, "## Overview\n\n{}\n", resume.overview.trim());
+    }
+
+    if !resume.experience.is_empty() {
+        let _ = writeln!(markdown, "## Experience\n");
+
+        for entry in &resume.experience {
+            let _ = writeln!(
+                markdown,
+                "### {}\n",
+                entry.company.as_deref().unwrap_or("Unknown company")
+            );
+
+            let role = role_line(entry);
+            if !role.is_empty() {
+                let _ = writeln!(markdown, "*{role}*\n");
+            }
+
+            for item in entry.desc.iter().flatten() {
+                let _ = writeln!(markdown, "- {item}");
+            }
+            let _ = writeln!(markdown);
+        }
+    }
+
+    let skills = skill_categories(resume);
+    if !skills.is_empty() {
+        let _ = writeln!(markdown, "## Skills\n");
+        for (label, values) in skills {
+            let _ = writeln!(markdown, "- **{label}:** {}", values.join(", "));
+        }
+        let _ = writeln!(markdown);
+    }
+
+    if !resume.education.is_empty() {
+        let _ = writeln!(markdown, "## Education\n");
+        for entry in &resume.education {
+            let degree: Vec<&str> = [&entry.degree, &entry.major]
+                .into_iter()
+                .filter_map(|value| value.as_deref())
+                .collect();
+            let _ = writeln!(
+                markdown,
+                "- **{}** {}",
+                entry.college.as_deref().unwrap_or_default(),
+                degree.join(", ")
+            );
+        }
+    }
+
+    markdown
+}
+
+pub fn to_plain_text(resume: &Resume) -> String {
+    let mut text = String::new();
+    let name = resume.info.name.as_deref().unwrap_or("Resume");
+
+    let _ = writeln!(text, "{name}\n{}\n", "=".repeat(name.chars().count()));
+
+    let contact = contact_details(resume);
+    if !contact.is_empty() {
+        let _ = writeln!(text, "{}\n", contact.join(" | "));
+    }
+
+    if !resume.overview.trim().is_empty() {
+        let _ = writeln!(text, "OVERVIEW\n\n{}\n", resume.overview.trim());
+    }
+
+    if !resume.experience.is_empty() {
+        let _ = writeln!(text, "EXPERIENCE\n");
+
+        for entry in &resume.experience {
+            let _ = writeln!(text, "{}", entry.company.as_deref().unwrap_or_default());
+
+            let role = role_line(entry);
+            if !role.is_empty() {
+                let _ = writeln!(text, "{role}");
+            }
+
+            for item in entry.desc.iter().flatten() {
+                let _ = writeln!(text, "  * {item}");
+            }
+            let _ = writeln!(text);
+        }
+    }
+
+    let skills = skill_categories(resume);
+    if !skills.is_empty() {
+        let _ = writeln!(text, "SKILLS\n");
+        for (label, values) in skills {
+            let _ = writeln!(text, "{label}: {}", values.join(", "));
+        }
+        let _ = writeln!(text);
+    }
+
+    if !resume.education.is_empty() {
+        let _ = writeln!(text, "EDUCATION\n");
+        for entry in &resume.education {
+            let details: Vec<&str> = [&entry.college, &entry.degree, &entry.major]
+                .into_iter()
+                .filter_map(|value| value.as_deref())
+                .collect();
+            let _ = writeln!(text, "{}", details.join(", "));
+        }
+    }
+
+    text
+}
+
+// Escapes the characters that have meaning in vCard property values (RFC 6350 section 3.4)
+fn vcard_escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace('\n', "\\n")
+}
+
+pub fn to_vcard(resume: &Resume) -> String {
+    let info = &resume.info;
+    let name = info.name.as_deref().unwrap_or_default();
+
+    // N is family;given;additional;prefix;suffix
+    let (given, family) = name.rsplit_once(' ').unwrap_or((name, ""));
+
+    let mut lines = vec![
+        "BEGIN:VCARD".to_string(),
+        "VERSION:4.0".to_string(),
+        format!("FN:{}", vcard_escape(name)),
+        format!("N:{};{};;;", vcard_escape(family), vcard_escape(given)),
+    ];
+
+    if let Some(email) = &info.email {
+        lines.push(format!("EMAIL;TYPE=work:{}", vcard_escape(email)));
+    }
+    if let Some(phone) = &info.phone {
+        lines.push(format!("TEL;TYPE=cell:{}", vcard_escape(phone)));
+    }
+    for url in [&info.website, &info.linkedin, &info.github]
+        .into_iter()
+        .flatten()
+    {
+        lines.push(format!("URL:{}", vcard_escape(url)));
+    }
+    if let Some(title) = resume
+        .experience
+        .first()
+        .and_then(|entry| entry.title.as_ref())
+    {
+        lines.push(format!("TITLE:{}", vcard_escape(title)));
+    }
+
+    lines.push("END:VCARD".to_string());
+
+    // vCard lines are CRLF terminated
+    lines.join("\r\n") + "\r\n"
+}
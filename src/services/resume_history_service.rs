@@ -0,0 +1,238 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    services::{
+        resume_override_service::{apply_overrides, load_overrides},
+        resume_store_service::resume_store,
+        resume_variant_service::pdf_path,
+    },
+    Resume, ResumeCache,
+};
+
+lazy_static! {
+    // Held by everything that changes which revision is live or pending, so rollbacks, approvals
+    // and rejections never interleave
+    pub(crate) static ref ROLLBACK_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+}
+
+type HistoryError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResumeRevision {
+    pub rev: u64,
+    pub uploaded_at: u64,
+    pub sha256: String,
+    // Hand-written resumes are staged without a PDF
+    pub has_pdf: bool,
+    // Free text sent along with the upload, e.g. what changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ResumeHistory {
+    pub live: Option<u64>,
+    pub pending: Option<u64>,
+    pub revisions: Vec<ResumeRevision>,
+}
+
+// A resume being staged, before the store has given it a revision id
+pub struct NewRevision<'a> {
+    pub resume: &'a Resume,
+    pub pdf_bytes: Option<&'a [u8]>,
+    pub uploaded_at: u64,
+    pub sha256: String,
+    pub note: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+pub struct RevisionDetails {
+    #[serde(flatten)]
+    pub revision: ResumeRevision,
+    pub resume: Resume,
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    tmp_path.into()
+}
+
+// Writes to a temporary file first so readers never see a partially written file
+pub(crate) fn write_atomically(path: &Path, bytes: &[u8]) -> Result<(), HistoryError> {
+    let tmp_path = tmp_path(path);
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&tmp_path)?;
+
+    file.write_all(bytes)?;
+    file.sync_all()?;
+
+    fs::rename(&tmp_path, path)?;
+
+    Ok(())
+}
+
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+pub async fn load_history(variant: &str) -> Result<ResumeHistory, HistoryError> {
+    resume_store().load_history(variant).await
+}
+
+// Stores the uploaded PDF and its parsed JSON as a new revision and marks it as pending. The hash
+// is taken over the PDF, or over the resume JSON when there is no PDF
+pub async fn record_revision(
+    variant: &str,
+    pdf_bytes: Option<&[u8]>,
+    resume: &Resume,
+    note: Option<&str>,
+) -> Result<ResumeRevision, HistoryError> {
+    let sha256 = match pdf_bytes {
+        Some(pdf_bytes) => hash_bytes(pdf_bytes),
+        None => hash_bytes(serde_json::to_string(resume)?.as_bytes()),
+    };
+
+    let revision = resume_store()
+        .add_revision(
+            variant,
+            NewRevision {
+                resume,
+                pdf_bytes,
+                uploaded_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+                sha256,
+                note,
+            },
+        )
+        .await?;
+
+    println!("Recorded {variant} resume revision {}", revision.rev);
+
+    Ok(revision)
+}
+
+// Called when the pending resume is rejected. Returns the revision that was pending, if any
+pub async fn clear_pending(variant: &str) -> Result<Option<u64>, HistoryError> {
+    resume_store().clear_pending_revision(variant).await
+}
+
+// Whether the variant's uploaded PDF belongs to its live resume. Revisions without a PDF, like
+// hand-written ones, leave an older revision's PDF in place. Variants from before the history
+// was kept are assumed to match
+pub async fn live_pdf_matches(variant: &str) -> Result<bool, HistoryError> {
+    let history = load_history(variant).await?;
+
+    let Some(live) = history.live else {
+        return Ok(true);
+    };
+
+    Ok(history
+        .revisions
+        .iter()
+        .find(|revision| revision.rev == live)
+        .is_none_or(|revision| revision.has_pdf))
+}
+
+pub async fn get_revision(
+    variant: &str,
+    rev: u64,
+) -> Result<Option<RevisionDetails>, HistoryError> {
+    let revision = match load_history(variant)
+        .await?
+        .revisions
+        .into_iter()
+        .find(|revision| revision.rev == rev)
+    {
+        Some(revision) => revision,
+        None => return Ok(None),
+    };
+
+    let resume = resume_store()
+        .load_revision(variant, rev)
+        .await?
+        .ok_or_else(|| format!("Revision {rev} has no resume"))?;
+
+    Ok(Some(RevisionDetails { revision, resume }))
+}
+
+// Makes a previous revision live again. The cache write lock is held while the served PDF is
+// swapped and the cache is updated, so requests never observe a mix of two revisions
+pub async fn rollback(
+    variant: &str,
+    rev: u64,
+    resume_cache: &ResumeCache,
+) -> Result<Option<ResumeRevision>, HistoryError> {
+    // Rollbacks swap both the store and the served PDF, so they are serialized here
+    let _rollback_guard = ROLLBACK_LOCK.lock().await;
+
+    let Some(RevisionDetails {
+        revision,
+        resume: parsed,
+    }) = get_revision(variant, rev).await?
+    else {
+        return Ok(None);
+    };
+
+    let pdf_bytes = resume_store().load_revision_pdf(variant, rev).await?;
+
+    let mut resume = parsed.clone();
+    apply_overrides(&mut resume, &load_overrides(variant)?);
+
+    // Stage the PDF before anything is switched over. Revisions without a PDF leave the currently
+    // served PDF alone
+    let pdf_path = pdf_path(variant);
+    let pdf_tmp = tmp_path(&pdf_path);
+
+    if let Some(pdf_bytes) = &pdf_bytes {
+        fs::write(&pdf_tmp, pdf_bytes)?;
+    }
+
+    // The store is switched over first, the cache and served PDF only follow once it has the new
+    // live revision. If it can't be recorded the previous live resume is put back
+    let previous = resume_store().load_live(variant).await.ok();
+
+    let switched = match resume_store().save_live(variant, &parsed).await {
+        Ok(()) => resume_store().set_live_revision(variant, rev).await,
+        Err(err) => Err(err),
+    };
+
+    if let Err(err) = switched {
+        if let Some(previous) = &previous {
+            resume_store().save_live(variant, previous).await?;
+        }
+
+        let _ = fs::remove_file(&pdf_tmp);
+
+        return Err(err);
+    }
+
+    {
+        let mut write_to_cache = resume_cache
+            .variants
+            .write()
+            .map_err(|_| "Error establishing write lock on resume cache")?;
+
+        if pdf_bytes.is_some() {
+            fs::rename(&pdf_tmp, &pdf_path)?;
+        }
+
+        write_to_cache.insert(variant.to_string(), resume);
+    }
+
+    println!("Rolled {variant} resume back to revision {rev}");
+
+    Ok(Some(revision))
+}
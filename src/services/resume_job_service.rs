@@ -0,0 +1,418 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use lazy_static::lazy_static;
+use redis::{aio::ConnectionManager, AsyncCommands, Script};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    server_functions::{generate_token, get_env_variable},
+    services::{
+        redis_repository::RedisRepository,
+        resume_history_service::hash_bytes,
+        resume_parsing_service::{is_invalid_resume, parse_resume, ParsedResume},
+        resume_staging_service::{stage_pending_resume, UploadResponse},
+    },
+};
+
+lazy_static! {
+    // Parsing is retried this many times in total before a job is marked as failed
+    static ref RESUME_JOB_MAX_ATTEMPTS: u32 = get_env_variable("RESUME_JOB_MAX_ATTEMPTS")
+        .and_then(|attempts| attempts.parse().ok())
+        .unwrap_or(3);
+    // Base delay between retries, doubled after every failed attempt
+    static ref RESUME_JOB_RETRY_DELAY: u64 = get_env_variable("RESUME_JOB_RETRY_DELAY_SECS")
+        .and_then(|delay| delay.parse().ok())
+        .unwrap_or(10);
+    // A job that hasn't made progress for this long while processing was interrupted part way
+    static ref RESUME_JOB_STALE_AFTER: u64 = get_env_variable("RESUME_JOB_STALE_AFTER_SECS")
+        .and_then(|stale_after| stale_after.parse().ok())
+        .unwrap_or(600);
+    // Points an upload's key at a new job, unless it already points at a job other than the one
+    // given as finished. Returns the job the key points at afterwards
+    static ref CLAIM_UPLOAD: Script = Script::new(
+        r"
+        local current = redis.call('GET', KEYS[1])
+        if current and current ~= ARGV[2] then
+            return current
+        end
+        redis.call('SET', KEYS[1], ARGV[1], 'EX', ARGV[3])
+        return ARGV[1]
+        ",
+    );
+    // Lets go of an upload's key, unless a newer job has already taken it over
+    static ref RELEASE_UPLOAD: Script = Script::new(
+        r"
+        if redis.call('GET', KEYS[1]) == ARGV[1] then
+            redis.call('DEL', KEYS[1])
+        end
+        return 0
+        ",
+    );
+}
+
+const QUEUE_KEY: &str = "resume_jobs:queue";
+// Jobs move here while they run, so one interrupted by a restart can be put back on the queue
+const PROCESSING_KEY: &str = "resume_jobs:processing";
+// Jobs waiting to be retried, scored by the time they become due
+const RETRY_KEY: &str = "resume_jobs:retry";
+
+// Finished jobs are kept around for a week so their result can still be looked up
+const FINISHED_JOB_TTL: i64 = 60 * 60 * 24 * 7;
+// Only a job stuck for longer than this, e.g. while the worker is down, lets the same file be
+// queued again
+const UPLOAD_CLAIM_TTL: i64 = 60 * 60 * 24;
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const STALE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+type JobError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Parsing,
+    Validating,
+    // Done, the resume is staged as pending and waiting for approval
+    Pending,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResumeJob {
+    pub id: String,
+    pub variant: String,
+    pub sha256: String,
+    pub note: Option<String>,
+    pub status: JobStatus,
+    pub attempts: u32,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub error: Option<String>,
+    pub result: Option<UploadResponse>,
+}
+
+fn job_key(id: &str) -> String {
+    format!("resume_job:{id}")
+}
+
+fn pdf_key(id: &str) -> String {
+    format!("resume_job:{id}:pdf")
+}
+
+// Points at the unfinished job for a given file, so uploading it twice doesn't queue it twice
+fn upload_key(variant: &str, sha256: &str) -> String {
+    format!("resume_job_upload:{variant}:{sha256}")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+async fn save_job(con: &mut ConnectionManager, job: &ResumeJob) -> Result<(), JobError> {
+    () = con
+        .set(job_key(&job.id), serde_json::to_string(job)?)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn load_job(
+    con: &mut ConnectionManager,
+    id: &str,
+) -> Result<Option<ResumeJob>, JobError> {
+    let job_string: Option<String> = con.get(job_key(id)).await?;
+
+    match job_string {
+        Some(job_string) => Ok(Some(serde_json::from_str::<ResumeJob>(&job_string)?)),
+        None => Ok(None),
+    }
+}
+
+fn is_active(job: &ResumeJob) -> bool {
+    !matches!(job.status, JobStatus::Pending | JobStatus::Failed)
+}
+
+// Queues the upload, unless the same file is already queued for the variant. Returns the job and
+// whether it is new. The PDF is stored alongside the job so the job can still run after a restart
+pub async fn enqueue_upload(
+    con: &mut ConnectionManager,
+    variant: &str,
+    pdf_bytes: &[u8],
+    note: Option<String>,
+) -> Result<(ResumeJob, bool), JobError> {
+    let job = ResumeJob {
+        id: generate_token(),
+        variant: variant.to_string(),
+        sha256: hash_bytes(pdf_bytes),
+        note,
+        status: JobStatus::Queued,
+        attempts: 0,
+        created_at: now(),
+        updated_at: now(),
+        error: None,
+        result: None,
+    };
+
+    // Saved before the upload is claimed, so whoever finds the claim can also find the job
+    () = con.set(pdf_key(&job.id), pdf_bytes).await?;
+    save_job(con, &job).await?;
+
+    let mut finished = String::new();
+
+    loop {
+        let claimed_by: String = CLAIM_UPLOAD
+            .key(upload_key(variant, &job.sha256))
+            .arg(&job.id)
+            .arg(&finished)
+            .arg(UPLOAD_CLAIM_TTL)
+            .invoke_async(con)
+            .await?;
+
+        if claimed_by == job.id {
+            break;
+        }
+
+        match load_job(con, &claimed_by).await?.filter(is_active) {
+            Some(active_job) => {
+                () = con.del(&[pdf_key(&job.id), job_key(&job.id)]).await?;
+                return Ok((active_job, false));
+            }
+            // Its job finished or expired just before it let go of the key
+            None => finished = claimed_by,
+        }
+    }
+
+    () = con.lpush(QUEUE_KEY, &job.id).await?;
+
+    println!("Queued {variant} resume upload job {}", job.id);
+
+    Ok((job, true))
+}
+
+async fn update_status(
+    con: &mut ConnectionManager,
+    job: &mut ResumeJob,
+    status: JobStatus,
+) -> Result<(), JobError> {
+    job.status = status;
+    job.updated_at = now();
+
+    save_job(con, job).await
+}
+
+// Finished jobs no longer need their PDF, and expire after a while
+async fn finish_job(
+    con: &mut ConnectionManager,
+    job: &mut ResumeJob,
+    status: JobStatus,
+) -> Result<(), JobError> {
+    update_status(con, job, status).await?;
+
+    () = con.del(pdf_key(&job.id)).await?;
+    () = RELEASE_UPLOAD
+        .key(upload_key(&job.variant, &job.sha256))
+        .arg(&job.id)
+        .invoke_async(con)
+        .await?;
+    () = con.expire(job_key(&job.id), FINISHED_JOB_TTL).await?;
+
+    Ok(())
+}
+
+async fn fail_job(
+    con: &mut ConnectionManager,
+    job: &mut ResumeJob,
+    error: String,
+) -> Result<(), JobError> {
+    println!("Resume upload job {} failed: {error}", job.id);
+
+    job.error = Some(error);
+
+    finish_job(con, job, JobStatus::Failed).await
+}
+
+async fn run_job(con: &mut ConnectionManager, id: &str) -> Result<(), JobError> {
+    let Some(mut job) = load_job(con, id).await? else {
+        println!("Resume upload job {id} no longer exists, skipping");
+        return Ok(());
+    };
+
+    let pdf_bytes: Option<Vec<u8>> = con.get(pdf_key(id)).await?;
+
+    let Some(pdf_bytes) = pdf_bytes else {
+        return fail_job(con, &mut job, "Uploaded PDF is missing".to_string()).await;
+    };
+
+    job.attempts += 1;
+    update_status(con, &mut job, JobStatus::Parsing).await?;
+
+    let ParsedResume { parser, resume } = match parse_resume(&pdf_bytes).await {
+        Ok(parsed) => parsed,
+        // Retrying won't make an unreadable PDF parse
+        Err(err) if is_invalid_resume(&err) => {
+            let error = format!("Resume could not be parsed: {err}");
+            return fail_job(con, &mut job, error).await;
+        }
+        // Otherwise ParseCV was most likely down or slow, so give it another go later
+        Err(err) if job.attempts < *RESUME_JOB_MAX_ATTEMPTS => {
+            let delay = *RESUME_JOB_RETRY_DELAY * 2u64.pow(job.attempts - 1);
+
+            println!(
+                "Resume upload job {id} attempt {} failed, retrying in {delay}s: {err}",
+                job.attempts
+            );
+
+            job.error = Some(format!("Attempt {} failed: {err}", job.attempts));
+            update_status(con, &mut job, JobStatus::Queued).await?;
+            () = con.zadd(RETRY_KEY, id, now() + delay).await?;
+
+            return Ok(());
+        }
+        Err(err) => {
+            let error = format!(
+                "Error parsing resume after {} attempt(s): {err}",
+                job.attempts
+            );
+            return fail_job(con, &mut job, error).await;
+        }
+    };
+
+    update_status(con, &mut job, JobStatus::Validating).await?;
+
+    // The PDF is kept with the revision and only served once the resume is approved
+    match stage_pending_resume(
+        &job.variant,
+        resume,
+        Some(&pdf_bytes),
+        parser,
+        job.note.as_deref(),
+    )
+    .await
+    {
+        Ok(response) => {
+            job.error = None;
+            job.result = Some(response);
+
+            println!("Resume upload job {id} is staged as pending");
+
+            finish_job(con, &mut job, JobStatus::Pending).await
+        }
+        Err(err) => fail_job(con, &mut job, err.to_string()).await,
+    }
+}
+
+// Puts jobs left in the processing list by a previous run back on the queue
+async fn recover_interrupted_jobs(con: &mut ConnectionManager) -> Result<(), JobError> {
+    loop {
+        let id: Option<String> = con.rpoplpush(PROCESSING_KEY, QUEUE_KEY).await?;
+
+        match id {
+            Some(id) => println!("Re-queued interrupted resume upload job {id}"),
+            None => return Ok(()),
+        }
+    }
+}
+
+// Puts jobs that stopped part way back on the queue without waiting for a restart. Jobs that are
+// already finished are just dropped from the processing list
+async fn requeue_stale_jobs(con: &mut ConnectionManager) -> Result<(), JobError> {
+    let processing: Vec<String> = con.lrange(PROCESSING_KEY, 0, -1).await?;
+
+    for id in processing {
+        let job = load_job(con, &id).await?;
+
+        let stale = match &job {
+            Some(job) if matches!(job.status, JobStatus::Pending | JobStatus::Failed) => false,
+            Some(job) if job.updated_at + *RESUME_JOB_STALE_AFTER > now() => continue,
+            _ => true,
+        };
+
+        // Only the caller that actually removed the entry re-queues it
+        let removed: i64 = con.lrem(PROCESSING_KEY, 1, &id).await?;
+
+        let Some(mut job) = job.filter(|_| stale && removed > 0) else {
+            continue;
+        };
+
+        if job.attempts >= *RESUME_JOB_MAX_ATTEMPTS {
+            let error = format!("Interrupted after {} attempt(s)", job.attempts);
+            fail_job(con, &mut job, error).await?;
+            continue;
+        }
+
+        update_status(con, &mut job, JobStatus::Queued).await?;
+        () = con.lpush(QUEUE_KEY, &id).await?;
+
+        println!("Re-queued stale resume upload job {id}");
+    }
+
+    Ok(())
+}
+
+async fn queue_due_retries(con: &mut ConnectionManager) -> Result<(), JobError> {
+    let due: Vec<String> = con.zrangebyscore(RETRY_KEY, 0, now()).await?;
+
+    for id in due {
+        // Only the caller that actually removed the entry re-queues it
+        let removed: i64 = con.zrem(RETRY_KEY, &id).await?;
+
+        if removed > 0 {
+            () = con.lpush(QUEUE_KEY, &id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn process_next_job(con: &mut ConnectionManager) -> Result<bool, JobError> {
+    queue_due_retries(con).await?;
+
+    let id: Option<String> = con.rpoplpush(QUEUE_KEY, PROCESSING_KEY).await?;
+
+    let Some(id) = id else {
+        return Ok(false);
+    };
+
+    run_job(con, &id).await?;
+
+    // Jobs that error out part way stay in the processing list until they're found to be stale
+    () = con.lrem(PROCESSING_KEY, 1, &id).await?;
+
+    Ok(true)
+}
+
+// Runs upload jobs one at a time for as long as the server is up
+pub async fn run_resume_jobs(redis: RedisRepository) {
+    let mut con = redis.connection();
+
+    // Keeps trying until redis is reachable, jobs can't run before they're recovered
+    while let Err(err) = recover_interrupted_jobs(&mut con).await {
+        println!("Error recovering interrupted resume upload jobs: {err}");
+        tokio::time::sleep(POLL_INTERVAL * 5).await;
+    }
+
+    let mut stale_check = tokio::time::Instant::now() + STALE_CHECK_INTERVAL;
+
+    loop {
+        if tokio::time::Instant::now() >= stale_check {
+            stale_check += STALE_CHECK_INTERVAL;
+
+            if let Err(err) = requeue_stale_jobs(&mut con).await {
+                println!("Error re-queuing stale resume upload jobs: {err}");
+            }
+        }
+
+        match process_next_job(&mut con).await {
+            Ok(true) => continue,
+            Ok(false) => tokio::time::sleep(POLL_INTERVAL).await,
+            // The connection reconnects by itself, so just back off for a bit
+            Err(err) => {
+                println!("Resume job worker error: {err}");
+                tokio::time::sleep(POLL_INTERVAL * 5).await;
+            }
+        }
+    }
+}
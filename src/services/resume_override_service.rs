@@ -0,0 +1,297 @@
+use std::{
+    fs,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    server_functions::generate_token,
+    services::{
+        resume_history_service::write_atomically,
+        resume_variant_service::{overrides_path, resume_dir},
+    },
+    Experience, Resume,
+};
+
+lazy_static! {
+    // Guards read-modify-write cycles on the overrides document
+    static ref OVERRIDES_LOCK: Mutex<()> = Mutex::new(());
+}
+
+type OverrideError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExperienceField {
+    Company,
+    Title,
+    Duration,
+    Location,
+    Desc,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SkillCategory {
+    Languages,
+    Frameworks,
+    Devops,
+    Database,
+    DevTools,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApplicantField {
+    Name,
+    Phone,
+    Email,
+    Github,
+    Linkedin,
+    Website,
+}
+
+// What part of the parsed resume an override replaces. Experience entries can be selected by
+// index, by company, or both - when both are given the entry at `index` must also match `company`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "target", rename_all = "snake_case")]
+pub enum OverrideTarget {
+    Experience {
+        index: Option<usize>,
+        company: Option<String>,
+        field: ExperienceField,
+    },
+    Skills {
+        category: SkillCategory,
+    },
+    Applicant {
+        field: ApplicantField,
+    },
+    Overview,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum OverrideValue {
+    Text(String),
+    List(Vec<String>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewOverride {
+    #[serde(flatten)]
+    pub target: OverrideTarget,
+    pub value: OverrideValue,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResumeOverride {
+    pub id: String,
+    pub created_at: u64,
+    #[serde(flatten)]
+    pub target: OverrideTarget,
+    pub value: OverrideValue,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct OverrideReport {
+    pub applied: Vec<String>,
+    pub unmatched: Vec<ResumeOverride>,
+}
+
+impl NewOverride {
+    // List values are only valid for description and skill overrides, everything else takes text
+    pub fn check(&self) -> Result<(), String> {
+        let expects_list = matches!(
+            self.target,
+            OverrideTarget::Skills { .. }
+                | OverrideTarget::Experience {
+                    field: ExperienceField::Desc,
+                    ..
+                }
+        );
+
+        if let OverrideTarget::Experience {
+            index: None,
+            company: None,
+            ..
+        } = self.target
+        {
+            return Err("Experience overrides need an index or a company".to_string());
+        }
+
+        match (&self.value, expects_list) {
+            (OverrideValue::List(_), true) | (OverrideValue::Text(_), false) => Ok(()),
+            (_, true) => Err("Override value must be a list".to_string()),
+            (_, false) => Err("Override value must be a string".to_string()),
+        }
+    }
+}
+
+fn read_overrides(variant: &str) -> Result<Vec<ResumeOverride>, OverrideError> {
+    let path = overrides_path(variant);
+
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+
+    let overrides_string = fs::read_to_string(path)?;
+
+    Ok(serde_json::from_str::<Vec<ResumeOverride>>(
+        &overrides_string,
+    )?)
+}
+
+// Overrides are kept per variant, since each variant is parsed from a different PDF
+pub fn load_overrides(variant: &str) -> Result<Vec<ResumeOverride>, OverrideError> {
+    let _guard = OVERRIDES_LOCK
+        .lock()
+        .map_err(|_| "Overrides lock poisoned")?;
+
+    read_overrides(variant)
+}
+
+pub fn add_override(
+    variant: &str,
+    new_override: NewOverride,
+) -> Result<ResumeOverride, OverrideError> {
+    let _guard = OVERRIDES_LOCK
+        .lock()
+        .map_err(|_| "Overrides lock poisoned")?;
+
+    let mut overrides = read_overrides(variant)?;
+
+    let resume_override = ResumeOverride {
+        id: generate_token(),
+        created_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        target: new_override.target,
+        value: new_override.value,
+    };
+
+    overrides.push(resume_override.clone());
+
+    fs::create_dir_all(resume_dir(variant))?;
+    write_atomically(
+        &overrides_path(variant),
+        serde_json::to_string(&overrides)?.as_bytes(),
+    )?;
+
+    Ok(resume_override)
+}
+
+// Returns false if no override had the given id
+pub fn remove_override(variant: &str, id: &str) -> Result<bool, OverrideError> {
+    let _guard = OVERRIDES_LOCK
+        .lock()
+        .map_err(|_| "Overrides lock poisoned")?;
+
+    let mut overrides = read_overrides(variant)?;
+    let count = overrides.len();
+
+    overrides.retain(|resume_override| resume_override.id != id);
+
+    if overrides.len() == count {
+        return Ok(false);
+    }
+
+    write_atomically(
+        &overrides_path(variant),
+        serde_json::to_string(&overrides)?.as_bytes(),
+    )?;
+
+    Ok(true)
+}
+
+fn find_experience<'a>(
+    experience: &'a mut [Experience],
+    index: Option<usize>,
+    company: &Option<String>,
+) -> Option<&'a mut Experience> {
+    let same_company = |entry: &Experience| match (&entry.company, company) {
+        (Some(entry_company), Some(company)) => entry_company.eq_ignore_ascii_case(company),
+        _ => false,
+    };
+
+    match index {
+        Some(index) => experience
+            .get_mut(index)
+            .filter(|entry| company.is_none() || same_company(entry)),
+        None => experience.iter_mut().find(|entry| same_company(entry)),
+    }
+}
+
+fn apply_override(resume: &mut Resume, resume_override: &ResumeOverride) -> bool {
+    let value = resume_override.value.clone();
+
+    match (&resume_override.target, value) {
+        (
+            OverrideTarget::Experience {
+                index,
+                company,
+                field,
+            },
+            value,
+        ) => {
+            let Some(entry) = find_experience(&mut resume.experience, *index, company) else {
+                return false;
+            };
+
+            match (field, value) {
+                (ExperienceField::Desc, OverrideValue::List(desc)) => entry.desc = Some(desc),
+                (ExperienceField::Company, OverrideValue::Text(text)) => entry.company = Some(text),
+                (ExperienceField::Title, OverrideValue::Text(text)) => entry.title = Some(text),
+                (ExperienceField::Duration, OverrideValue::Text(text)) => {
+                    entry.duration = Some(text)
+                }
+                (ExperienceField::Location, OverrideValue::Text(text)) => {
+                    entry.location = Some(text)
+                }
+                _ => return false,
+            }
+        }
+        (OverrideTarget::Skills { category }, OverrideValue::List(skills)) => {
+            let skills = Some(skills);
+            match category {
+                SkillCategory::Languages => resume.skills.languages = skills,
+                SkillCategory::Frameworks => resume.skills.frameworks = skills,
+                SkillCategory::Devops => resume.skills.devops = skills,
+                SkillCategory::Database => resume.skills.database = skills,
+                SkillCategory::DevTools => resume.skills.dev_tools = skills,
+            }
+        }
+        (OverrideTarget::Applicant { field }, OverrideValue::Text(text)) => {
+            let text = Some(text);
+            match field {
+                ApplicantField::Name => resume.info.name = text,
+                ApplicantField::Phone => resume.info.phone = text,
+                ApplicantField::Email => resume.info.email = text,
+                ApplicantField::Github => resume.info.github = text,
+                ApplicantField::Linkedin => resume.info.linkedin = text,
+                ApplicantField::Website => resume.info.website = text,
+            }
+        }
+        (OverrideTarget::Overview, OverrideValue::Text(text)) => resume.overview = text,
+        _ => return false,
+    }
+
+    true
+}
+
+// Merges overrides over the parsed resume in the order they were created. Overrides that no
+// longer point at anything (e.g. a company that disappeared after a re-parse) are reported back
+pub fn apply_overrides(resume: &mut Resume, overrides: &[ResumeOverride]) -> OverrideReport {
+    let mut report = OverrideReport::default();
+
+    for resume_override in overrides {
+        if apply_override(resume, resume_override) {
+            report.applied.push(resume_override.id.clone());
+        } else {
+            report.unmatched.push(resume_override.clone());
+        }
+    }
+
+    report
+}
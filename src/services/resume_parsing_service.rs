@@ -1,84 +1,212 @@
-use std::{io::Write, path::PathBuf};
+use std::{fmt, path::PathBuf};
 
+use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use lazy_static::lazy_static;
 use reqwest::Client;
 use std::fs;
 
-use crate::{server_functions::get_env_variable, Resume};
+use crate::{
+    server_functions::get_env_variable,
+    services::{
+        local_resume_parser::LocalPdfParser,
+        resume_history_service::write_atomically,
+        resume_override_service::{apply_overrides, load_overrides},
+        resume_store_service::resume_store,
+        resume_variant_service::pdf_path,
+    },
+    Resume,
+};
 
 lazy_static! {
     static ref CLIENT: Client = reqwest::Client::new();
     static ref PARSE_URL: String = get_env_variable("PARSE_URL").expect("PARSE_URL not set!");
     static ref PARSE_API_KEY: String =
         get_env_variable("PARSE_API_KEY").expect("PARSE_API_KEY not set!");
-    static ref RESUME_FILE_NAME: String =
-        get_env_variable("RESUME_FILE_NAME").expect("RESUME_FILE_NAME not set!");
+    static ref PARSERS: Vec<Box<dyn ResumeParser>> = configured_parsers();
 }
 
-pub async fn parse_resume(file_bytes: Vec<u8>) -> Result<Resume, Box<dyn std::error::Error>> {
-    let form = reqwest::multipart::Form::new().part(
-        "resume",
-        reqwest::multipart::Part::bytes(file_bytes)
-            .file_name("resume.pdf")
-            .mime_str("application/pdf")?,
-    );
-
-    let res = CLIENT
-        .post(format!("{}/api/parse", PARSE_URL.as_str()))
-        .header("apiKey", PARSE_API_KEY.as_str())
-        .multipart(form)
-        .send()
-        .await?;
-
-    if res.status() != reqwest::StatusCode::OK {
-        println!("parseCV error: {}", res.text().await.unwrap());
-        return Err("Bad response from parseCV service!".into());
+pub type ParseError = Box<dyn std::error::Error + Send + Sync>;
+
+// A parse failure that trying again won't fix, like a file that isn't a valid PDF or has no
+// recognizable resume in it. Anything else, like ParseCV being down, is worth retrying
+#[derive(Debug)]
+pub struct InvalidResume(pub String);
+
+impl fmt::Display for InvalidResume {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
     }
+}
 
-    let resume = res.json::<Resume>().await?;
+impl std::error::Error for InvalidResume {}
 
-    Ok(resume)
+pub fn is_invalid_resume(err: &ParseError) -> bool {
+    err.is::<InvalidResume>()
 }
 
-pub fn update_resume_json() -> Result<(), Box<dyn std::error::Error>> {
-    let pending_path: PathBuf = "resumes/parsed_resume_pending.json".into();
-    let current_path: PathBuf = "resumes/parsed_resume.json".into();
+// A way of turning an uploaded PDF into a `Resume`
+#[async_trait]
+pub trait ResumeParser: Send + Sync {
+    // Reported back in upload responses so we know which parser produced the result
+    fn name(&self) -> &'static str;
 
-    fs::copy(&pending_path, &current_path)?;
+    async fn parse(&self, file_bytes: &[u8]) -> Result<Resume, ParseError>;
+}
 
-    Ok(())
+pub struct ParseCvParser;
+
+#[async_trait]
+impl ResumeParser for ParseCvParser {
+    fn name(&self) -> &'static str {
+        "parsecv"
+    }
+
+    async fn parse(&self, file_bytes: &[u8]) -> Result<Resume, ParseError> {
+        let form = reqwest::multipart::Form::new().part(
+            "resume",
+            reqwest::multipart::Part::bytes(file_bytes.to_vec())
+                .file_name("resume.pdf")
+                .mime_str("application/pdf")?,
+        );
+
+        let res = CLIENT
+            .post(format!("{}/api/parse", PARSE_URL.as_str()))
+            .header("apiKey", PARSE_API_KEY.as_str())
+            .multipart(form)
+            .send()
+            .await?;
+
+        let status = res.status();
+
+        if status != reqwest::StatusCode::OK {
+            println!("parseCV error: {}", res.text().await.unwrap_or_default());
+
+            // ParseCV turns down files it can't read with a client error, timeouts and rate
+            // limits aside
+            if status.is_client_error()
+                && status != reqwest::StatusCode::REQUEST_TIMEOUT
+                && status != reqwest::StatusCode::TOO_MANY_REQUESTS
+            {
+                return Err(Box::new(InvalidResume(format!(
+                    "parseCV rejected the resume with {status}"
+                ))));
+            }
+
+            return Err("Bad response from parseCV service!".into());
+        }
+
+        let resume = res.json::<Resume>().await?;
+
+        Ok(resume)
+    }
 }
 
-// Saves resume response as JSON file. Will probably replace with postgres db later on
-pub async fn save_resume_json(resume: &Resume) -> Result<(), Box<dyn std::error::Error>> {
-    // Overwrites existing parsed_resume.json file
-    let mut file = fs::OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open("resumes/parsed_resume_pending.json")?;
+// Parsers are tried in the order given by RESUME_PARSERS, e.g. "parsecv,local"
+fn configured_parsers() -> Vec<Box<dyn ResumeParser>> {
+    let parser_names =
+        get_env_variable("RESUME_PARSERS").unwrap_or_else(|| "parsecv,local".to_string());
+
+    parser_names
+        .split(',')
+        .map(str::trim)
+        .filter_map(|name| -> Option<Box<dyn ResumeParser>> {
+            match name {
+                "parsecv" => Some(Box::new(ParseCvParser)),
+                "local" => Some(Box::new(LocalPdfParser)),
+                _ => {
+                    println!("Ignoring unknown resume parser: {name}");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+pub struct ParsedResume {
+    pub parser: &'static str,
+    pub resume: Resume,
+}
+
+// Falls back to the next configured parser whenever one fails. Only when every parser found the
+// resume itself to be invalid is the failure an InvalidResume
+pub async fn parse_resume(file_bytes: &[u8]) -> Result<ParsedResume, ParseError> {
+    let mut all_invalid = true;
+
+    for parser in PARSERS.iter() {
+        match parser.parse(file_bytes).await {
+            Ok(resume) => {
+                return Ok(ParsedResume {
+                    parser: parser.name(),
+                    resume,
+                })
+            }
+            Err(err) => {
+                println!("Resume parser {} failed: {err}", parser.name());
+                all_invalid &= is_invalid_resume(&err);
+            }
+        }
+    }
+
+    if all_invalid && !PARSERS.is_empty() {
+        return Err(Box::new(InvalidResume(
+            "No resume parser could read the resume".to_string(),
+        )));
+    }
 
-    let resume_json = serde_json::to_string(&resume)?;
+    Err("No resume parser was able to parse the resume".into())
+}
 
-    file.write_all(resume_json.as_bytes())?;
+// Saves the parsed resume as the variant's pending resume in the configured store
+pub async fn save_resume_json(
+    variant: &str,
+    resume: &Resume,
+) -> Result<(), Box<dyn std::error::Error>> {
+    resume_store()
+        .save_pending(variant, resume)
+        .await
+        .map_err(|err| err as Box<dyn std::error::Error>)
+}
 
-    Ok(())
+// Loads the live resume exactly as ParseCV returned it, without any overrides
+pub async fn load_parsed_resume(variant: &str) -> Result<Resume, Box<dyn std::error::Error>> {
+    resume_store()
+        .load_live(variant)
+        .await
+        .map_err(|err| err as Box<dyn std::error::Error>)
 }
 
-pub async fn load_resume() -> Result<Resume, Box<dyn std::error::Error>> {
-    let resume_string = fs::read_to_string("resumes/parsed_resume.json")?;
+pub async fn load_resume(variant: &str) -> Result<Resume, Box<dyn std::error::Error>> {
+    let mut resume = load_parsed_resume(variant).await?;
+
+    // Manual fixes are layered on top of whatever ParseCV produced
+    let overrides = load_overrides(variant).map_err(|err| err as Box<dyn std::error::Error>)?;
+    let report = apply_overrides(&mut resume, &overrides);
 
-    let resume = serde_json::from_str::<Resume>(&resume_string)?;
+    for unmatched in report.unmatched {
+        println!(
+            "Override {} no longer matches anything in the parsed {variant} resume",
+            unmatched.id
+        );
+    }
 
     Ok(resume)
 }
 
-pub async fn update_current_resume(
-    new_resume_bytes: Vec<u8>,
+pub async fn load_pending_resume(variant: &str) -> Result<Resume, Box<dyn std::error::Error>> {
+    resume_store()
+        .load_pending(variant)
+        .await
+        .map_err(|err| err as Box<dyn std::error::Error>)
+}
+
+// Makes a newly approved PDF the one that is served. Uploaded PDFs wait with their revision until
+// then, so the served PDF always matches the live resume
+pub fn replace_current_pdf(
+    variant: &str,
+    new_resume_bytes: &[u8],
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let curr_resume_path_str = format!("uploads/{}.pdf", RESUME_FILE_NAME.to_string());
-    let curr_resume_path: PathBuf = curr_resume_path_str.clone().into();
+    let curr_resume_path = pdf_path(variant);
 
     // If a resume already exists, copy it to a different name and replace it
     if curr_resume_path.is_file() {
@@ -100,15 +228,7 @@ pub async fn update_current_resume(
         fs::copy(&curr_resume_path, &backup_path)?;
     }
 
-    println!("Overwriting resume file {}", curr_resume_path_str);
-
-    let mut file = fs::OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(&curr_resume_path_str)?;
-
-    file.write_all(new_resume_bytes.as_slice())?;
+    println!("Overwriting resume file {}", curr_resume_path.display());
 
-    Ok(())
+    write_atomically(&curr_resume_path, new_resume_bytes)
 }
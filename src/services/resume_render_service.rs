@@ -0,0 +1,349 @@
+use std::{fs, path::PathBuf};
+
+use printpdf::{
+    BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+};
+
+use crate::{
+    services::resume_history_service::{hash_bytes, write_atomically},
+    Resume,
+};
+
+const GENERATED_DIR: &str = "uploads/generated";
+
+// US Letter
+const PAGE_WIDTH: f32 = 215.9;
+const PAGE_HEIGHT: f32 = 279.4;
+
+// Rough average glyph width of Helvetica as a fraction of the font size, used for line wrapping
+const AVERAGE_CHAR_WIDTH: f32 = 0.5;
+const PT_TO_MM: f32 = 0.3528;
+
+type RenderError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderTemplate {
+    Classic,
+    Compact,
+}
+
+struct TemplateStyle {
+    margin: f32,
+    name_size: f32,
+    heading_size: f32,
+    body_size: f32,
+    line_spacing: f32,
+    section_spacing: f32,
+}
+
+impl RenderTemplate {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "classic" => Some(RenderTemplate::Classic),
+            "compact" => Some(RenderTemplate::Compact),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RenderTemplate::Classic => "classic",
+            RenderTemplate::Compact => "compact",
+        }
+    }
+
+    fn style(&self) -> TemplateStyle {
+        match self {
+            RenderTemplate::Classic => TemplateStyle {
+                margin: 20.0,
+                name_size: 22.0,
+                heading_size: 13.0,
+                body_size: 10.5,
+                line_spacing: 1.45,
+                section_spacing: 6.0,
+            },
+            RenderTemplate::Compact => TemplateStyle {
+                margin: 14.0,
+                name_size: 18.0,
+                heading_size: 11.5,
+                body_size: 9.0,
+                line_spacing: 1.3,
+                section_spacing: 3.5,
+            },
+        }
+    }
+}
+
+struct Fonts {
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    italic: IndirectFontRef,
+}
+
+// Writes text top to bottom, starting a new page whenever the current one is full
+struct PageWriter<'a> {
+    doc: &'a PdfDocumentReference,
+    layer: PdfLayerReference,
+    style: TemplateStyle,
+    y: f32,
+}
+
+impl PageWriter<'_> {
+    fn ensure_space(&mut self, height: f32) {
+        if self.y - height >= self.style.margin {
+            return;
+        }
+
+        let (page, layer) = self
+            .doc
+            .add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.y = PAGE_HEIGHT - self.style.margin;
+    }
+
+    fn line(&mut self, text: &str, size: f32, indent: f32, font: &IndirectFontRef) {
+        let height = size * PT_TO_MM * self.style.line_spacing;
+
+        self.ensure_space(height);
+        self.y -= height;
+        self.layer
+            .use_text(text, size, Mm(self.style.margin + indent), Mm(self.y), font);
+    }
+
+    // Wraps on word boundaries, continuation lines are indented by `hanging`
+    fn paragraph(
+        &mut self,
+        text: &str,
+        size: f32,
+        indent: f32,
+        hanging: f32,
+        font: &IndirectFontRef,
+    ) {
+        let usable = PAGE_WIDTH - 2.0 * self.style.margin - indent - hanging;
+        let max_chars = (usable / (size * PT_TO_MM * AVERAGE_CHAR_WIDTH)).max(10.0) as usize;
+
+        let mut lines: Vec<String> = Vec::new();
+        let mut current = String::new();
+
+        for word in text.split_whitespace() {
+            if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > max_chars
+            {
+                lines.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+
+        if !current.is_empty() {
+            lines.push(current);
+        }
+
+        for (index, line) in lines.iter().enumerate() {
+            let offset = if index == 0 { indent } else { indent + hanging };
+            self.line(line, size, offset, font);
+        }
+    }
+
+    fn gap(&mut self, height: f32) {
+        self.y -= height;
+    }
+}
+
+fn heading(writer: &mut PageWriter, fonts: &Fonts, title: &str) {
+    let size = writer.style.heading_size;
+    let spacing = writer.style.section_spacing;
+
+    writer.gap(spacing);
+    writer.line(&title.to_uppercase(), size, 0.0, &fonts.bold);
+    writer.gap(spacing / 3.0);
+}
+
+pub fn render_resume_pdf(
+    resume: &Resume,
+    template: RenderTemplate,
+) -> Result<Vec<u8>, RenderError> {
+    let style = template.style();
+    let title = resume
+        .info
+        .name
+        .clone()
+        .unwrap_or_else(|| "Resume".to_string());
+
+    let (doc, page, layer) = PdfDocument::new(&title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+
+    let fonts = Fonts {
+        regular: doc.add_builtin_font(BuiltinFont::Helvetica)?,
+        bold: doc.add_builtin_font(BuiltinFont::HelveticaBold)?,
+        italic: doc.add_builtin_font(BuiltinFont::HelveticaOblique)?,
+    };
+
+    let layer = doc.get_page(page).get_layer(layer);
+    let body = style.body_size;
+    let name_size = style.name_size;
+    let top = PAGE_HEIGHT - style.margin;
+
+    let mut writer = PageWriter {
+        doc: &doc,
+        layer,
+        style,
+        y: top,
+    };
+
+    writer.line(&title, name_size, 0.0, &fonts.bold);
+
+    let info = &resume.info;
+    let contact: Vec<&str> = [
+        &info.email,
+        &info.phone,
+        &info.github,
+        &info.linkedin,
+        &info.website,
+    ]
+    .into_iter()
+    .filter_map(|value| value.as_deref())
+    .collect();
+
+    if !contact.is_empty() {
+        writer.paragraph(&contact.join("  |  "), body, 0.0, 0.0, &fonts.regular);
+    }
+
+    if !resume.overview.trim().is_empty() {
+        heading(&mut writer, &fonts, "Overview");
+        writer.paragraph(&resume.overview, body, 0.0, 0.0, &fonts.regular);
+    }
+
+    if !resume.experience.is_empty() {
+        heading(&mut writer, &fonts, "Experience");
+
+        for entry in &resume.experience {
+            if let Some(company) = &entry.company {
+                writer.line(company, body + 1.0, 0.0, &fonts.bold);
+            }
+
+            let role: Vec<&str> = [&entry.title, &entry.duration, &entry.location]
+                .into_iter()
+                .filter_map(|value| value.as_deref())
+                .collect();
+            if !role.is_empty() {
+                writer.paragraph(&role.join("  |  "), body, 0.0, 0.0, &fonts.italic);
+            }
+
+            for item in entry.desc.iter().flatten() {
+                writer.paragraph(&format!("• {item}"), body, 3.0, 3.0, &fonts.regular);
+            }
+
+            writer.gap(body * PT_TO_MM * 0.5);
+        }
+    }
+
+    let skills = &resume.skills;
+    let skill_lines: Vec<String> = [
+        ("Languages", &skills.languages),
+        ("Tools and Frameworks", &skills.frameworks),
+        ("Database", &skills.database),
+        ("Devops and Deployment", &skills.devops),
+        ("Development Tools", &skills.dev_tools),
+    ]
+    .into_iter()
+    .filter_map(|(label, values)| {
+        let values = values.as_ref().filter(|values| !values.is_empty())?;
+        Some(format!("{label}: {}", values.join(", ")))
+    })
+    .collect();
+
+    if !skill_lines.is_empty() {
+        heading(&mut writer, &fonts, "Skills");
+        for line in skill_lines {
+            writer.paragraph(&line, body, 0.0, 3.0, &fonts.regular);
+        }
+    }
+
+    if !resume.education.is_empty() {
+        heading(&mut writer, &fonts, "Education");
+
+        for entry in &resume.education {
+            if let Some(college) = &entry.college {
+                writer.line(college, body, 0.0, &fonts.bold);
+            }
+
+            let degree = match (&entry.degree, &entry.major) {
+                (Some(degree), Some(major)) => Some(format!("{degree} in {major}")),
+                (Some(degree), None) => Some(degree.clone()),
+                (None, Some(major)) => Some(major.clone()),
+                (None, None) => None,
+            };
+            if let Some(degree) = degree {
+                writer.paragraph(&degree, body, 0.0, 0.0, &fonts.italic);
+            }
+        }
+    }
+
+    Ok(doc.save_to_bytes()?)
+}
+
+// Generated PDFs are cached on disk keyed by the variant, its live revision `rev`, the template and a
+// hash of the resume content, since overrides can change what is served without creating a new
+// revision. Only the current content is kept, in every template
+pub fn rendered_resume_path(
+    variant: &str,
+    rev: u64,
+    resume: &Resume,
+    template: RenderTemplate,
+) -> Result<PathBuf, RenderError> {
+    let content_hash = hash_bytes(serde_json::to_string(resume)?.as_bytes());
+
+    let path = PathBuf::from(format!(
+        "{GENERATED_DIR}/{variant}-{rev}-{}-{}.pdf",
+        template.name(),
+        &content_hash[..12]
+    ));
+
+    if path.is_file() {
+        return Ok(path);
+    }
+
+    println!(
+        "Rendering {} {variant} resume PDF for revision {rev}",
+        template.name()
+    );
+
+    let pdf_bytes = render_resume_pdf(resume, template)?;
+
+    fs::create_dir_all(GENERATED_DIR)?;
+    write_atomically(&path, &pdf_bytes)?;
+
+    prune_renders(variant, rev, &content_hash[..12]);
+
+    Ok(path)
+}
+
+// Removes the variant's renders of earlier revisions or content. File names are split from the
+// right since variant names can contain dashes but the other parts can't
+fn prune_renders(variant: &str, rev: u64, content_hash: &str) {
+    let Ok(entries) = fs::read_dir(GENERATED_DIR) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let Some(stem) = file_name
+            .to_str()
+            .and_then(|name| name.strip_suffix(".pdf"))
+        else {
+            continue;
+        };
+
+        let parts: Vec<&str> = stem.rsplitn(4, '-').collect();
+        let [hash, _template, file_rev, file_variant] = parts[..] else {
+            continue;
+        };
+
+        if file_variant == variant && (file_rev != rev.to_string() || hash != content_hash) {
+            if let Err(err) = fs::remove_file(entry.path()) {
+                println!("Could not remove old render {stem}: {err}");
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use lazy_static::lazy_static;
+
+use crate::{
+    server_functions::get_env_variable,
+    services::resume_variant_service::{is_default, resume_dir},
+    Resume,
+};
+
+lazy_static! {
+    static ref RESUME_SOURCE_FILE: String =
+        get_env_variable("RESUME_SOURCE_FILE").unwrap_or_else(|| "resumes/resume.toml".to_string());
+}
+
+type SourceError = Box<dyn std::error::Error + Send + Sync>;
+
+// Human-editable formats a resume can be written in. Both mirror the `Resume` JSON structure
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SourceFormat {
+    Toml,
+    Yaml,
+}
+
+impl SourceFormat {
+    pub const ALL: [SourceFormat; 2] = [SourceFormat::Toml, SourceFormat::Yaml];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "toml" => Some(SourceFormat::Toml),
+            "yaml" | "yml" => Some(SourceFormat::Yaml),
+            _ => None,
+        }
+    }
+
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        match content_type.split(';').next()?.trim() {
+            "application/toml" | "text/toml" | "text/x-toml" => Some(SourceFormat::Toml),
+            "application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml" => {
+                Some(SourceFormat::Yaml)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<Self> {
+        Self::from_name(path.extension()?.to_str()?)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SourceFormat::Toml => "toml",
+            SourceFormat::Yaml => "yaml",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            SourceFormat::Toml => "application/toml",
+            SourceFormat::Yaml => "application/yaml",
+        }
+    }
+}
+
+pub fn parse_source(source: &str, format: SourceFormat) -> Result<Resume, SourceError> {
+    let resume = match format {
+        SourceFormat::Toml => toml::from_str::<Resume>(source)?,
+        SourceFormat::Yaml => serde_yaml::from_str::<Resume>(source)?,
+    };
+
+    Ok(resume)
+}
+
+pub fn export_resume(resume: &Resume, format: SourceFormat) -> Result<String, SourceError> {
+    let source = match format {
+        SourceFormat::Toml => toml::to_string_pretty(resume)?,
+        SourceFormat::Yaml => serde_yaml::to_string(resume)?,
+    };
+
+    Ok(source)
+}
+
+// The default variant reads the file set by RESUME_SOURCE_FILE, other variants read
+// resume.toml (or resume.yaml) from their own directory
+fn source_file_path(variant: &str) -> PathBuf {
+    if is_default(variant) {
+        return PathBuf::from(RESUME_SOURCE_FILE.as_str());
+    }
+
+    let dir = resume_dir(variant);
+
+    ["resume.toml", "resume.yaml", "resume.yml"]
+        .into_iter()
+        .map(|file_name| dir.join(file_name))
+        .find(|path| path.is_file())
+        .unwrap_or_else(|| dir.join("resume.toml"))
+}
+
+// Reads a variant's hand-written resume. The format is taken from the file extension
+pub fn load_source_file(variant: &str) -> Result<(Resume, SourceFormat), SourceError> {
+    let path = source_file_path(variant);
+    let path = path.as_path();
+
+    let format = SourceFormat::from_path(path)
+        .ok_or_else(|| format!("Unsupported resume source file: {}", path.display()))?;
+
+    let source = fs::read_to_string(path)?;
+
+    Ok((parse_source(&source, format)?, format))
+}
@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    services::{
+        notification_service::notify_resume_staged,
+        resume_approval_service::auto_approve_time,
+        resume_history_service::record_revision,
+        resume_override_service::{apply_overrides, load_overrides},
+        resume_parsing_service::save_resume_json,
+        resume_validation_service::{validate_resume, ValidationReport},
+    },
+    Resume,
+};
+
+type StagingError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UploadResponse {
+    pub variant: String,
+    pub parser: String,
+    pub revision: u64,
+    pub validation: ValidationReport,
+    pub resume: Resume,
+}
+
+// Validates a parsed resume as it would be served, i.e. with overrides applied
+pub fn validate_with_overrides(
+    variant: &str,
+    resume: &Resume,
+) -> Result<ValidationReport, StagingError> {
+    let overrides = load_overrides(variant).map_err(|_| "Error loading overrides")?;
+
+    let mut resume = resume.clone();
+    apply_overrides(&mut resume, &overrides);
+
+    Ok(validate_resume(&resume))
+}
+
+// Saves a resume as the pending JSON and records it in the history, the same way for parsed
+// uploads and hand-written imports. Error messages are safe to return to the caller
+pub async fn stage_pending_resume(
+    variant: &str,
+    resume: Resume,
+    pdf_bytes: Option<&[u8]>,
+    parser: &str,
+    note: Option<&str>,
+) -> Result<UploadResponse, StagingError> {
+    // Save the response as JSON
+    save_resume_json(variant, &resume)
+        .await
+        .map_err(|_| "Error saving resume JSON")?;
+
+    // Keep a copy of this upload so it can be restored later
+    let revision = record_revision(variant, pdf_bytes, &resume, note)
+        .await
+        .map_err(|_| "Error recording resume revision")?;
+
+    // Invalid resumes are still staged so they can be fixed with overrides, but are flagged
+    let validation = validate_with_overrides(variant, &resume)?;
+
+    if !validation.is_valid() {
+        println!(
+            "Pending {variant} resume revision {} has {} validation error(s)",
+            revision.rev,
+            validation.errors.len()
+        );
+    }
+
+    let response = UploadResponse {
+        variant: variant.to_string(),
+        parser: parser.to_string(),
+        revision: revision.rev,
+        validation,
+        resume,
+    };
+
+    notify_resume_staged(
+        &response,
+        auto_approve_time(revision.uploaded_at, &response.validation),
+    );
+
+    Ok(response)
+}
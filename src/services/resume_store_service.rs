@@ -0,0 +1,403 @@
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+
+use crate::{
+    server_functions::get_env_variable,
+    services::{
+        filesystem_resume_store::FilesystemResumeStore,
+        postgres_resume_store::PostgresResumeStore,
+        resume_history_service::{NewRevision, ResumeHistory, ResumeRevision},
+    },
+    Resume,
+};
+
+lazy_static! {
+    static ref RESUME_STORE: Box<dyn ResumeStore> = configured_store();
+}
+
+pub type StoreError = Box<dyn std::error::Error + Send + Sync>;
+
+// Where the parsed live and pending resume of every variant is kept, along with its revision
+// history. The served PDF and overrides stay on disk whichever store is used
+#[async_trait]
+pub trait ResumeStore: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // Whether the live resumes are files that can be edited by hand, which the file watcher
+    // reloads. Other stores are only changed through the API
+    fn keeps_files(&self) -> bool {
+        false
+    }
+
+    // Called once on startup, before anything is loaded
+    async fn init(&self) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    // Every variant with a live resume, default first
+    async fn live_variants(&self) -> Result<Vec<String>, StoreError>;
+
+    // Every variant the store has anything for, including ones that have never been approved,
+    // default first
+    async fn known_variants(&self) -> Result<Vec<String>, StoreError>;
+
+    async fn load_live(&self, variant: &str) -> Result<Resume, StoreError>;
+
+    async fn load_pending(&self, variant: &str) -> Result<Resume, StoreError>;
+
+    async fn save_pending(&self, variant: &str, resume: &Resume) -> Result<(), StoreError>;
+
+    // Replaces the live resume directly, used when rolling back to an older revision
+    async fn save_live(&self, variant: &str, resume: &Resume) -> Result<(), StoreError>;
+
+    // Makes the pending resume live. The pending resume is kept, the same way the pending JSON
+    // file always has been
+    async fn promote_pending(&self, variant: &str) -> Result<(), StoreError>;
+
+    async fn discard_pending(&self, variant: &str) -> Result<(), StoreError>;
+
+    // The variant's revisions, oldest first, and which of them are live and pending
+    async fn load_history(&self, variant: &str) -> Result<ResumeHistory, StoreError>;
+
+    // Records a staged resume as the variant's newest revision and marks it as pending
+    async fn add_revision(
+        &self,
+        variant: &str,
+        revision: NewRevision<'_>,
+    ) -> Result<ResumeRevision, StoreError>;
+
+    async fn load_revision(&self, variant: &str, rev: u64) -> Result<Option<Resume>, StoreError>;
+
+    // None for unknown revisions and ones staged without a PDF
+    async fn load_revision_pdf(
+        &self,
+        variant: &str,
+        rev: u64,
+    ) -> Result<Option<Vec<u8>>, StoreError>;
+
+    // Points the history at the revision that is now live. It stops being pending if it was
+    async fn set_live_revision(&self, variant: &str, rev: u64) -> Result<(), StoreError>;
+
+    // Returns the revision that was pending, if any
+    async fn clear_pending_revision(&self, variant: &str) -> Result<Option<u64>, StoreError>;
+}
+
+// RESUME_STORE is either "filesystem" (the default) or "postgres", which needs DATABASE_URL
+fn configured_store() -> Box<dyn ResumeStore> {
+    let store = get_env_variable("RESUME_STORE").unwrap_or_else(|| "filesystem".to_string());
+
+    match store.as_str() {
+        "postgres" => Box::new(PostgresResumeStore::new(
+            &get_env_variable("DATABASE_URL").expect("DATABASE_URL not set!"),
+        )),
+        "filesystem" => Box::new(FilesystemResumeStore::default()),
+        _ => panic!("Unknown resume store: {store}"),
+    }
+}
+
+pub fn resume_store() -> &'static dyn ResumeStore {
+    RESUME_STORE.as_ref()
+}
+
+// Runs the store's setup, e.g. database migrations. Has to be called before any resume is loaded
+pub async fn init_resume_store() -> Result<(), StoreError> {
+    let store = resume_store();
+
+    store.init().await?;
+
+    println!("Using the {} resume store", store.name());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use sqlx::{postgres::PgConnectOptions, Connection, PgConnection};
+
+    use super::*;
+
+    fn sample_resume(overview: &str) -> Resume {
+        serde_json::from_value(serde_json::json!({
+            "info": {
+                "name": "Jane Doe",
+                "phone": null,
+                "email": "jane@example.com",
+                "github": null,
+                "linkedin": null,
+                "website": null
+            },
+            "skills": {
+                "languages": ["Rust", "SQL"],
+                "frameworks": null,
+                "devops": null,
+                "database": ["Postgres"],
+                "dev_tools": null
+            },
+            "overview": overview,
+            "experience": [{
+                "company": "Acme",
+                "title": "Engineer",
+                "duration": "2020 - Present",
+                "location": null,
+                "desc": ["Built things"]
+            }],
+            "education": [{ "college": "State", "degree": "BS", "major": null }]
+        }))
+        .unwrap()
+    }
+
+    fn new_revision<'a>(resume: &'a Resume, pdf_bytes: Option<&'a [u8]>) -> NewRevision<'a> {
+        NewRevision {
+            resume,
+            pdf_bytes,
+            uploaded_at: 1_700_000_000,
+            sha256: "abc123".to_string(),
+            note: Some("test upload"),
+        }
+    }
+
+    fn temp_root() -> PathBuf {
+        std::env::temp_dir().join(format!("resume-store-test-{}", uuid::Uuid::new_v4()))
+    }
+
+    // Postgres tests run against a fresh database on the server in TEST_DATABASE_URL. They're
+    // ignored by default, run them with `cargo test --features ssr -- --include-ignored`
+    async fn postgres_store() -> (PostgresResumeStore, TestDatabase) {
+        let url = std::env::var("TEST_DATABASE_URL")
+            .expect("TEST_DATABASE_URL has to point at a Postgres server for the postgres tests");
+
+        let database = TestDatabase::create(&url).await;
+        let store = PostgresResumeStore::with_options(database.options.clone());
+        store.migrate().await.unwrap();
+
+        (store, database)
+    }
+
+    struct TestDatabase {
+        url: String,
+        name: String,
+        options: PgConnectOptions,
+    }
+
+    impl TestDatabase {
+        async fn create(url: &str) -> Self {
+            let name = format!("resume_store_test_{}", uuid::Uuid::new_v4().simple());
+
+            let mut con = PgConnection::connect(url).await.unwrap();
+            sqlx::query(&format!("CREATE DATABASE {name}"))
+                .execute(&mut con)
+                .await
+                .unwrap();
+
+            let options = url.parse::<PgConnectOptions>().unwrap().database(&name);
+
+            TestDatabase {
+                url: url.to_string(),
+                name,
+                options,
+            }
+        }
+
+        async fn drop(self) {
+            let mut con = PgConnection::connect(&self.url).await.unwrap();
+            sqlx::query(&format!("DROP DATABASE {} WITH (FORCE)", self.name))
+                .execute(&mut con)
+                .await
+                .unwrap();
+        }
+    }
+
+    async fn live_and_pending(store: &dyn ResumeStore) {
+        let live = sample_resume("Live");
+        let pending = sample_resume("Pending");
+
+        assert!(store.load_live("backend").await.is_err());
+
+        store.save_live("backend", &live).await.unwrap();
+        store.save_pending("backend", &pending).await.unwrap();
+
+        assert_eq!(store.load_live("backend").await.unwrap(), live);
+        assert_eq!(store.load_pending("backend").await.unwrap(), pending);
+        assert!(store
+            .live_variants()
+            .await
+            .unwrap()
+            .contains(&"backend".to_string()));
+
+        store.promote_pending("backend").await.unwrap();
+        assert_eq!(store.load_live("backend").await.unwrap(), pending);
+
+        store.save_pending("backend", &live).await.unwrap();
+        store.discard_pending("backend").await.unwrap();
+        assert!(store.load_pending("backend").await.is_err());
+        assert_eq!(store.load_live("backend").await.unwrap(), pending);
+    }
+
+    async fn revisions(store: &dyn ResumeStore) {
+        let first = sample_resume("First");
+        let second = sample_resume("Second");
+
+        assert_eq!(
+            store.load_history("frontend").await.unwrap().revisions,
+            vec![]
+        );
+        assert_eq!(
+            store.clear_pending_revision("frontend").await.unwrap(),
+            None
+        );
+
+        let revision = store
+            .add_revision("frontend", new_revision(&first, Some(b"%PDF-1")))
+            .await
+            .unwrap();
+        assert_eq!(revision.rev, 1);
+        assert!(revision.has_pdf);
+
+        store.set_live_revision("frontend", 1).await.unwrap();
+
+        let revision = store
+            .add_revision("frontend", new_revision(&second, None))
+            .await
+            .unwrap();
+        assert_eq!(revision.rev, 2);
+        assert!(!revision.has_pdf);
+        assert_eq!(revision.note.as_deref(), Some("test upload"));
+
+        let history = store.load_history("frontend").await.unwrap();
+        assert_eq!(history.live, Some(1));
+        assert_eq!(history.pending, Some(2));
+        assert_eq!(history.revisions.len(), 2);
+        assert_eq!(history.revisions[1], revision);
+        assert!(store
+            .known_variants()
+            .await
+            .unwrap()
+            .contains(&"frontend".to_string()));
+
+        assert_eq!(
+            store.load_revision("frontend", 1).await.unwrap(),
+            Some(first)
+        );
+        assert_eq!(
+            store.load_revision("frontend", 2).await.unwrap(),
+            Some(second)
+        );
+        assert_eq!(store.load_revision("frontend", 3).await.unwrap(), None);
+        assert_eq!(
+            store.load_revision_pdf("frontend", 1).await.unwrap(),
+            Some(b"%PDF-1".to_vec())
+        );
+        assert_eq!(store.load_revision_pdf("frontend", 2).await.unwrap(), None);
+
+        // Making the pending revision live means it's no longer pending
+        store.set_live_revision("frontend", 2).await.unwrap();
+        let history = store.load_history("frontend").await.unwrap();
+        assert_eq!((history.live, history.pending), (Some(2), None));
+
+        store
+            .add_revision("frontend", new_revision(&sample_resume("Third"), None))
+            .await
+            .unwrap();
+        assert_eq!(
+            store.clear_pending_revision("frontend").await.unwrap(),
+            Some(3)
+        );
+        assert_eq!(
+            store.clear_pending_revision("frontend").await.unwrap(),
+            None
+        );
+        assert_eq!(store.load_history("frontend").await.unwrap().live, Some(2));
+
+        // Revisions of other variants are numbered on their own
+        let revision = store
+            .add_revision("other", new_revision(&sample_resume("Other"), None))
+            .await
+            .unwrap();
+        assert_eq!(revision.rev, 1);
+    }
+
+    async fn run_suite(store: &dyn ResumeStore) {
+        live_and_pending(store).await;
+        revisions(store).await;
+    }
+
+    #[tokio::test]
+    async fn filesystem_store() {
+        let root = temp_root();
+
+        run_suite(&FilesystemResumeStore::new(&root)).await;
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres server in TEST_DATABASE_URL"]
+    async fn postgres_store_backend() {
+        let (store, database) = postgres_store().await;
+
+        run_suite(&store).await;
+
+        database.drop().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres server in TEST_DATABASE_URL"]
+    async fn imports_filesystem_store() {
+        let (store, database) = postgres_store().await;
+
+        let root = temp_root();
+        let source = FilesystemResumeStore::new(&root);
+        let first = sample_resume("First");
+        let second = sample_resume("Second");
+
+        source
+            .add_revision("default", new_revision(&first, Some(b"%PDF-1")))
+            .await
+            .unwrap();
+        source.set_live_revision("default", 1).await.unwrap();
+        source.save_live("default", &first).await.unwrap();
+        source
+            .add_revision("default", new_revision(&second, None))
+            .await
+            .unwrap();
+        source.save_pending("default", &second).await.unwrap();
+
+        // Like two instances starting at once, only one of them imports the variant
+        let other_instance = PostgresResumeStore::with_options(database.options.clone());
+        let (imported, other_imported) = tokio::join!(
+            store.import_from(&source),
+            other_instance.import_from(&source)
+        );
+        imported.unwrap();
+        other_imported.unwrap();
+
+        // A second run leaves the imported variant alone
+        source.discard_pending("default").await.unwrap();
+        store.import_from(&source).await.unwrap();
+
+        let history = store.load_history("default").await.unwrap();
+        assert_eq!(
+            history.revisions,
+            source.load_history("default").await.unwrap().revisions
+        );
+        assert_eq!((history.live, history.pending), (Some(1), Some(2)));
+        assert_eq!(store.load_live("default").await.unwrap(), first);
+        assert_eq!(store.load_pending("default").await.unwrap(), second);
+        assert_eq!(
+            store.load_revision_pdf("default", 1).await.unwrap(),
+            Some(b"%PDF-1".to_vec())
+        );
+
+        // Imported revision ids carry on where the filesystem store left off
+        let revision = store
+            .add_revision("default", new_revision(&sample_resume("Third"), None))
+            .await
+            .unwrap();
+        assert_eq!(revision.rev, 3);
+
+        std::fs::remove_dir_all(root).unwrap();
+        database.drop().await;
+    }
+}
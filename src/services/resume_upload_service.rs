@@ -0,0 +1,75 @@
+use lazy_static::lazy_static;
+use serde::Serialize;
+
+use crate::{server_functions::get_env_variable, services::resume_history_service::load_history};
+
+lazy_static! {
+    // Uploads larger than this are rejected while they are still streaming in
+    pub static ref RESUME_MAX_UPLOAD_BYTES: usize = get_env_variable("RESUME_MAX_UPLOAD_BYTES")
+        .and_then(|max_bytes| max_bytes.parse().ok())
+        .unwrap_or(10 * 1024 * 1024);
+}
+
+// Text fields like `variant` and `note` are short, anything longer is a bad request
+pub const MAX_TEXT_FIELD_BYTES: usize = 1024;
+
+type UploadError = Box<dyn std::error::Error + Send + Sync>;
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+// Looks at the file itself rather than the declared content type. Readers accept a header
+// anywhere in the first 1024 bytes, and the trailer has to be near the end of the file
+pub fn check_pdf(bytes: &[u8]) -> Result<(), &'static str> {
+    let head = &bytes[..bytes.len().min(1024)];
+    let tail = &bytes[bytes.len().saturating_sub(1024)..];
+
+    if !contains(head, b"%PDF-") {
+        return Err("File is not a PDF");
+    }
+
+    if !contains(tail, b"%%EOF") {
+        return Err("PDF is truncated, it has no %%EOF marker");
+    }
+
+    if !contains(tail, b"startxref") {
+        return Err("PDF has no cross-reference table");
+    }
+
+    if !contains(bytes, b" obj") {
+        return Err("PDF doesn't contain any objects");
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize, Debug)]
+pub struct DuplicateRevision {
+    pub revision: u64,
+    // Either "live" or "pending"
+    pub status: &'static str,
+}
+
+// Uploading the PDF that is already live or pending doesn't need to go through ParseCV again
+pub async fn find_duplicate_revision(
+    variant: &str,
+    sha256: &str,
+) -> Result<Option<DuplicateRevision>, UploadError> {
+    let history = load_history(variant).await?;
+
+    let duplicate = [(history.pending, "pending"), (history.live, "live")]
+        .into_iter()
+        .filter_map(|(rev, status)| Some((rev?, status)))
+        .find(|(rev, _)| {
+            history
+                .revisions
+                .iter()
+                .any(|revision| revision.rev == *rev && revision.sha256 == sha256)
+        })
+        .map(|(revision, status)| DuplicateRevision { revision, status });
+
+    Ok(duplicate)
+}
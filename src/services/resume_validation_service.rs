@@ -0,0 +1,161 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Resume;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ValidationIssue {
+    pub field: String,
+    pub message: String,
+}
+
+// Errors mean the resume would render incorrectly (or not at all) and shouldn't go live.
+// Warnings are worth a look but don't block approval
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub errors: Vec<ValidationIssue>,
+    pub warnings: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    fn error(&mut self, field: impl Into<String>, message: &str) {
+        self.errors.push(ValidationIssue {
+            field: field.into(),
+            message: message.to_string(),
+        });
+    }
+
+    fn warning(&mut self, field: impl Into<String>, message: &str) {
+        self.warnings.push(ValidationIssue {
+            field: field.into(),
+            message: message.to_string(),
+        });
+    }
+}
+
+fn is_blank(value: &Option<String>) -> bool {
+    value.as_deref().is_none_or(|value| value.trim().is_empty())
+}
+
+fn is_plausible_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+
+    !local.is_empty()
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !email.contains(char::is_whitespace)
+}
+
+fn is_plausible_phone(phone: &str) -> bool {
+    let digits = phone.chars().filter(char::is_ascii_digit).count();
+
+    (7..=15).contains(&digits)
+        && phone
+            .chars()
+            .all(|c| c.is_ascii_digit() || " ()+-.".contains(c))
+}
+
+fn is_plausible_url(url: &str) -> bool {
+    let host = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(url);
+    let host = host.split('/').next().unwrap_or_default();
+
+    host.contains('.') && !host.starts_with('.') && !url.contains(char::is_whitespace)
+}
+
+fn check_url(report: &mut ValidationReport, field: &str, value: &Option<String>, domain: &str) {
+    let Some(url) = value.as_deref() else {
+        return;
+    };
+
+    if !is_plausible_url(url) {
+        report.warning(format!("info.{field}"), "Does not look like a URL");
+    } else if !url.to_lowercase().contains(domain) {
+        report.warning(format!("info.{field}"), "Points somewhere unexpected");
+    }
+}
+
+pub fn validate_resume(resume: &Resume) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    let info = &resume.info;
+
+    if is_blank(&info.name) {
+        report.warning("info.name", "Missing applicant name");
+    }
+
+    if let Some(email) = info.email.as_deref() {
+        if !is_plausible_email(email) {
+            report.warning("info.email", "Does not look like an email address");
+        }
+    }
+
+    if let Some(phone) = info.phone.as_deref() {
+        if !is_plausible_phone(phone) {
+            report.warning("info.phone", "Does not look like a phone number");
+        }
+    }
+
+    check_url(&mut report, "github", &info.github, "github.com");
+    check_url(&mut report, "linkedin", &info.linkedin, "linkedin.com");
+    check_url(&mut report, "website", &info.website, ".");
+
+    if resume.overview.trim().is_empty() {
+        report.warning("overview", "Overview is empty");
+    }
+
+    if resume.experience.is_empty() {
+        report.error("experience", "No experience entries");
+    }
+
+    // The experience section only renders entries with all of these set
+    for (index, entry) in resume.experience.iter().enumerate() {
+        if is_blank(&entry.company) {
+            report.error(format!("experience[{index}].company"), "Missing company");
+        }
+        if is_blank(&entry.title) {
+            report.error(format!("experience[{index}].title"), "Missing title");
+        }
+        if is_blank(&entry.duration) {
+            report.error(format!("experience[{index}].duration"), "Missing duration");
+        }
+        if entry.desc.as_ref().is_none_or(|desc| desc.is_empty()) {
+            report.error(format!("experience[{index}].desc"), "Missing description");
+        }
+    }
+
+    let skills = &resume.skills;
+
+    for (category, values) in [
+        ("languages", &skills.languages),
+        ("frameworks", &skills.frameworks),
+        ("devops", &skills.devops),
+        ("database", &skills.database),
+        ("dev_tools", &skills.dev_tools),
+    ] {
+        match values {
+            None => report.error(format!("skills.{category}"), "Missing skill category"),
+            Some(values) if values.is_empty() => {
+                report.error(format!("skills.{category}"), "Skill category is empty")
+            }
+            Some(_) => {}
+        }
+    }
+
+    for (index, entry) in resume.education.iter().enumerate() {
+        if is_blank(&entry.college) {
+            report.warning(format!("education[{index}].college"), "Missing college");
+        }
+    }
+
+    report
+}
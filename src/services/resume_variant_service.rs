@@ -0,0 +1,128 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use lazy_static::lazy_static;
+
+use crate::server_functions::get_env_variable;
+
+lazy_static! {
+    static ref RESUME_FILE_NAME: String =
+        get_env_variable("RESUME_FILE_NAME").expect("RESUME_FILE_NAME not set!");
+    // Served when the site doesn't ask for a variant, and used by the unscoped internal routes
+    pub static ref DEFAULT_VARIANT: String =
+        get_env_variable("RESUME_DEFAULT_VARIANT").unwrap_or_else(|| "default".to_string());
+}
+
+const VARIANTS_DIR: &str = "resumes/variants";
+
+// Names that would make the internal routes ambiguous, e.g. /resume/history
+const RESERVED_NAMES: [&str; 11] = [
+    "update",
+    "approve",
+    "history",
+    "rollback",
+    "diff",
+    "overrides",
+    "import",
+    "export",
+    "jobs",
+    "pending",
+    "reject",
+];
+
+// Variant names end up in file paths, so only allow a conservative set of characters
+pub fn check_variant_name(variant: &str) -> Result<(), String> {
+    let valid = variant.len() <= 32
+        && variant.starts_with(|c: char| c.is_ascii_lowercase())
+        && variant
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+
+    if !valid {
+        return Err(format!(
+            "Invalid resume variant {variant:?}: use up to 32 lowercase letters, digits, '-' or '_', starting with a letter"
+        ));
+    }
+
+    if RESERVED_NAMES.contains(&variant) {
+        return Err(format!(
+            "{variant:?} is reserved and can't be used as a variant name"
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn is_default(variant: &str) -> bool {
+    variant == DEFAULT_VARIANT.as_str()
+}
+
+// The default variant keeps the original single-resume layout so existing deployments don't need
+// to move any files. Every other variant lives in its own directory under resumes/variants
+pub fn resume_dir(variant: &str) -> PathBuf {
+    if is_default(variant) {
+        PathBuf::from("resumes")
+    } else {
+        PathBuf::from(VARIANTS_DIR).join(variant)
+    }
+}
+
+pub fn parsed_resume_path(variant: &str) -> PathBuf {
+    resume_dir(variant).join("parsed_resume.json")
+}
+
+pub fn pending_resume_path(variant: &str) -> PathBuf {
+    resume_dir(variant).join("parsed_resume_pending.json")
+}
+
+pub fn overrides_path(variant: &str) -> PathBuf {
+    resume_dir(variant).join("overrides.json")
+}
+
+pub fn history_dir(variant: &str) -> PathBuf {
+    resume_dir(variant).join("history")
+}
+
+pub fn pdf_path(variant: &str) -> PathBuf {
+    if is_default(variant) {
+        PathBuf::from(format!("uploads/{}.pdf", RESUME_FILE_NAME.as_str()))
+    } else {
+        PathBuf::from(format!(
+            "uploads/{}_{variant}.pdf",
+            RESUME_FILE_NAME.as_str()
+        ))
+    }
+}
+
+// Every variant with a directory under `root`, including ones that have never been approved,
+// default first
+pub fn known_variants_in(root: &Path) -> Vec<String> {
+    let mut variants = vec![DEFAULT_VARIANT.to_string()];
+
+    let Ok(entries) = fs::read_dir(root.join(VARIANTS_DIR)) else {
+        return variants;
+    };
+
+    let mut others: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|variant| check_variant_name(variant).is_ok() && !is_default(variant))
+        .collect();
+    others.sort();
+
+    variants.extend(others);
+    variants
+}
+
+// Every variant that has a live resume on disk, default first
+pub fn stored_variants() -> Vec<String> {
+    stored_variants_in(Path::new(""))
+}
+
+pub fn stored_variants_in(root: &Path) -> Vec<String> {
+    known_variants_in(root)
+        .into_iter()
+        .filter(|variant| is_default(variant) || root.join(parsed_resume_path(variant)).is_file())
+        .collect()
+}
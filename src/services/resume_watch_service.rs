@@ -0,0 +1,188 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use lazy_static::lazy_static;
+
+use crate::{
+    server_functions::get_env_variable,
+    services::{
+        resume_parsing_service::load_resume,
+        resume_store_service::resume_store,
+        resume_validation_service::validate_resume,
+        resume_variant_service::{parsed_resume_path, pdf_path, stored_variants},
+    },
+    ResumeCache,
+};
+
+lazy_static! {
+    static ref RESUME_WATCH_ENABLED: bool = get_env_variable("RESUME_WATCH_ENABLED")
+        .map(|enabled| enabled != "false" && enabled != "0")
+        .unwrap_or(true);
+    static ref RESUME_WATCH_INTERVAL: Duration = Duration::from_millis(
+        get_env_variable("RESUME_WATCH_INTERVAL_MS")
+            .and_then(|interval| interval.parse().ok())
+            .unwrap_or(2000)
+    );
+    // How long a file has to stay unchanged before it is reloaded, so half written files and
+    // bursts of writes only trigger one reload
+    static ref RESUME_WATCH_DEBOUNCE: Duration = Duration::from_millis(
+        get_env_variable("RESUME_WATCH_DEBOUNCE_MS")
+            .and_then(|debounce| debounce.parse().ok())
+            .unwrap_or(1000)
+    );
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum WatchedKind {
+    Json,
+    Pdf,
+}
+
+// Modified time and size, None if the file doesn't exist
+type FileSignature = Option<(SystemTime, u64)>;
+
+struct WatchedFile {
+    variant: String,
+    kind: WatchedKind,
+    signature: FileSignature,
+    changed_at: Option<Instant>,
+}
+
+fn file_signature(path: &PathBuf) -> FileSignature {
+    let metadata = fs::metadata(path).ok()?;
+
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+// Only swaps the new resume in if it parses and passes validation, otherwise the cache keeps
+// serving the previous one
+async fn reload_resume_json(variant: &str, resume_cache: &ResumeCache) {
+    let resume = match load_resume(variant).await {
+        Ok(resume) => resume,
+        Err(err) => {
+            println!("Ignoring changed {variant} resume JSON, it could not be loaded: {err}");
+            return;
+        }
+    };
+
+    let validation = validate_resume(&resume);
+
+    if !validation.is_valid() {
+        println!(
+            "Ignoring changed {variant} resume JSON, it has {} validation error(s)",
+            validation.errors.len()
+        );
+        return;
+    }
+
+    let Ok(mut write_to_cache) = resume_cache.variants.write() else {
+        println!("Error establishing write lock on resume cache");
+        return;
+    };
+
+    // Approvals and rollbacks update the cache themselves before the watcher notices the file
+    if write_to_cache.get(variant) == Some(&resume) {
+        return;
+    }
+
+    write_to_cache.insert(variant.to_string(), resume);
+
+    println!("Reloaded {variant} resume from disk");
+}
+
+// The PDF is served straight from disk, so there is nothing to swap, but a file that isn't a
+// PDF is worth flagging
+fn check_resume_pdf(variant: &str, path: &PathBuf) {
+    match fs::read(path) {
+        Ok(bytes) if bytes.starts_with(b"%PDF-") => {
+            println!("Detected new {variant} resume PDF on disk")
+        }
+        Ok(_) => println!(
+            "Changed {variant} resume PDF {} doesn't look like a PDF",
+            path.display()
+        ),
+        Err(err) => println!("Could not read changed {variant} resume PDF: {err}"),
+    }
+}
+
+// Polls the live resume JSON and PDF of every variant, since files can be edited by hand or
+// replaced by a volume mount while the server is running
+pub async fn watch_resume_files(resume_cache: Arc<ResumeCache>) {
+    if !*RESUME_WATCH_ENABLED {
+        println!("Resume file watcher disabled");
+        return;
+    }
+
+    // The JSON on disk isn't what's served then, and changes to the store come through the API
+    if !resume_store().keeps_files() {
+        println!(
+            "Resume file watcher disabled, the {} store doesn't keep resumes in files",
+            resume_store().name()
+        );
+        return;
+    }
+
+    let mut watched: HashMap<PathBuf, WatchedFile> = HashMap::new();
+    let mut interval = tokio::time::interval(*RESUME_WATCH_INTERVAL);
+    let mut initialized = false;
+
+    loop {
+        interval.tick().await;
+
+        for variant in stored_variants() {
+            for (kind, path) in [
+                (WatchedKind::Json, parsed_resume_path(&variant)),
+                (WatchedKind::Pdf, pdf_path(&variant)),
+            ] {
+                let signature = file_signature(&path);
+
+                // Files already there on start-up were loaded by main, anything that shows up
+                // later counts as a change
+                watched.entry(path).or_insert_with(|| WatchedFile {
+                    variant: variant.clone(),
+                    kind,
+                    signature: if initialized { None } else { signature },
+                    changed_at: None,
+                });
+            }
+        }
+
+        initialized = true;
+
+        for (path, file) in watched.iter_mut() {
+            let signature = file_signature(path);
+
+            if signature != file.signature {
+                file.signature = signature;
+                file.changed_at = Some(Instant::now());
+                continue;
+            }
+
+            match file.changed_at {
+                Some(changed_at) if changed_at.elapsed() >= *RESUME_WATCH_DEBOUNCE => {
+                    file.changed_at = None;
+                }
+                _ => continue,
+            }
+
+            if signature.is_none() {
+                println!(
+                    "{} was removed, keeping the cached {} resume",
+                    path.display(),
+                    file.variant
+                );
+                continue;
+            }
+
+            match file.kind {
+                WatchedKind::Json => reload_resume_json(&file.variant, &resume_cache).await,
+                WatchedKind::Pdf => check_resume_pdf(&file.variant, path),
+            }
+        }
+    }
+}
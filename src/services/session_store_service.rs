@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use actix_session::storage::{
+    CookieSessionStore, LoadError, RedisSessionStore, SaveError, SessionKey, SessionStore,
+    UpdateError,
+};
+use actix_web::cookie::time::Duration;
+
+use crate::server_functions::get_env_variable;
+
+type SessionState = HashMap<String, String>;
+
+// Backs the session middleware. SESSION_STORE picks "redis" (the default) or "cookie", which keeps
+// the whole session in an encrypted cookie so no redis is needed
+pub enum SessionBackend {
+    Redis(Box<RedisSessionStore>),
+    Cookie(CookieSessionStore),
+}
+
+impl Clone for SessionBackend {
+    fn clone(&self) -> Self {
+        match self {
+            SessionBackend::Redis(store) => SessionBackend::Redis(store.clone()),
+            SessionBackend::Cookie(_) => SessionBackend::Cookie(CookieSessionStore::default()),
+        }
+    }
+}
+
+impl SessionBackend {
+    pub async fn configured(redis_connection_string: Option<&str>) -> Result<Self, anyhow::Error> {
+        let backend = match get_env_variable("SESSION_STORE").as_deref() {
+            None | Some("redis") => {
+                let connection_string = redis_connection_string.ok_or_else(|| {
+                    anyhow::anyhow!("SESSION_STORE=redis needs REDIS_CONNECTION_STRING")
+                })?;
+
+                SessionBackend::Redis(Box::new(RedisSessionStore::new(connection_string).await?))
+            }
+            Some("cookie") => SessionBackend::Cookie(CookieSessionStore::default()),
+            Some(store) => anyhow::bail!("Unknown SESSION_STORE: {store}"),
+        };
+
+        println!("Using the {} session store", backend.name());
+
+        Ok(backend)
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            SessionBackend::Redis(_) => "redis",
+            SessionBackend::Cookie(_) => "cookie",
+        }
+    }
+}
+
+impl SessionStore for SessionBackend {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        match self {
+            SessionBackend::Redis(store) => store.load(session_key).await,
+            SessionBackend::Cookie(store) => store.load(session_key).await,
+        }
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        match self {
+            SessionBackend::Redis(store) => store.save(session_state, ttl).await,
+            SessionBackend::Cookie(store) => store.save(session_state, ttl).await,
+        }
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        match self {
+            SessionBackend::Redis(store) => store.update(session_key, session_state, ttl).await,
+            SessionBackend::Cookie(store) => store.update(session_key, session_state, ttl).await,
+        }
+    }
+
+    async fn update_ttl(
+        &self,
+        session_key: &SessionKey,
+        ttl: &Duration,
+    ) -> Result<(), anyhow::Error> {
+        match self {
+            SessionBackend::Redis(store) => store.update_ttl(session_key, ttl).await,
+            SessionBackend::Cookie(store) => store.update_ttl(session_key, ttl).await,
+        }
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> Result<(), anyhow::Error> {
+        match self {
+            SessionBackend::Redis(store) => store.delete(session_key).await,
+            SessionBackend::Cookie(store) => store.delete(session_key).await,
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    oauth::SessionData,
+    server_functions::get_env_variable,
+    services::{
+        download_link_service::{DownloadLink, DownloadRecord, LinkClaim},
+        memory_token_store::MemoryTokenStore,
+        redis_repository::RedisRepository,
+    },
+};
+
+// Every collection whose entries expire, also the redis keys they're kept under
+// OAuth login states, by the time they expire
+pub const STATES: &str = "states";
+// Every link token, by the time it expires
+pub const LINKS: &str = "pdf_links";
+// Every link with stored details, by the time its details and download log can be dropped
+pub const LINK_RECORDS: &str = "pdf_link_records";
+// OAuth sessions. Redis expires them on its own, so only the memory store sweeps them
+pub const SESSIONS: &str = "sessions";
+
+// How long a stored session outlives its last save. It's also the identity middleware's login
+// deadline, so no login lasts longer than the session it was saved with
+pub const SESSION_TTL: u64 = 60 * 60 * 24 * 3;
+
+pub type TokenStoreError = Box<dyn std::error::Error + Send + Sync>;
+
+// Kept with a login's state until the auth server redirects back. States stored before these
+// details were added load as the default
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct LoginState {
+    // PKCE code verifier, when PKCE is turned on
+    pub code_verifier: Option<String>,
+    // Expected in the ID token, when logging in with OpenID Connect
+    pub nonce: Option<String>,
+    // The provider the login was started with, whose callback has to finish it
+    #[serde(default)]
+    pub provider: String,
+}
+
+// Where OAuth states, sessions and download links are kept
+#[async_trait]
+pub trait TokenStore: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn add_state(
+        &self,
+        state: &str,
+        login_state: &LoginState,
+        expires_at: u64,
+    ) -> Result<(), TokenStoreError>;
+
+    // States can only be used once, so they're removed whether they are still valid or not. None
+    // for unknown or expired states
+    async fn take_state(
+        &self,
+        state: &str,
+        now: u64,
+    ) -> Result<Option<LoginState>, TokenStoreError>;
+
+    // Sessions are keyed by SessionData::key and expire SESSION_TTL after they were saved
+    async fn save_session(&self, session_data: &SessionData) -> Result<(), TokenStoreError>;
+
+    // None for unknown or expired sessions
+    async fn load_session(&self, key: &str) -> Result<Option<SessionData>, TokenStoreError>;
+
+    // For sessions the provider won't refresh anymore
+    async fn delete_session(&self, key: &str) -> Result<(), TokenStoreError>;
+
+    // The token only becomes valid together with its details
+    async fn save_link(&self, link: &DownloadLink, keep_until: u64) -> Result<(), TokenStoreError>;
+
+    async fn load_link(&self, token: &str) -> Result<Option<DownloadLink>, TokenStoreError>;
+
+    // Checks that the link hasn't expired or run out of downloads and counts the download, all in
+    // one step so two concurrent downloads can't both get the last one
+    async fn claim_download(&self, token: &str, now: u64) -> Result<LinkClaim, TokenStoreError>;
+
+    async fn record_download(
+        &self,
+        token: &str,
+        record: &DownloadRecord,
+        keep_until: u64,
+    ) -> Result<(), TokenStoreError>;
+
+    // The download count and log of a link
+    async fn link_downloads(
+        &self,
+        token: &str,
+    ) -> Result<(u32, Vec<DownloadRecord>), TokenStoreError>;
+
+    // Tokens of every link whose details are still kept
+    async fn link_tokens(&self, now: u64) -> Result<Vec<String>, TokenStoreError>;
+
+    // Removes every entry that expired before now, returns how many went from each collection
+    async fn sweep_expired(&self, now: u64)
+        -> Result<BTreeMap<&'static str, u64>, TokenStoreError>;
+}
+
+// TOKEN_STORE picks the store, "redis" (the default) or "memory". The memory store needs no redis
+// but forgets everything on restart and isn't shared between instances
+pub fn configured_token_store(
+    redis: Option<&RedisRepository>,
+) -> Result<Arc<dyn TokenStore>, TokenStoreError> {
+    let store: Arc<dyn TokenStore> = match get_env_variable("TOKEN_STORE").as_deref() {
+        None | Some("redis") => Arc::new(
+            redis
+                .ok_or("TOKEN_STORE=redis needs REDIS_CONNECTION_STRING")?
+                .clone(),
+        ),
+        Some("memory") => Arc::new(MemoryTokenStore::default()),
+        Some(store) => return Err(format!("Unknown TOKEN_STORE: {store}").into()),
+    };
+
+    println!("Using the {} token store", store.name());
+
+    Ok(store)
+}